@@ -0,0 +1,281 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use usvg::{ImageKind, Options};
+
+use crate::hooks::Hook;
+use crate::HrefStringResolver;
+
+thread_local! {
+    static TENANT: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+    static EGRESS: Cell<Usage> = const { Cell::new(Usage::new()) };
+}
+
+/// Record that a request was sent on this thread.
+#[cfg(any(
+    test,
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "hyper"
+))]
+pub(crate) fn record_request() {
+    EGRESS.with(|egress| {
+        let mut usage = egress.get();
+        usage.requests += 1;
+        egress.set(usage);
+    });
+}
+
+/// Record that `bytes` bytes of a response body were downloaded on this thread.
+#[cfg(any(
+    test,
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "hyper"
+))]
+pub(crate) fn record_bytes(bytes: usize) {
+    EGRESS.with(|egress| {
+        let mut usage = egress.get();
+        usage.bytes += bytes as u64;
+        egress.set(usage);
+    });
+}
+
+/// Run `f`, returning the usage recorded on this thread while it ran, which is also added to the
+/// usage of any enclosing measurement.
+pub(crate) fn measure<R>(f: impl FnOnce() -> R) -> (R, Usage) {
+    let outer = EGRESS.with(|egress| egress.replace(Usage::new()));
+    let result = f();
+    let usage = EGRESS.with(|egress| {
+        let mut total = outer;
+        total.add(egress.get());
+        egress.replace(total)
+    });
+    (result, usage)
+}
+
+/// Run `f` with `tenant` as the tenant of the images fetched on the current thread, e.g. for one
+/// render.
+///
+/// The previous tenant is restored afterwards, even if `f` panics.
+///
+/// ```
+/// use usvg_remote_resolvers::accounting::{current_tenant, with_tenant};
+///
+/// with_tenant("customer-42", || {
+///     assert_eq!(current_tenant().as_deref(), Some("customer-42"));
+///     // let tree = usvg::Tree::from_str(svg, &options);
+/// });
+/// assert_eq!(current_tenant(), None);
+/// ```
+pub fn with_tenant<R>(tenant: impl Into<Arc<str>>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<str>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            TENANT.with(|tenant| *tenant.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(TENANT.with(|current| current.replace(Some(tenant.into()))));
+    f()
+}
+
+/// Get the tenant set with [`with_tenant`] on the current thread.
+pub fn current_tenant() -> Option<Arc<str>> {
+    TENANT.with(|tenant| tenant.borrow().clone())
+}
+
+/// Requests and downloaded bytes attributed to a tenant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of requests sent, including retries.
+    pub requests: u64,
+    /// The number of response body bytes downloaded.
+    pub bytes: u64,
+}
+
+impl Usage {
+    const fn new() -> Self {
+        Self {
+            requests: 0,
+            bytes: 0,
+        }
+    }
+
+    fn add(&mut self, other: Usage) {
+        self.requests += other.requests;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Totals of the [`Usage`] per tenant, filled by [`AccountingResolver::with_ledger`].
+///
+/// Clones share the same totals, so a clone can be kept to read them after the resolver is moved
+/// into the [`Options`]. Usage outside of [`with_tenant`] is attributed to the empty tenant `""`.
+#[derive(Debug, Clone, Default)]
+pub struct EgressLedger(Arc<Mutex<HashMap<String, Usage>>>);
+
+impl EgressLedger {
+    /// Create a new empty `EgressLedger`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the usage of `tenant` so far.
+    pub fn usage(&self, tenant: &str) -> Usage {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|usage| usage.get(tenant).copied())
+            .unwrap_or_default()
+    }
+
+    /// Take the usage of every tenant so far, e.g. at the end of a billing period, and start over.
+    pub fn take(&self) -> HashMap<String, Usage> {
+        self.0
+            .lock()
+            .map(|mut usage| std::mem::take(&mut *usage))
+            .unwrap_or_default()
+    }
+
+    fn record(&self, tenant: Option<&str>, usage: Usage) {
+        if let Ok(mut totals) = self.0.lock() {
+            totals
+                .entry(tenant.unwrap_or_default().to_string())
+                .or_default()
+                .add(usage);
+        }
+    }
+}
+
+type AccountingHook = Hook<dyn Fn(Option<&str>, &str, Usage) + Send + Sync>;
+
+/// A resolver that attributes the requests and bytes downloaded by the inner resolver to the
+/// tenant set with [`with_tenant`], so multi-tenant platforms can bill or limit image egress per
+/// customer.
+///
+/// The hook is called after every `href` that sent at least one request, with the tenant, the
+/// `href` and its [`Usage`]. Images served from a cache without a request are not reported.
+///
+/// The usage is reported by [`BlockingReqwestResolver`](crate::reqwest_blocking::BlockingReqwestResolver),
+/// [`CachedBlockingReqwestResolver`](crate::reqwest_blocking::CachedBlockingReqwestResolver)
+/// and [`ReqwestResolver`](crate::reqwest::ReqwestResolver), wherever they are in the chain of
+/// inner resolvers.
+///
+/// ```
+/// use usvg_remote_resolvers::accounting::{with_tenant, AccountingResolver, EgressLedger};
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let ledger = EgressLedger::new();
+/// let resolver = AccountingResolver::with_ledger(DefaultResolver, ledger.clone());
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+///
+/// with_tenant("customer-42", || {
+///     // let tree = usvg::Tree::from_str(svg, &options);
+/// });
+/// let usage = ledger.usage("customer-42");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AccountingResolver<T> {
+    inner: T,
+    hook: AccountingHook,
+}
+
+impl<T> AccountingResolver<T> {
+    /// Create a new `AccountingResolver` calling `hook` with the tenant, the `href` and its usage.
+    pub fn new(inner: T, hook: impl Fn(Option<&str>, &str, Usage) + Send + Sync + 'static) -> Self {
+        Self {
+            inner,
+            hook: Hook(Arc::new(hook)),
+        }
+    }
+
+    /// Create a new `AccountingResolver` adding the usage to `ledger`.
+    pub fn with_ledger(inner: T, ledger: EgressLedger) -> Self {
+        Self::new(inner, move |tenant, _, usage| ledger.record(tenant, usage))
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for AccountingResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let (kind, usage) = measure(|| self.inner.get_image_kind(href, options));
+        if usage.requests > 0 {
+            (self.hook)(current_tenant().as_deref(), href, usage);
+        }
+        kind
+    }
+}
+
+#[cfg(all(test, any(feature = "reqwest", feature = "reqwest_blocking")))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn attributes_usage_to_tenants() {
+        let body = include_bytes!("../test_data/gray.png");
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(body)
+            .create();
+        s.mock("GET", "/missing.png").with_status(404).create();
+
+        let ledger = EgressLedger::new();
+        let resolver = AccountingResolver::with_ledger(
+            crate::reqwest_blocking::BlockingReqwestResolver::default(),
+            ledger.clone(),
+        );
+        let options = Options::default();
+        with_tenant("a", || {
+            resolver.get_image_kind(&format!("{}/gray.png", s.url()), &options);
+            resolver.get_image_kind(&format!("{}/missing.png", s.url()), &options);
+        });
+        with_tenant("b", || {
+            resolver.get_image_kind(&format!("{}/gray.png", s.url()), &options);
+        });
+
+        assert_eq!(
+            ledger.usage("a"),
+            Usage {
+                requests: 2,
+                bytes: body.len() as u64,
+            }
+        );
+        assert_eq!(ledger.usage("b").requests, 1);
+        assert_eq!(ledger.take().len(), 2);
+        assert_eq!(ledger.usage("a"), Usage::default());
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[test]
+    fn unsent_requests_are_not_counted() {
+        let ledger = EgressLedger::new();
+        let resolver = AccountingResolver::with_ledger(
+            crate::reqwest::ReqwestResolver::default(),
+            ledger.clone(),
+        );
+        with_tenant("a", || {
+            // There is no runtime to send the request on.
+            assert!(resolver
+                .get_image_kind("https://example.com/gray.png", &Options::default())
+                .is_none());
+        });
+        assert_eq!(ledger.usage("a").requests, 0);
+    }
+}
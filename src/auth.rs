@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::utils::base64;
+
+/// A username and password for HTTP authentication.
+#[derive(Clone)]
+pub struct Credentials {
+    /// The username.
+    pub username: String,
+    /// The password.
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Credentials for HTTP Basic and Digest authentication, per host.
+///
+/// When a response is `401 Unauthorized` with a `WWW-Authenticate` challenge and the store has
+/// credentials for the host, the request is retried once with an `Authorization` header answering
+/// the challenge. Digest challenges with the `MD5`, `MD5-sess`, `SHA-256` and `SHA-256-sess`
+/// algorithms and the `auth` quality of protection are supported.
+///
+/// ```
+/// use usvg_remote_resolvers::auth::CredentialStore;
+///
+/// let store = CredentialStore::new().with_host("legacy.example.com", "user", "password");
+/// assert!(store.get("LEGACY.example.com").is_some());
+/// assert!(store.get("example.com").is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CredentialStore {
+    hosts: HashMap<String, Credentials>,
+}
+
+impl CredentialStore {
+    /// Create an empty `CredentialStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `username` and `password` for requests to `host`.
+    pub fn with_host(
+        mut self,
+        host: impl Into<String>,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.hosts.insert(
+            host.into().to_ascii_lowercase(),
+            Credentials {
+                username: username.into(),
+                password: password.into(),
+            },
+        );
+        self
+    }
+
+    /// Get the credentials for `host`.
+    pub fn get(&self, host: &str) -> Option<&Credentials> {
+        self.hosts.get(&host.to_ascii_lowercase())
+    }
+
+    /// Answer the challenges of a `401` response to `GET url` with the credentials for its host.
+    ///
+    /// Digest is preferred over Basic if both are offered.
+    #[cfg_attr(not(feature = "reqwest_blocking"), allow(dead_code))]
+    pub(crate) fn authorization<'c>(
+        &self,
+        url: &url::Url,
+        challenges: impl IntoIterator<Item = &'c str>,
+    ) -> Option<String> {
+        let credentials = self.get(url.host_str()?)?;
+        let challenges: Vec<_> = challenges
+            .into_iter()
+            .filter_map(Challenge::parse)
+            .collect();
+        let uri = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        challenges
+            .iter()
+            .find(|c| matches!(c, Challenge::Digest(_)))
+            .or_else(|| challenges.first())
+            .and_then(|challenge| challenge.answer(credentials, &uri))
+    }
+}
+
+#[derive(Debug)]
+enum Challenge {
+    Basic,
+    Digest(HashMap<String, String>),
+}
+
+impl Challenge {
+    fn parse(header: &str) -> Option<Self> {
+        let header = header.trim();
+        let (scheme, params) = header.split_once(' ').unwrap_or((header, ""));
+        if scheme.eq_ignore_ascii_case("basic") {
+            Some(Self::Basic)
+        } else if scheme.eq_ignore_ascii_case("digest") {
+            Some(Self::Digest(parse_params(params)))
+        } else {
+            None
+        }
+    }
+
+    fn answer(&self, credentials: &Credentials, uri: &str) -> Option<String> {
+        let params = match self {
+            Self::Basic => {
+                let token =
+                    base64(format!("{}:{}", credentials.username, credentials.password).as_bytes());
+                return Some(format!("Basic {token}"));
+            }
+            Self::Digest(params) => params,
+        };
+        let realm = params.get("realm").map(String::as_str).unwrap_or_default();
+        let nonce = params.get("nonce")?;
+        let algorithm = params.get("algorithm").map(String::as_str).unwrap_or("MD5");
+        let hash: fn(&str) -> String = match algorithm.to_ascii_uppercase().as_str() {
+            "MD5" | "MD5-SESS" => |s| hex(&<md5::Md5 as md5::Digest>::digest(s)),
+            "SHA-256" | "SHA-256-SESS" => |s| hex(&<sha2::Sha256 as sha2::Digest>::digest(s)),
+            _ => return None,
+        };
+        let qop = match params.get("qop") {
+            Some(qop) if qop.split(',').any(|q| q.trim() == "auth") => Some("auth"),
+            Some(_) => return None,
+            None => None,
+        };
+        let cnonce = cnonce();
+        let nc = "00000001";
+
+        let mut ha1 = hash(&format!(
+            "{}:{}:{}",
+            credentials.username, realm, credentials.password
+        ));
+        if algorithm.to_ascii_uppercase().ends_with("-SESS") {
+            ha1 = hash(&format!("{ha1}:{nonce}:{cnonce}"));
+        }
+        let ha2 = hash(&format!("GET:{uri}"));
+        let response = match qop {
+            Some(qop) => hash(&format!("{ha1}:{nonce}:{nc}:{cnonce}:{qop}:{ha2}")),
+            None => hash(&format!("{ha1}:{nonce}:{ha2}")),
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{realm}\", nonce=\"{nonce}\", uri=\"{uri}\", \
+             algorithm={algorithm}, response=\"{response}\"",
+            credentials.username
+        );
+        if let Some(qop) = qop {
+            header.push_str(&format!(", qop={qop}, nc={nc}, cnonce=\"{cnonce}\""));
+        }
+        if let Some(opaque) = params.get("opaque") {
+            header.push_str(&format!(", opaque=\"{opaque}\""));
+        }
+        Some(header)
+    }
+}
+
+/// Parse the comma separated `name=value` parameters of a challenge, where values may be quoted.
+fn parse_params(params: &str) -> HashMap<String, String> {
+    let mut parsed = HashMap::new();
+    let mut rest = params.trim();
+    while let Some((name, after)) = rest.split_once('=') {
+        let name = name
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .to_ascii_lowercase();
+        let after = after.trim_start();
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            None => {
+                let end = after.find(',').unwrap_or(after.len());
+                (after[..end].trim(), &after[end..])
+            }
+        };
+        parsed.insert(name, value.to_string());
+        rest = after.trim_start().trim_start_matches(',');
+    }
+    parsed
+}
+
+fn cnonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let seed = format!(
+        "{nanos}:{}:{}",
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        std::process::id()
+    );
+    hex(&<md5::Md5 as md5::Digest>::digest(seed))[..16].to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic() {
+        let store = CredentialStore::new().with_host("example.com", "Aladdin", "open sesame");
+        let url = url::Url::parse("https://example.com/a.png").unwrap();
+        assert_eq!(
+            store
+                .authorization(&url, ["Basic realm=\"assets\""])
+                .as_deref(),
+            Some("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==")
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+    }
+
+    #[test]
+    fn digest_rfc2617_example() {
+        let params = parse_params(
+            r#"realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        );
+        let credentials = Credentials {
+            username: "Mufasa".to_string(),
+            password: "Circle Of Life".to_string(),
+        };
+        let header = Challenge::Digest(params)
+            .answer(&credentials, "/dir/index.html")
+            .unwrap();
+        assert!(header.starts_with("Digest username=\"Mufasa\", realm=\"testrealm@host.com\""));
+        assert!(header.contains("qop=auth, nc=00000001"));
+        assert!(header.ends_with("opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+
+        let params = parse_params(
+            r#"realm="testrealm@host.com", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093""#,
+        );
+        let header = Challenge::Digest(params)
+            .answer(&credentials, "/dir/index.html")
+            .unwrap();
+        // RFC 2069 style response without qop.
+        assert!(header.contains("response=\"670fd8c2df070c60b045671b8b24ff02\""));
+    }
+}
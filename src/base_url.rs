@@ -0,0 +1,123 @@
+use std::borrow::Cow;
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// A resolver that joins relative `href`s (`images/logo.png`, `../a.png`, `/assets/x.svg`)
+/// against a base URL before passing them to the inner resolver.
+///
+/// SVG documents taken from web pages usually reference their images relative to the page, which
+/// the HTTP resolvers don't target on their own. Absolute `href`s are passed on unchanged, and
+/// malformed ones, or relative ones with a base like `data:` that they can't be joined to, are
+/// not targets.
+///
+/// ```
+/// use usvg_remote_resolvers::base_url::BaseUrlResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let base = url::Url::parse("https://example.com/blog/post.html").unwrap();
+/// let resolver = BaseUrlResolver::new(DefaultResolver, base);
+/// assert_eq!(resolver.join("../logo.png").as_deref(), Some("https://example.com/logo.png"));
+/// assert_eq!(resolver.join("/x.svg").as_deref(), Some("https://example.com/x.svg"));
+/// assert_eq!(resolver.join("s3://bucket/a.png").as_deref(), Some("s3://bucket/a.png"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BaseUrlResolver<T> {
+    inner: T,
+    base: url::Url,
+}
+
+impl<T> BaseUrlResolver<T> {
+    /// Create a new `BaseUrlResolver` joining relative `href`s against `base`.
+    pub fn new(inner: T, base: url::Url) -> Self {
+        Self { inner, base }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the base URL of this resolver.
+    pub fn base(&self) -> &url::Url {
+        &self.base
+    }
+
+    /// Get the `href` passed to the inner resolver for `href`, or `None` if it can't be joined.
+    pub fn join<'h>(&self, href: &'h str) -> Option<Cow<'h, str>> {
+        join(&self.base, href)
+    }
+}
+
+/// Join `href` against `base` if it is relative, or `None` if it can't be joined.
+fn join<'h>(base: &url::Url, href: &'h str) -> Option<Cow<'h, str>> {
+    match url::Url::parse(href) {
+        Ok(_) => Some(Cow::Borrowed(href)),
+        Err(url::ParseError::RelativeUrlWithoutBase) => {
+            Some(Cow::Owned(base.join(href).ok()?.into()))
+        }
+        Err(_) => None,
+    }
+}
+
+/// Create a copy of `options` that joins relative `href`s against `base` before resolving them,
+/// for parsing an SVG image fetched from `base`.
+pub(crate) fn nested_options<'b>(options: &'b Options, base: url::Url) -> Options<'b> {
+    let mut nested = crate::utils::borrow_options(options);
+    nested.image_href_resolver.resolve_string = Box::new(move |href, opts| {
+        let href = join(&base, href).unwrap_or(Cow::Borrowed(href));
+        (options.image_href_resolver.resolve_string)(&href, opts)
+    });
+    nested
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for BaseUrlResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.join(href)
+            .is_some_and(|href| self.inner.is_target(&href))
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let Some(joined) = self.join(href) else {
+            crate::utils::log_warn!(
+                "cannot join '{}' to the base URL",
+                crate::redact::redact(href)
+            );
+            return None;
+        };
+        self.inner.get_image_kind(&joined, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryResolver;
+
+    #[test]
+    fn joins_relative_hrefs() {
+        let inner = MemoryResolver::new()
+            .insert_png(
+                "https://example.com/assets/gray.png",
+                include_bytes!("../test_data/gray.png"),
+            )
+            .insert_png(
+                "https://cdn.example.com/gray.png",
+                include_bytes!("../test_data/gray.png"),
+            );
+        let base = url::Url::parse("https://example.com/pages/index.html").unwrap();
+        let resolver = BaseUrlResolver::new(inner, base);
+        let options = Options::default();
+
+        for href in [
+            "../assets/gray.png",
+            "/assets/gray.png",
+            "https://cdn.example.com/gray.png",
+        ] {
+            assert!(resolver.is_target(href), "{href}");
+            assert!(resolver.get_image_kind(href, &options).is_some(), "{href}");
+        }
+        assert!(!resolver.is_target("gray.png"));
+        assert!(!resolver.is_target("http://[::1"));
+    }
+}
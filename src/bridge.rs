@@ -0,0 +1,62 @@
+use std::future::Future;
+
+/// A tokio runtime owned by a resolver, for driving async clients from synchronous callers.
+///
+/// Futures are spawned onto the runtime's own worker thread and waited for with a channel, so
+/// [`run`](`Self::run`) works both outside of any runtime and from inside another runtime, where
+/// `block_on` would panic. The runtime is shut down in the background when dropped, which is also
+/// allowed from async contexts.
+#[derive(Debug)]
+pub(crate) struct BackgroundRuntime(Option<tokio::runtime::Runtime>);
+
+impl BackgroundRuntime {
+    /// Start a runtime with a single worker thread.
+    pub(crate) fn new() -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("usvg-remote-resolvers")
+            .enable_all()
+            .build()?;
+        Ok(Self(Some(runtime)))
+    }
+
+    /// Run `future` on the runtime and block the current thread until it completes.
+    ///
+    /// Returns `None` if the future panicked.
+    pub(crate) fn run<F>(&self, future: F) -> Option<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        self.0.as_ref()?.spawn(async move {
+            let _ = tx.send(future.await);
+        });
+        rx.recv().ok()
+    }
+}
+
+impl Drop for BackgroundRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_outside_of_runtime() {
+        let runtime = BackgroundRuntime::new().unwrap();
+        assert_eq!(runtime.run(async { 1 + 1 }), Some(2));
+    }
+
+    #[tokio::test]
+    async fn runs_inside_current_thread_runtime() {
+        let runtime = BackgroundRuntime::new().unwrap();
+        assert_eq!(runtime.run(async { 1 + 1 }), Some(2));
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// A budget of failed attempts shared by the resolvers of a render.
+///
+/// Slow failures add up: a document with a hundred broken images can take minutes to render if
+/// every fetch waits for its timeout. Once the budget is spent, [`BudgetResolver`] returns `None`
+/// without trying, so the rest of the parse finishes quickly. Clones share the budget.
+///
+/// Use a new budget for every render, or [`reset`](`Self::reset`) it between renders.
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    limit: usize,
+    spent: Arc<AtomicUsize>,
+}
+
+impl RetryBudget {
+    /// Create a new `RetryBudget` allowing `limit` failed attempts.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            spent: Arc::default(),
+        }
+    }
+
+    /// Spend one attempt, returning `false` if the budget was already exhausted.
+    pub fn try_spend(&self) -> bool {
+        self.spent
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |spent| {
+                (spent < self.limit).then_some(spent + 1)
+            })
+            .is_ok()
+    }
+
+    /// The number of attempts left.
+    pub fn remaining(&self) -> usize {
+        self.limit.saturating_sub(self.spent.load(Ordering::SeqCst))
+    }
+
+    /// Returns `true` if no attempts are left.
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Restore the full budget.
+    pub fn reset(&self) {
+        self.spent.store(0, Ordering::SeqCst);
+    }
+}
+
+/// A resolver that spends a [`RetryBudget`] on every failure of the inner resolver, and stops
+/// calling it once the budget is exhausted.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+/// use usvg_remote_resolvers::budget::{BudgetResolver, RetryBudget};
+///
+/// let budget = RetryBudget::new(10);
+/// let resolver = BudgetResolver::new(DefaultResolver, budget.clone());
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// // Parse the document, then check whether images were skipped.
+/// let skipped = budget.is_exhausted();
+/// ```
+#[derive(Debug, Clone)]
+pub struct BudgetResolver<T> {
+    inner: T,
+    budget: RetryBudget,
+}
+
+impl<T> BudgetResolver<T> {
+    /// Create a new `BudgetResolver` spending `budget`.
+    pub fn new(inner: T, budget: RetryBudget) -> Self {
+        Self { inner, budget }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the budget of this resolver.
+    pub fn budget(&self) -> &RetryBudget {
+        &self.budget
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for BudgetResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        if self.budget.is_exhausted() {
+            crate::utils::log_warn!(
+                "failure budget exhausted; skipping '{}'",
+                crate::redact::redact(href)
+            );
+            return None;
+        }
+        let kind = self.inner.get_image_kind(href, options);
+        if kind.is_none() {
+            self.budget.try_spend();
+        }
+        kind
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountingResolver(AtomicUsize);
+
+    impl HrefStringResolver<'_> for CountingResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            crate::DefaultResolver.get_image_kind(href, options)
+        }
+    }
+
+    #[test]
+    fn stops_after_budget() {
+        let budget = RetryBudget::new(2);
+        let resolver = BudgetResolver::new(CountingResolver(AtomicUsize::new(0)), budget.clone());
+        let options = Options::default();
+
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_some());
+        for _ in 0..4 {
+            assert!(resolver
+                .get_image_kind("./test_data/missing.png", &options)
+                .is_none());
+        }
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 3);
+        assert!(budget.is_exhausted());
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_none());
+
+        budget.reset();
+        assert_eq!(budget.remaining(), 2);
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_some());
+    }
+}
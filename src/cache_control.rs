@@ -0,0 +1,119 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+thread_local! {
+    static CURRENT: Cell<RequestCacheControl> = const { Cell::new(RequestCacheControl::new()) };
+}
+
+/// `Cache-Control` directives sent with image requests during a render.
+///
+/// The directives are set for the duration of a closure with [`scope`](`Self::scope`), so the
+/// same resolver can force fresh assets for a preview render and prefer cached ones for a batch
+/// render. They apply to requests made on the current thread, which is where usvg calls the
+/// resolvers while parsing.
+///
+/// The HTTP resolvers send the directives as a `Cache-Control` request header, and
+/// [`CachedBlockingReqwestResolver`](`crate::reqwest_blocking::CachedBlockingReqwestResolver`)
+/// also applies them to its own cache.
+///
+/// ```
+/// use std::time::Duration;
+/// use usvg_remote_resolvers::cache_control::RequestCacheControl;
+///
+/// let preview = RequestCacheControl::new().with_no_cache();
+/// preview.scope(|| {
+///     assert_eq!(RequestCacheControl::current().header_value().as_deref(), Some("no-cache"));
+///     // let tree = usvg::Tree::from_str(svg, &options);
+/// });
+///
+/// let batch = RequestCacheControl::new().with_max_stale(Duration::from_secs(3600));
+/// assert_eq!(batch.header_value().as_deref(), Some("max-stale=3600"));
+/// assert_eq!(RequestCacheControl::current(), RequestCacheControl::new());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestCacheControl {
+    no_cache: bool,
+    max_stale: Option<Duration>,
+}
+
+impl RequestCacheControl {
+    /// Create a new `RequestCacheControl` without any directives.
+    pub const fn new() -> Self {
+        Self {
+            no_cache: false,
+            max_stale: None,
+        }
+    }
+
+    /// Send `no-cache`, forcing cached responses to be revalidated with the origin.
+    pub fn with_no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Send `max-stale`, accepting cached responses that have been stale for at most `max_stale`.
+    pub fn with_max_stale(mut self, max_stale: Duration) -> Self {
+        self.max_stale = Some(max_stale);
+        self
+    }
+
+    /// Whether `no-cache` is set.
+    pub fn no_cache(&self) -> bool {
+        self.no_cache
+    }
+
+    /// The `max-stale` limit, if set.
+    pub fn max_stale(&self) -> Option<Duration> {
+        self.max_stale
+    }
+
+    /// The value of the `Cache-Control` request header, or `None` if there are no directives.
+    pub fn header_value(&self) -> Option<String> {
+        let mut directives = Vec::new();
+        if self.no_cache {
+            directives.push("no-cache".to_string());
+        }
+        if let Some(max_stale) = self.max_stale {
+            directives.push(format!("max-stale={}", max_stale.as_secs()));
+        }
+        (!directives.is_empty()).then(|| directives.join(", "))
+    }
+
+    /// Get the directives of the current thread.
+    pub fn current() -> Self {
+        CURRENT.with(Cell::get)
+    }
+
+    /// Run `f` with these directives set for the current thread.
+    ///
+    /// The previous directives are restored afterwards, even if `f` panics.
+    pub fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        struct Restore(RequestCacheControl);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.set(self.0));
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.replace(self)));
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_restores() {
+        let outer = RequestCacheControl::new().with_max_stale(Duration::from_secs(60));
+        outer.scope(|| {
+            RequestCacheControl::new().with_no_cache().scope(|| {
+                assert!(RequestCacheControl::current().no_cache());
+            });
+            assert_eq!(RequestCacheControl::current(), outer);
+        });
+        assert_eq!(RequestCacheControl::current(), RequestCacheControl::new());
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use usvg::{ImageKind, Options};
+
+use crate::utils::ImageKindTypes;
+use crate::HrefStringResolver;
+
+const SCHEME: &str = "cas://";
+
+/// A store of content-addressed blobs used by [`CasResolver`].
+///
+/// Implement this for object stores; [`DirCasStore`] reads blobs from a local directory.
+pub trait CasStore: Send + Sync {
+    /// Get the blob with the given `digest`, a lowercase hex string, computed with `algorithm`
+    /// (e.g. `"sha256"`).
+    fn get(&self, algorithm: &str, digest: &str) -> Option<Vec<u8>>;
+}
+
+impl<T: CasStore + ?Sized> CasStore for Arc<T> {
+    fn get(&self, algorithm: &str, digest: &str) -> Option<Vec<u8>> {
+        (**self).get(algorithm, digest)
+    }
+}
+
+/// A [`CasStore`] reading blobs from `<dir>/<algorithm>/<digest>`.
+#[derive(Debug, Clone)]
+pub struct DirCasStore {
+    dir: PathBuf,
+}
+
+impl DirCasStore {
+    /// Create a new `DirCasStore` reading blobs from `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl CasStore for DirCasStore {
+    fn get(&self, algorithm: &str, digest: &str) -> Option<Vec<u8>> {
+        let path = self.dir.join(algorithm).join(digest);
+        match std::fs::read(&path) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                crate::utils::log_warn!("failed to read '{}': {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// A resolver for content-addressed `href`s like `cas://sha256/<digest>`.
+///
+/// The blob is loaded from a [`CasStore`] and its SHA-256 digest is verified before it is used.
+/// The image type is taken from an optional file extension after the digest
+/// (`cas://sha256/<digest>.png`), or detected from the content.
+///
+/// ```
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::cas::{CasResolver, DirCasStore};
+///
+/// let resolver = CasResolver::new(DirCasStore::new("/var/lib/assets"));
+/// assert!(resolver.is_target(
+///     "cas://sha256/e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+/// ));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CasResolver<S> {
+    store: S,
+}
+
+impl<S: CasStore> CasResolver<S> {
+    /// Create a new `CasResolver` loading blobs from `store`.
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    /// Get the underlying store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Load and verify the blob for `href`.
+    fn load<'h>(&self, href: &'h str) -> Option<(Vec<u8>, Option<&'h str>)> {
+        let path = href.get(SCHEME.len()..)?;
+        let (algorithm, name) = path.split_once('/')?;
+        let (digest, ext) = match name.split_once('.') {
+            Some((digest, ext)) => (digest, Some(ext)),
+            None => (name, None),
+        };
+        if !algorithm.eq_ignore_ascii_case("sha256") {
+            crate::utils::log_warn!("unsupported digest algorithm for '{}'", href);
+            return None;
+        }
+        // Validated before touching the store, so the digest is also a safe file name.
+        if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            crate::utils::log_warn!("invalid digest in '{}'", href);
+            return None;
+        }
+        let digest = digest.to_ascii_lowercase();
+        let data = self.store.get("sha256", &digest)?;
+        if hex(&Sha256::digest(&data)) != digest {
+            crate::utils::log_warn!("digest mismatch for '{}'", href);
+            return None;
+        }
+        Some((data, ext))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl<S: CasStore> HrefStringResolver<'_> for CasResolver<S> {
+    fn is_target(&self, href: &str) -> bool {
+        href.get(..SCHEME.len())
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let (data, ext) = self.load(href)?;
+        let image_type = match ext {
+            Some(ext) => ImageKindTypes::get_image_type(None, &format!(".{ext}")),
+            None => ImageKindTypes::from_bytes(&data),
+        };
+        let Some(image_type) = image_type else {
+            crate::utils::log_warn!("unsupported image type for '{}'", href);
+            return None;
+        };
+        image_type.into_image_kind(data.into(), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_png_href() -> String {
+        let data = std::fs::read("./test_data/gray.png").unwrap();
+        format!("cas://sha256/{}", hex(&Sha256::digest(&data)))
+    }
+
+    struct OneBlob(Vec<u8>);
+
+    impl CasStore for OneBlob {
+        fn get(&self, _: &str, _: &str) -> Option<Vec<u8>> {
+            Some(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn resolves_verified_blob() {
+        let dir = std::env::temp_dir().join("usvg-remote-resolvers-cas");
+        let href = gray_png_href();
+        let digest = href.rsplit('/').next().unwrap();
+        std::fs::create_dir_all(dir.join("sha256")).unwrap();
+        std::fs::copy("./test_data/gray.png", dir.join("sha256").join(digest)).unwrap();
+
+        let resolver = CasResolver::new(DirCasStore::new(&dir));
+        assert!(resolver.is_target(&href));
+        let options = Options::default();
+        assert!(matches!(
+            resolver.get_image_kind(&href, &options),
+            Some(ImageKind::PNG(_))
+        ));
+        assert!(matches!(
+            resolver.get_image_kind(&format!("{href}.png"), &options),
+            Some(ImageKind::PNG(_))
+        ));
+        assert!(resolver
+            .get_image_kind("cas://sha256/../gray.png", &options)
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_digest_mismatch() {
+        let resolver = CasResolver::new(OneBlob(b"not the image".to_vec()));
+        assert!(resolver
+            .get_image_kind(&gray_png_href(), &Options::default())
+            .is_none());
+    }
+}
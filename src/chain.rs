@@ -0,0 +1,114 @@
+use usvg::{ImageKind, Options};
+
+use crate::explain::{Outcome, ResolutionTrace};
+use crate::HrefStringResolver;
+
+type BoxedResolver<'a> = Box<dyn HrefStringResolver<'a> + 'a>;
+
+/// A resolver that tries any number of resolvers in order, and uses the first one that targets the
+/// `href` and resolves it.
+///
+/// This behaves like nested [`FallbackResolver`](crate::FallbackResolver)s, without their nested
+/// types, so the chain can be built at runtime.
+///
+/// ```
+/// use usvg_remote_resolvers::chain::ChainResolver;
+/// use usvg_remote_resolvers::memory::MemoryResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, FileResolver, HrefStringResolver};
+///
+/// let resolver = ChainResolver::new()
+///     .push(MemoryResolver::new())
+///     .push(FileResolver::new())
+///     .push(DefaultResolver);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Default)]
+pub struct ChainResolver<'a> {
+    resolvers: Vec<BoxedResolver<'a>>,
+}
+
+impl std::fmt::Debug for ChainResolver<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChainResolver")
+            .field("resolvers", &self.resolvers.len())
+            .finish()
+    }
+}
+
+impl<'a> ChainResolver<'a> {
+    /// Create a new empty `ChainResolver`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Try `resolver` after the resolvers pushed before.
+    pub fn push(mut self, resolver: impl HrefStringResolver<'a> + 'a) -> Self {
+        self.resolvers.push(Box::new(resolver));
+        self
+    }
+
+    /// Get the number of resolvers in the chain.
+    pub fn len(&self) -> usize {
+        self.resolvers.len()
+    }
+
+    /// Returns `true` if there are no resolvers in the chain.
+    pub fn is_empty(&self) -> bool {
+        self.resolvers.is_empty()
+    }
+}
+
+impl<'a> HrefStringResolver<'a> for ChainResolver<'a> {
+    fn is_target(&self, href: &str) -> bool {
+        self.resolvers.iter().any(|r| r.is_target(href))
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        self.resolvers
+            .iter()
+            .filter(|r| r.is_target(href))
+            .find_map(|r| r.get_image_kind(href, options))
+    }
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        let outcome = if self.is_target(trace.href()) {
+            Outcome::Matched
+        } else {
+            Outcome::NotTarget
+        };
+        trace.push::<Self>(outcome);
+        trace.nested(|trace| {
+            for resolver in &self.resolvers {
+                resolver.explain_into(trace);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryResolver;
+
+    #[test]
+    fn first_success_wins() {
+        let gray = include_bytes!("../test_data/gray.png");
+        let resolver = ChainResolver::new()
+            .push(MemoryResolver::new().insert_svg_str("a.svg", "not an svg"))
+            .push(MemoryResolver::new().insert_png("b.png", gray))
+            .push(MemoryResolver::new().insert_gif("a.svg", Vec::new()))
+            .push(MemoryResolver::new().insert_jpeg("b.png", Vec::new()));
+        let options = Options::default();
+
+        assert!(matches!(
+            resolver.get_image_kind("a.svg", &options),
+            Some(ImageKind::GIF(_))
+        ));
+        assert!(matches!(
+            resolver.get_image_kind("b.png", &options),
+            Some(ImageKind::PNG(_))
+        ));
+        assert!(!resolver.is_target("c.png"));
+        assert!(resolver.get_image_kind("c.png", &options).is_none());
+        assert_eq!(resolver.len(), 4);
+    }
+}
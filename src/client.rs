@@ -0,0 +1,732 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+#[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+use std::sync::Weak;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crate::target::TargetFilter;
+
+/// The number of redirects reqwest follows by default.
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Which IP address families are used to connect to a host.
+///
+/// reqwest connects to the addresses of the first family in the resolved list first, and starts
+/// connecting to the other family if that has not succeeded within a short delay (300ms), in the
+/// happy-eyeballs style. Preferring a family moves it to the front, so a blackholed IPv6 route
+/// only delays a fetch by that delay instead of stalling it until the connect timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AddressFamily {
+    /// Use the addresses in the order returned by the system resolver.
+    #[default]
+    Any,
+    /// Try IPv4 addresses first and fall back to IPv6.
+    PreferIpv4,
+    /// Try IPv6 addresses first and fall back to IPv4.
+    PreferIpv6,
+    /// Only connect to IPv4 addresses.
+    Ipv4Only,
+    /// Only connect to IPv6 addresses.
+    Ipv6Only,
+}
+
+impl AddressFamily {
+    fn sort(self, addrs: &mut Vec<SocketAddr>) {
+        match self {
+            Self::Any => {}
+            Self::PreferIpv4 => addrs.sort_by_key(|a| a.is_ipv6()),
+            Self::PreferIpv6 => addrs.sort_by_key(|a| a.is_ipv4()),
+            Self::Ipv4Only => addrs.retain(|a| a.is_ipv4()),
+            Self::Ipv6Only => addrs.retain(|a| a.is_ipv6()),
+        }
+    }
+}
+
+/// The address each host was pinned to by a client built with
+/// [`ClientConfig::with_dns_pins`].
+///
+/// The first lookup of a host pins it to the first address of the result, and every later request
+/// to that host connects to the same address, so a document can't mix images from different edge
+/// nodes of a round-robin CDN. Clones share the pins.
+///
+/// Use a new `DnsPins` for every render, or [`clear`](`Self::clear`) them between renders.
+/// Connections that are still pooled keep their address, so pair this with
+/// [`with_pool_max_idle_per_host(0)`](`ClientConfig::with_pool_max_idle_per_host`) when clearing
+/// the pins of a long-lived client.
+#[derive(Debug, Clone, Default)]
+pub struct DnsPins(Arc<Mutex<HashMap<String, IpAddr>>>);
+
+impl DnsPins {
+    /// Create new empty `DnsPins`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the address `host` is pinned to.
+    pub fn get(&self, host: &str) -> Option<IpAddr> {
+        self.lock().get(host).copied()
+    }
+
+    /// Remove all pins, so the next lookup of every host pins it again.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, IpAddr>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Pin `host` to the first of `addrs` unless it is already pinned, and return its pin.
+    fn pin(&self, host: String, addrs: &[SocketAddr]) -> Option<IpAddr> {
+        let first = addrs.first()?.ip();
+        Some(*self.lock().entry(host).or_insert(first))
+    }
+}
+
+/// A DNS resolver using the system resolver on a separate thread, reordering the addresses by
+/// [`AddressFamily`] and pinning hosts to a single address with [`DnsPins`].
+#[derive(Debug)]
+struct FamilyResolver {
+    family: AddressFamily,
+    pins: Option<DnsPins>,
+}
+
+type LookupResult = std::io::Result<Vec<SocketAddr>>;
+
+#[derive(Default)]
+struct LookupState {
+    result: Option<LookupResult>,
+    waker: Option<Waker>,
+}
+
+struct Lookup(Arc<Mutex<LookupState>>);
+
+impl Future for Lookup {
+    type Output = LookupResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl reqwest::dns::Resolve for FamilyResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let family = self.family;
+        let host = name.as_str().to_string();
+        let pins = self.pins.clone();
+        if let Some(ip) = pins.as_ref().and_then(|pins| pins.get(&host)) {
+            let addr = SocketAddr::new(ip, 0);
+            return Box::pin(
+                async move { Ok(Box::new(std::iter::once(addr)) as reqwest::dns::Addrs) },
+            );
+        }
+        let state = Arc::new(Mutex::new(LookupState::default()));
+        let lookup = Lookup(state.clone());
+        std::thread::spawn(move || {
+            let result = (host.as_str(), 0).to_socket_addrs().map(|addrs| {
+                let mut addrs = addrs.collect();
+                family.sort(&mut addrs);
+                match pins.and_then(|pins| pins.pin(host, &addrs)) {
+                    Some(ip) => vec![SocketAddr::new(ip, 0)],
+                    None => addrs,
+                }
+            });
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        Box::pin(async move {
+            let addrs = lookup.await?;
+            if addrs.is_empty() {
+                return Err("no address of the requested family".into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Settings for the [`reqwest`] clients used by the HTTP resolvers.
+///
+/// Documents with hundreds of images can either exhaust the connection limits of an origin or
+/// cause reconnect churn with the default pool settings, so the pool and keep-alive behaviour can
+/// be tuned here, as can the [`AddressFamily`] preference for hosts with broken IPv6 routes.
+/// Settings which are not set keep the reqwest defaults.
+///
+/// ```
+/// use std::time::Duration;
+/// use usvg_remote_resolvers::client::ClientConfig;
+///
+/// let config = ClientConfig::new()
+///     .with_pool_max_idle_per_host(8)
+///     .with_pool_idle_timeout(Duration::from_secs(30))
+///     .with_tcp_keepalive(Duration::from_secs(60));
+/// # #[cfg(feature = "reqwest_blocking")]
+/// let resolver = usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver::from_config(&config).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClientConfig {
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Option<Duration>>,
+    tcp_keepalive: Option<Option<Duration>>,
+    connect_timeout: Option<Duration>,
+    first_byte_timeout: Option<Duration>,
+    address_family: AddressFamily,
+    max_redirects: Option<usize>,
+    redirects: Arc<RedirectLog>,
+    redirect_target: Option<Arc<TargetFilter>>,
+    dns_pins: Option<DnsPins>,
+}
+
+impl ClientConfig {
+    /// Create a new `ClientConfig` keeping the reqwest defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of idle connections kept per host. `0` disables pooling.
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Set how long idle connections are kept in the pool. `None` keeps them indefinitely.
+    pub fn with_pool_idle_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.pool_idle_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Set the TCP keep-alive interval of connections. `None` disables TCP keep-alive.
+    pub fn with_tcp_keepalive(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.tcp_keepalive = Some(interval.into());
+        self
+    }
+
+    /// Set the timeout for connecting to a host.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Abandon a response if no byte of it arrives within `timeout`, e.g. 2 seconds for a hung
+    /// origin.
+    ///
+    /// The timeout applies to waiting for the response headers and to every read of the body, and
+    /// restarts whenever data arrives, so an origin that is actively streaming a large image may
+    /// continue, while one that stops sending is abandoned after `timeout`. Bound the total time
+    /// separately, e.g. with [`reqwest::ClientBuilder::timeout`] or an
+    /// [`AdaptiveTimeout`](`crate::reqwest_blocking::AdaptiveTimeout`).
+    ///
+    /// On the blocking client, this replaces its timeout, which already applies to every
+    /// operation rather than to the whole request.
+    pub fn with_first_byte_timeout(mut self, timeout: Duration) -> Self {
+        self.first_byte_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the [`AddressFamily`] used to connect to hosts.
+    ///
+    /// Anything other than [`AddressFamily::Any`] replaces the DNS resolver of the client.
+    pub fn with_address_family(mut self, family: AddressFamily) -> Self {
+        self.address_family = family;
+        self
+    }
+
+    /// Pin every host to the first address it resolves to, recording the pins in `pins`.
+    ///
+    /// This replaces the DNS resolver of the client. See [`DnsPins`] for how to scope the pins to
+    /// a render.
+    pub fn with_dns_pins(mut self, pins: DnsPins) -> Self {
+        self.dns_pins = Some(pins);
+        self
+    }
+
+    /// Follow at most `max` redirects, and record the redirect chain of every request.
+    ///
+    /// Resolvers created with `from_config` report the chain in
+    /// [`ResponseMetadata::redirects`](`crate::hooks::ResponseMetadata::redirects`), which helps
+    /// to spot CDN misconfigurations that bounce image requests through extra hops.
+    pub fn with_max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = Some(max);
+        self
+    }
+
+    /// Only follow redirects to URLs accepted by `target`, failing the request otherwise.
+    ///
+    /// The [`TargetFilter`] of an HTTP resolver only sees the `href`, so without this, a server
+    /// it accepts can redirect it to a host, port or scheme it would never connect to directly,
+    /// e.g. `http://169.254.169.254/`. The resolvers reject such responses, but only after the
+    /// redirect was followed. Use the same filter as the resolvers, or as a
+    /// [`PolicyResolver`](`crate::target::PolicyResolver`) wrapping them:
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::client::ClientConfig;
+    /// use usvg_remote_resolvers::target::TargetFilter;
+    ///
+    /// let target = TargetFilter::new()
+    ///     .with_schemes(["https"])
+    ///     .with_denied_host_suffix("169.254.169.254");
+    /// let config = ClientConfig::new().with_redirect_target(target.clone());
+    /// # #[cfg(feature = "reqwest_blocking")]
+    /// let resolver = usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver::from_config(&config)
+    ///     .unwrap()
+    ///     .with_target(target);
+    /// ```
+    ///
+    /// Unless limited with [`with_max_redirects`](`Self::with_max_redirects`), up to 10
+    /// redirects are followed, like by default.
+    pub fn with_redirect_target(mut self, target: TargetFilter) -> Self {
+        self.redirect_target = Some(Arc::new(target));
+        self
+    }
+
+    /// The log of redirect chains filled by clients built from this config.
+    #[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+    pub(crate) fn redirect_log(&self) -> Option<Arc<RedirectLog>> {
+        self.max_redirects.map(|_| self.redirects.clone())
+    }
+
+    fn redirect_policy(&self) -> Option<reqwest::redirect::Policy> {
+        if self.max_redirects.is_none() && self.redirect_target.is_none() {
+            return None;
+        }
+        let max = self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS);
+        let log = self.max_redirects.map(|_| self.redirects.clone());
+        let target = self.redirect_target.clone();
+        Some(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > max {
+                return attempt.error(format!("too many redirects (limit {max})"));
+            }
+            if let Some(target) = &target {
+                if !target.matches(attempt.url().as_str()) {
+                    let url = crate::redact::redact(attempt.url().as_str()).into_owned();
+                    return attempt.error(format!("redirect to '{url}' rejected by target"));
+                }
+            }
+            if let Some(log) = &log {
+                log.record(attempt.previous());
+            }
+            attempt.follow()
+        }))
+    }
+
+    fn dns_resolver(&self) -> Option<Arc<FamilyResolver>> {
+        if self.address_family == AddressFamily::Any && self.dns_pins.is_none() {
+            return None;
+        }
+        Some(Arc::new(FamilyResolver {
+            family: self.address_family,
+            pins: self.dns_pins.clone(),
+        }))
+    }
+
+    /// Apply these settings to an async [`ClientBuilder`](`reqwest::ClientBuilder`).
+    #[cfg(any(feature = "reqwest", feature = "reqwest_http_cache"))]
+    pub fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(timeout) = self.first_byte_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(resolver) = self.dns_resolver() {
+            builder = builder.dns_resolver(resolver);
+        }
+        if let Some(policy) = self.redirect_policy() {
+            builder = builder.redirect(policy);
+        }
+        builder
+    }
+
+    /// Build an async [`Client`](`reqwest::Client`) with these settings.
+    #[cfg(any(feature = "reqwest", feature = "reqwest_http_cache"))]
+    pub fn build(&self) -> reqwest::Result<reqwest::Client> {
+        self.apply(reqwest::Client::builder()).build()
+    }
+
+    /// Apply these settings to a blocking [`ClientBuilder`](`reqwest::blocking::ClientBuilder`).
+    #[cfg(feature = "reqwest_blocking")]
+    pub fn apply_blocking(
+        &self,
+        mut builder: reqwest::blocking::ClientBuilder,
+    ) -> reqwest::blocking::ClientBuilder {
+        if let Some(timeout) = self.first_byte_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(resolver) = self.dns_resolver() {
+            builder = builder.dns_resolver(resolver);
+        }
+        if let Some(policy) = self.redirect_policy() {
+            builder = builder.redirect(policy);
+        }
+        builder
+    }
+
+    /// Build a blocking [`Client`](`reqwest::blocking::Client`) with these settings.
+    #[cfg(feature = "reqwest_blocking")]
+    pub fn build_blocking(&self) -> reqwest::Result<reqwest::blocking::Client> {
+        self.apply_blocking(reqwest::blocking::Client::builder())
+            .build()
+    }
+
+    /// Get an async client with these settings, shared with other resolvers using equal settings.
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn shared(&self) -> reqwest::Result<Arc<reqwest::Client>> {
+        static CLIENTS: ClientRegistry<reqwest::Client> = ClientRegistry::new();
+        CLIENTS.get_or_build(self.key(), || self.build())
+    }
+
+    /// Get a blocking client with these settings, shared with other resolvers using equal
+    /// settings.
+    #[cfg(feature = "reqwest_blocking")]
+    pub(crate) fn shared_blocking(&self) -> reqwest::Result<Arc<reqwest::blocking::Client>> {
+        static CLIENTS: ClientRegistry<reqwest::blocking::Client> = ClientRegistry::new();
+        CLIENTS.get_or_build(self.key(), || self.build_blocking())
+    }
+
+    #[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+    fn key(&self) -> ConfigKey {
+        ConfigKey {
+            pool_max_idle_per_host: self.pool_max_idle_per_host,
+            pool_idle_timeout: self.pool_idle_timeout,
+            tcp_keepalive: self.tcp_keepalive,
+            connect_timeout: self.connect_timeout,
+            first_byte_timeout: self.first_byte_timeout,
+            address_family: self.address_family,
+            max_redirects: self.max_redirects,
+            // Clients record redirects into the log of their config, so they can only be shared
+            // by configs sharing the log.
+            redirects: self
+                .max_redirects
+                .map(|_| Arc::as_ptr(&self.redirects) as usize),
+            redirect_target: self
+                .redirect_target
+                .as_ref()
+                .map(|target| Arc::as_ptr(target) as usize),
+            // Likewise, clients pin hosts into the pins of their config.
+            dns_pins: self
+                .dns_pins
+                .as_ref()
+                .map(|pins| Arc::as_ptr(&pins.0) as usize),
+        }
+    }
+}
+
+/// The settings of a [`ClientConfig`] that identify equivalent clients.
+#[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ConfigKey {
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Option<Duration>>,
+    tcp_keepalive: Option<Option<Duration>>,
+    connect_timeout: Option<Duration>,
+    first_byte_timeout: Option<Duration>,
+    address_family: AddressFamily,
+    max_redirects: Option<usize>,
+    redirects: Option<usize>,
+    redirect_target: Option<usize>,
+    dns_pins: Option<usize>,
+}
+
+/// Clients shared between resolvers built from equal [`ClientConfig`]s.
+///
+/// Only weak references are kept, so a client and its connection pool are dropped once no
+/// resolver uses it anymore.
+#[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+struct ClientRegistry<C>(Mutex<Vec<(ConfigKey, Weak<C>)>>);
+
+#[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+impl<C> ClientRegistry<C> {
+    const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    fn get_or_build(
+        &self,
+        key: ConfigKey,
+        build: impl FnOnce() -> reqwest::Result<C>,
+    ) -> reqwest::Result<Arc<C>> {
+        let mut clients = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        clients.retain(|(_, client)| client.strong_count() > 0);
+        if let Some(client) = clients
+            .iter()
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, client)| client.upgrade())
+        {
+            return Ok(client);
+        }
+        let client = Arc::new(build()?);
+        clients.push((key, Arc::downgrade(&client)));
+        Ok(client)
+    }
+}
+
+/// Redirect chains recorded by the redirect policy, keyed by the URL of the original request.
+#[derive(Debug, Default)]
+pub(crate) struct RedirectLog(Mutex<HashMap<String, Vec<String>>>);
+
+impl RedirectLog {
+    fn record(&self, chain: &[reqwest::Url]) {
+        let Some(first) = chain.first() else {
+            return;
+        };
+        if let Ok(mut log) = self.0.lock() {
+            log.insert(
+                first.to_string(),
+                chain.iter().map(|url| url.to_string()).collect(),
+            );
+        }
+    }
+
+    /// Take the URLs that redirected the request for `href`, in order.
+    #[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+    pub(crate) fn take(&self, href: &str) -> Vec<String> {
+        let Ok(url) = reqwest::Url::parse(href) else {
+            return Vec::new();
+        };
+        self.0
+            .lock()
+            .ok()
+            .and_then(|mut log| log.remove(url.as_str()))
+            .unwrap_or_default()
+    }
+}
+
+/// Forgets the redirect chain of a request to `href` when dropped, so the chains of failed
+/// requests, or of requests whose chain is never taken, don't pile up in the [`RedirectLog`].
+#[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+pub(crate) struct RedirectGuard<'a> {
+    log: Option<&'a RedirectLog>,
+    href: &'a str,
+}
+
+#[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+impl<'a> RedirectGuard<'a> {
+    pub(crate) fn new(log: Option<&'a RedirectLog>, href: &'a str) -> Self {
+        Self { log, href }
+    }
+}
+
+#[cfg(any(feature = "reqwest", feature = "reqwest_blocking"))]
+impl Drop for RedirectGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(log) = self.log {
+            log.take(self.href);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn shares_clients() {
+        let config = ClientConfig::new().with_pool_max_idle_per_host(4);
+        let a = config.shared_blocking().unwrap();
+        let b = config.clone().shared_blocking().unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let other = ClientConfig::new().with_pool_max_idle_per_host(5);
+        assert!(!Arc::ptr_eq(&a, &other.shared_blocking().unwrap()));
+
+        let weak = Arc::downgrade(&a);
+        drop((a, b));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn first_byte_timeout() {
+        use crate::HrefStringResolver;
+
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/stalled.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_millis(500));
+                w.write_all(include_bytes!("../test_data/gray.png"))
+            })
+            .create();
+        let url = format!("{}/stalled.png", s.url());
+        let options = usvg::Options::default();
+
+        let config = ClientConfig::new().with_first_byte_timeout(Duration::from_millis(100));
+        let resolver =
+            crate::reqwest_blocking::BlockingReqwestResolver::from_config(&config).unwrap();
+        assert!(resolver.get_image_kind(&url, &options).is_none());
+
+        let config = config.with_first_byte_timeout(Duration::from_secs(5));
+        let resolver =
+            crate::reqwest_blocking::BlockingReqwestResolver::from_config(&config).unwrap();
+        assert!(resolver.get_image_kind(&url, &options).is_some());
+
+        // A body that keeps arriving may take longer than the timeout in total.
+        s.mock("GET", "/dripping.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| {
+                for chunk in include_bytes!("../test_data/gray.png").chunks(16) {
+                    std::thread::sleep(Duration::from_millis(50));
+                    w.write_all(chunk)?;
+                    w.flush()?;
+                }
+                Ok(())
+            })
+            .create();
+        let url = format!("{}/dripping.png", s.url());
+        let config = ClientConfig::new().with_first_byte_timeout(Duration::from_millis(300));
+        let resolver =
+            crate::reqwest_blocking::BlockingReqwestResolver::from_config(&config).unwrap();
+        let start = std::time::Instant::now();
+        assert!(resolver.get_image_kind(&url, &options).is_some());
+        assert!(start.elapsed() > Duration::from_millis(300));
+    }
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn forgets_failed_redirect_chains() {
+        use crate::HrefStringResolver;
+
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let location = format!("http://{}/gray.png", closed.local_addr().unwrap());
+        drop(closed);
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/a.png")
+            .with_status(302)
+            .with_header("location", &location)
+            .create();
+
+        let config = ClientConfig::new().with_max_redirects(2);
+        let resolver =
+            crate::reqwest_blocking::BlockingReqwestResolver::from_config(&config).unwrap();
+        let href = format!("{}/a.png", s.url());
+        assert!(resolver
+            .get_image_kind(&href, &usvg::Options::default())
+            .is_none());
+        assert!(config.redirects.0.lock().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn rejects_redirects_to_denied_hosts() {
+        use crate::reqwest_blocking::BlockingReqwestResolver;
+        use crate::HrefStringResolver;
+
+        let mut s = mockito::Server::new();
+        let port = s.socket_address().port();
+        s.mock("GET", "/a.png")
+            .with_status(302)
+            .with_header(
+                "location",
+                &format!("http://localhost:{port}/meta-data.png"),
+            )
+            .create();
+        let meta_data = s
+            .mock("GET", "/meta-data.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+        let target = TargetFilter::new().with_denied_host_suffix("localhost");
+        let href = format!("{}/a.png", s.url());
+        assert!(target.matches(&href));
+        let options = usvg::Options::default();
+
+        // A client following any redirect fetches the denied host, but the response is rejected.
+        let resolver = BlockingReqwestResolver::default().with_target(target.clone());
+        assert!(resolver.get_image_kind(&href, &options).is_none());
+
+        // A client built with the target does not follow the redirect at all.
+        let config = ClientConfig::new().with_redirect_target(target.clone());
+        let resolver = BlockingReqwestResolver::from_config(&config)
+            .unwrap()
+            .with_target(target);
+        assert!(resolver.get_image_kind(&href, &options).is_none());
+        meta_data.assert();
+    }
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn rejects_redirects_to_denied_ports() {
+        use crate::reqwest_blocking::BlockingReqwestResolver;
+        use crate::HrefStringResolver;
+
+        let mut internal = mockito::Server::new();
+        let internal_port = internal.socket_address().port();
+        let admin = internal
+            .mock("GET", "/admin.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(0)
+            .create();
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/a.png")
+            .with_status(302)
+            .with_header("location", &format!("{}/admin.png", internal.url()))
+            .create();
+        let target = TargetFilter::new().with_denied_ports([internal_port]);
+        let href = format!("{}/a.png", s.url());
+        assert!(target.matches(&href));
+
+        let config = ClientConfig::new().with_redirect_target(target.clone());
+        let resolver = BlockingReqwestResolver::from_config(&config)
+            .unwrap()
+            .with_target(target);
+        assert!(resolver
+            .get_image_kind(&href, &usvg::Options::default())
+            .is_none());
+        admin.assert();
+    }
+
+    #[test]
+    fn address_family_order() {
+        let v4: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let v6: SocketAddr = "[::1]:0".parse().unwrap();
+
+        let mut addrs = vec![v6, v4];
+        AddressFamily::PreferIpv4.sort(&mut addrs);
+        assert_eq!(addrs, [v4, v6]);
+        AddressFamily::PreferIpv6.sort(&mut addrs);
+        assert_eq!(addrs, [v6, v4]);
+        AddressFamily::Ipv4Only.sort(&mut addrs);
+        assert_eq!(addrs, [v4]);
+    }
+}
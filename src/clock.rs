@@ -0,0 +1,85 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// The source of time for caching and rate limiting.
+///
+/// Resolvers use [`SystemClock`] by default. A [`MockClock`] can be injected instead to test
+/// cache TTLs, negative caching and rate limits without waiting in real time.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// The current time.
+    fn now(&self) -> SystemTime;
+
+    /// Block the current thread for `duration`.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+/// The system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to. Sleeping advances it instead of blocking.
+///
+/// Clones share the same time, so keep a clone to advance the clock of a resolver.
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use usvg_remote_resolvers::clock::{Clock, MockClock};
+///
+/// let clock = MockClock::new(SystemTime::UNIX_EPOCH);
+/// clock.advance(Duration::from_secs(60));
+/// clock.sleep(Duration::from_secs(1));
+/// assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(61));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock(Arc<Mutex<SystemTime>>);
+
+impl Default for MockClock {
+    /// A mock clock starting at the current system time.
+    fn default() -> Self {
+        Self::new(SystemTime::now())
+    }
+}
+
+impl MockClock {
+    /// Create a new `MockClock` starting at `now`.
+    pub fn new(now: SystemTime) -> Self {
+        Self(Arc::new(Mutex::new(now)))
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *now += duration;
+    }
+
+    /// Set the clock to `now`.
+    pub fn set(&self, now: SystemTime) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use usvg::{ImageHrefDataResolverFn, ImageKind, Options};
+
+type MimeTarget = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// A trait for resolving the decoded data of `data:` URLs into [`ImageKind`]s, the counterpart of
+/// [`HrefStringResolver`](crate::HrefStringResolver) for
+/// [`resolve_data`](usvg::ImageHrefResolver::resolve_data).
+///
+/// usvg decodes `data:` URLs itself and passes their MIME type and bytes to the resolver.
+///
+/// ```
+/// use usvg_remote_resolvers::data::{DataSizeLimitResolver, DefaultDataResolver, HrefDataResolver};
+///
+/// let mut options = usvg::Options::default();
+/// DataSizeLimitResolver::new(DefaultDataResolver, 1024 * 1024).set_into_options(&mut options);
+/// ```
+pub trait HrefDataResolver<'a>: Send + Sync {
+    /// Check if the data with the MIME type `mime` is a target of this resolver.
+    fn is_target(&self, mime: &str) -> bool;
+    /// Get the [`ImageKind`] from the `data` with the MIME type `mime`.
+    fn get_image_kind(
+        &self,
+        mime: &str,
+        data: Arc<Vec<u8>>,
+        options: &Options,
+    ) -> Option<ImageKind>;
+    /// Convert this resolver into a [`ImageHrefDataResolverFn`].
+    fn into_fn(self) -> ImageHrefDataResolverFn<'a>
+    where
+        Self: Sized + 'a,
+    {
+        Box::new(move |mime, data, options| {
+            if self.is_target(mime) {
+                let result = self.get_image_kind(mime, data, options);
+                if result.is_none() {
+                    crate::utils::log_warn!("failed to resolve image data of type '{}'", mime);
+                }
+                result
+            } else {
+                crate::utils::log_warn!(
+                    "image data of type '{}' is not a target for this resolver",
+                    mime
+                );
+                None
+            }
+        })
+    }
+    /// Set this resolver into the [`Options`](`usvg::Options`).
+    fn set_into_options(self, options: &mut Options<'a>)
+    where
+        Self: Sized + 'a,
+    {
+        options.image_href_resolver.resolve_data = self.into_fn();
+    }
+    /// Add a fallback to this resolver in case if the data is not the target of this resolver, or
+    /// if it fails to resolve.
+    fn with_fallback<T>(self, fallback: T) -> DataFallbackResolver<Self, T>
+    where
+        Self: Sized,
+        T: HrefDataResolver<'a>,
+    {
+        DataFallbackResolver::new(self, fallback)
+    }
+}
+
+/// Resolver using [`default_data_resolver`](`usvg::ImageHrefResolver::default_data_resolver`),
+/// which decodes PNG, JPEG, GIF, WebP and SVG data.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultDataResolver;
+
+impl<'a> HrefDataResolver<'a> for DefaultDataResolver {
+    fn is_target(&self, _: &str) -> bool {
+        true
+    }
+    fn get_image_kind(
+        &self,
+        mime: &str,
+        data: Arc<Vec<u8>>,
+        options: &Options,
+    ) -> Option<ImageKind> {
+        (usvg::ImageHrefResolver::default_data_resolver())(mime, data, options)
+    }
+    fn into_fn(self) -> ImageHrefDataResolverFn<'a> {
+        usvg::ImageHrefResolver::default_data_resolver()
+    }
+}
+
+/// A data resolver that tries the `primary` resolver first, and the `fallback` resolver if the
+/// primary doesn't target the data or fails to resolve it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DataFallbackResolver<T, U> {
+    /// The resolver that is tried first.
+    pub primary: T,
+    /// The resolver that is used if the primary resolver does not handle the data or fails.
+    pub fallback: U,
+}
+
+impl<T, U> DataFallbackResolver<T, U> {
+    /// Create a new `DataFallbackResolver` with the given `primary` and `fallback` resolvers.
+    pub fn new(primary: T, fallback: U) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<'a, T, U> HrefDataResolver<'a> for DataFallbackResolver<T, U>
+where
+    T: HrefDataResolver<'a>,
+    U: HrefDataResolver<'a>,
+{
+    fn is_target(&self, mime: &str) -> bool {
+        self.primary.is_target(mime) || self.fallback.is_target(mime)
+    }
+    fn get_image_kind(
+        &self,
+        mime: &str,
+        data: Arc<Vec<u8>>,
+        options: &Options,
+    ) -> Option<ImageKind> {
+        self.primary
+            .is_target(mime)
+            .then(|| self.primary.get_image_kind(mime, data.clone(), options))
+            .flatten()
+            .or_else(|| {
+                self.fallback
+                    .is_target(mime)
+                    .then(|| self.fallback.get_image_kind(mime, data, options))
+                    .flatten()
+            })
+    }
+}
+
+/// A data resolver that rejects data larger than `max_size` bytes before passing it to the inner
+/// resolver.
+#[derive(Debug, Clone, Copy)]
+pub struct DataSizeLimitResolver<T> {
+    inner: T,
+    max_size: usize,
+}
+
+impl<T> DataSizeLimitResolver<T> {
+    /// Create a new `DataSizeLimitResolver` passing data of up to `max_size` bytes to `inner`.
+    pub fn new(inner: T, max_size: usize) -> Self {
+        Self { inner, max_size }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the maximum size of the data in bytes.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+}
+
+impl<'a, T: HrefDataResolver<'a>> HrefDataResolver<'a> for DataSizeLimitResolver<T> {
+    fn is_target(&self, mime: &str) -> bool {
+        self.inner.is_target(mime)
+    }
+    fn get_image_kind(
+        &self,
+        mime: &str,
+        data: Arc<Vec<u8>>,
+        options: &Options,
+    ) -> Option<ImageKind> {
+        if data.len() > self.max_size {
+            crate::utils::log_warn!(
+                "image data of type '{}' exceeds {} bytes",
+                mime,
+                self.max_size
+            );
+            return None;
+        }
+        self.inner.get_image_kind(mime, data, options)
+    }
+}
+
+/// A data resolver calling a closure, optionally only for the MIME types matching a predicate.
+///
+/// ```
+/// use std::sync::Arc;
+/// use usvg::ImageKind;
+/// use usvg_remote_resolvers::data::{DataFnResolver, DefaultDataResolver, HrefDataResolver};
+///
+/// let resolver = DataFnResolver::new(|_, data, _| {
+///     let png = data.iter().map(|b| b ^ 0x5a).collect::<Vec<_>>();
+///     Some(ImageKind::PNG(Arc::new(png)))
+/// })
+/// .with_target(|mime| mime == "application/x-obfuscated-png")
+/// .with_fallback(DefaultDataResolver);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+pub struct DataFnResolver<F> {
+    resolve: F,
+    target: Option<MimeTarget>,
+}
+
+impl<F> std::fmt::Debug for DataFnResolver<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataFnResolver")
+            .field("target", &self.target.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> DataFnResolver<F>
+where
+    F: Fn(&str, Arc<Vec<u8>>, &Options) -> Option<ImageKind> + Send + Sync,
+{
+    /// Create a new `DataFnResolver` calling `resolve` for data of any MIME type.
+    pub fn new(resolve: F) -> Self {
+        Self {
+            resolve,
+            target: None,
+        }
+    }
+
+    /// Only call the closure for the MIME types matching `target`.
+    pub fn with_target(self, target: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            target: Some(Arc::new(target)),
+            ..self
+        }
+    }
+}
+
+impl<'a, F> HrefDataResolver<'a> for DataFnResolver<F>
+where
+    F: Fn(&str, Arc<Vec<u8>>, &Options) -> Option<ImageKind> + Send + Sync,
+{
+    fn is_target(&self, mime: &str) -> bool {
+        self.target.as_ref().is_none_or(|target| target(mime))
+    }
+    fn get_image_kind(
+        &self,
+        mime: &str,
+        data: Arc<Vec<u8>>,
+        options: &Options,
+    ) -> Option<ImageKind> {
+        (self.resolve)(mime, data, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_data_resolvers() {
+        let gray = include_bytes!("../test_data/gray.png");
+        let resolver = DataFnResolver::new(|_, data, _| Some(ImageKind::GIF(data)))
+            .with_target(|mime| mime == "image/x-custom")
+            .with_fallback(DataSizeLimitResolver::new(DefaultDataResolver, gray.len()));
+        let options = Options::default();
+        let data = |extra: usize| {
+            let mut data = gray.to_vec();
+            data.resize(gray.len() + extra, 0);
+            Arc::new(data)
+        };
+
+        assert!(matches!(
+            resolver.get_image_kind("image/x-custom", data(1), &options),
+            Some(ImageKind::GIF(_))
+        ));
+        assert!(matches!(
+            resolver.get_image_kind("image/png", data(0), &options),
+            Some(ImageKind::PNG(_))
+        ));
+        assert!(resolver
+            .get_image_kind("image/png", data(1), &options)
+            .is_none());
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><image width="1" height="1" href="data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' width='1' height='1'/%3E"/></svg>"#;
+        for (max_size, resolved) in [(1024, true), (0, false)] {
+            let mut options = Options::default();
+            DataSizeLimitResolver::new(DefaultDataResolver, max_size)
+                .set_into_options(&mut options);
+            let tree = usvg::Tree::from_str(svg, &options).unwrap();
+            assert_eq!(tree.root().has_children(), resolved);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use usvg::{ImageKind, Options};
+
+use crate::normalize::UrlNormalizer;
+use crate::svg_cache::OptionsFingerprint;
+use crate::HrefStringResolver;
+
+type Key = (String, OptionsFingerprint);
+
+#[derive(Debug, Default)]
+struct Lru {
+    /// The cached images with the tick they were last used at.
+    entries: HashMap<Key, (ImageKind, u64)>,
+    /// The keys by the tick they were last used at, least recently used first.
+    order: BTreeMap<u64, Key>,
+    tick: u64,
+}
+
+impl Lru {
+    fn get(&mut self, key: &Key) -> Option<ImageKind> {
+        self.tick += 1;
+        let (kind, used) = self.entries.get_mut(key)?;
+        let key = self.order.remove(used)?;
+        *used = self.tick;
+        let kind = kind.clone();
+        self.order.insert(self.tick, key);
+        Some(kind)
+    }
+
+    fn insert(&mut self, key: Key, kind: ImageKind, capacity: usize) {
+        self.tick += 1;
+        if let Some((_, used)) = self.entries.insert(key.clone(), (kind, self.tick)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.tick, key);
+        while self.entries.len() > capacity {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+        }
+    }
+}
+
+/// Trait for storing the images resolved by a [`CachedResolver`].
+///
+/// Implement this trait to keep the images in a storage of your own, e.g. a database shared by
+/// several processes. Entries are keyed by the canonical `href` of the image, as normalized by the
+/// resolver, and the [`OptionsFingerprint`] of the [`Options`] it was resolved with, since nested
+/// SVG images depend on them. A store may drop any entry, or decline to store it, e.g. an SVG
+/// tree it can't serialize; [`get`](Self::get) then returns `None` and the image is resolved again.
+pub trait ImageStore: Send + Sync {
+    /// Look up the image cached for `href` and `fingerprint`.
+    fn get(&self, href: &str, fingerprint: &OptionsFingerprint) -> Option<ImageKind>;
+    /// Store the image resolved for `href` and `fingerprint`.
+    fn put(&self, href: &str, fingerprint: &OptionsFingerprint, kind: ImageKind);
+    /// Remove the images cached for `href`, with any fingerprint.
+    fn remove(&self, href: &str);
+    /// Get the number of cached images.
+    fn len(&self) -> usize;
+    /// Returns `true` if no images are cached.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A shared image store, so that several resolvers can use the same cache.
+impl<S: ImageStore + ?Sized> ImageStore for Arc<S> {
+    fn get(&self, href: &str, fingerprint: &OptionsFingerprint) -> Option<ImageKind> {
+        (**self).get(href, fingerprint)
+    }
+    fn put(&self, href: &str, fingerprint: &OptionsFingerprint, kind: ImageKind) {
+        (**self).put(href, fingerprint, kind)
+    }
+    fn remove(&self, href: &str) {
+        (**self).remove(href)
+    }
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+/// An [`ImageStore`] keeping the images in memory, evicting the least recently used ones beyond a
+/// maximum number of entries.
+///
+/// Raster images share their data with the store; trees are cloned out of it.
+#[derive(Debug)]
+pub struct LruImageStore {
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+impl LruImageStore {
+    /// Create a new `LruImageStore` keeping at most `capacity` images.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lru: Mutex::default(),
+        }
+    }
+
+    /// Remove all cached images.
+    pub fn clear(&self) {
+        *self.lock() = Lru::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.lru.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ImageStore for LruImageStore {
+    fn get(&self, href: &str, fingerprint: &OptionsFingerprint) -> Option<ImageKind> {
+        self.lock().get(&(href.to_string(), fingerprint.clone()))
+    }
+    fn put(&self, href: &str, fingerprint: &OptionsFingerprint, kind: ImageKind) {
+        let key = (href.to_string(), fingerprint.clone());
+        self.lock().insert(key, kind, self.capacity);
+    }
+    fn remove(&self, href: &str) {
+        let mut lru = self.lock();
+        let Lru { entries, order, .. } = &mut *lru;
+        entries.retain(|(h, _), (_, used)| {
+            let keep = h != href;
+            if !keep {
+                order.remove(used);
+            }
+            keep
+        });
+    }
+    fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+}
+
+/// A resolver that keeps the images resolved by the inner resolver in an [`ImageStore`], by
+/// default an [`LruImageStore`] in memory.
+///
+/// Unlike an HTTP cache, which keeps the fetched bytes, this keeps the resolved [`ImageKind`], so
+/// nested SVG images are not parsed again on every render. Entries are keyed by the `href`,
+/// normalized if a [`UrlNormalizer`] is set, and the [`OptionsFingerprint`] of the [`Options`],
+/// since nested SVG images depend on them. Failures are not cached.
+///
+/// ```
+/// use usvg_remote_resolvers::decoded_cache::CachedResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let resolver = CachedResolver::new(DefaultResolver, 256);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug)]
+pub struct CachedResolver<T, S = LruImageStore> {
+    inner: T,
+    store: S,
+    normalizer: Option<Arc<dyn UrlNormalizer>>,
+}
+
+impl<T> CachedResolver<T> {
+    /// Create a new `CachedResolver` keeping at most `capacity` images in memory.
+    pub fn new(inner: T, capacity: usize) -> Self {
+        Self::with_store(inner, LruImageStore::new(capacity))
+    }
+
+    /// Remove all cached images.
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+}
+
+impl<T, S: ImageStore> CachedResolver<T, S> {
+    /// Create a new `CachedResolver` keeping the images in `store`.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use usvg_remote_resolvers::decoded_cache::{CachedResolver, ImageStore, LruImageStore};
+    /// use usvg_remote_resolvers::DefaultResolver;
+    ///
+    /// let store = Arc::new(LruImageStore::new(256));
+    /// let resolver = CachedResolver::with_store(DefaultResolver, store.clone());
+    /// assert!(store.is_empty());
+    /// ```
+    pub fn with_store(inner: T, store: S) -> Self {
+        Self {
+            inner,
+            store,
+            normalizer: None,
+        }
+    }
+
+    /// Key the cached images by the `href` normalized with `normalizer`.
+    pub fn with_normalizer(mut self, normalizer: impl UrlNormalizer + 'static) -> Self {
+        self.normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the store of the cached images.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Number of cached images.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns `true` if no images are cached.
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>, S: ImageStore> HrefStringResolver<'a> for CachedResolver<T, S> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let key = crate::normalize::normalize(self.normalizer.as_ref(), href);
+        let fingerprint = OptionsFingerprint::new(options);
+        if let Some(kind) = self.store.get(&key, &fingerprint) {
+            crate::events::record_cache_hit();
+            return Some(kind);
+        }
+        let kind = self.inner.get_image_kind(href, options)?;
+        self.store.put(&key, &fingerprint, kind.clone());
+        Some(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingResolver(AtomicUsize);
+
+    impl HrefStringResolver<'_> for CountingResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            crate::DefaultResolver.get_image_kind(href, options)
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let resolver = CachedResolver::new(CountingResolver(AtomicUsize::new(0)), 2);
+        let options = Options::default();
+        let count = || resolver.inner().0.load(Ordering::SeqCst);
+
+        for href in ["./test_data/gray.png", "test_data/gray.png"] {
+            assert!(resolver.get_image_kind(href, &options).is_some());
+        }
+        // Use the first one again, so the second one is evicted by the third.
+        resolver.get_image_kind("./test_data/gray.png", &options);
+        assert_eq!(count(), 2);
+        resolver.get_image_kind("./test_data/../test_data/gray.png", &options);
+        assert_eq!(resolver.len(), 2);
+
+        resolver.get_image_kind("./test_data/gray.png", &options);
+        assert_eq!(count(), 3);
+        resolver.get_image_kind("test_data/gray.png", &options);
+        assert_eq!(count(), 4);
+
+        assert!(resolver
+            .get_image_kind("./test_data/missing.png", &options)
+            .is_none());
+        assert!(resolver
+            .get_image_kind("./test_data/missing.png", &options)
+            .is_none());
+        assert_eq!(count(), 6);
+    }
+
+    /// Keeps only the raster images, as a persistent store of their bytes would.
+    #[derive(Default)]
+    struct RasterStore(Mutex<HashMap<String, Arc<Vec<u8>>>>);
+
+    impl ImageStore for RasterStore {
+        fn get(&self, href: &str, _: &OptionsFingerprint) -> Option<ImageKind> {
+            let data = self.0.lock().unwrap().get(href)?.clone();
+            Some(ImageKind::PNG(data))
+        }
+        fn put(&self, href: &str, _: &OptionsFingerprint, kind: ImageKind) {
+            if let ImageKind::PNG(data) = kind {
+                self.0.lock().unwrap().insert(href.to_string(), data);
+            }
+        }
+        fn remove(&self, href: &str) {
+            self.0.lock().unwrap().remove(href);
+        }
+        fn len(&self) -> usize {
+            self.0.lock().unwrap().len()
+        }
+    }
+
+    #[test]
+    fn custom_store() {
+        let store = Arc::new(RasterStore::default());
+        let resolver =
+            CachedResolver::with_store(CountingResolver(AtomicUsize::new(0)), store.clone())
+                .with_normalizer(crate::normalize::DefaultNormalizer::new());
+        let options = Options::default();
+        let count = || resolver.inner().0.load(Ordering::SeqCst);
+
+        for _ in 0..2 {
+            assert!(resolver
+                .get_image_kind("./test_data/gray.png", &options)
+                .is_some());
+        }
+        assert_eq!(count(), 1);
+        assert_eq!(store.len(), 1);
+
+        store.remove(&crate::normalize::DefaultNormalizer::new().normalize("./test_data/gray.png"));
+        assert!(store.is_empty());
+        resolver.get_image_kind("./test_data/gray.png", &options);
+        assert_eq!(count(), 2);
+
+        let lru = LruImageStore::new(4);
+        let fingerprint = OptionsFingerprint::new(&options);
+        lru.put("a.png", &fingerprint, ImageKind::PNG(Arc::new(Vec::new())));
+        lru.put("b.png", &fingerprint, ImageKind::PNG(Arc::new(Vec::new())));
+        lru.remove("a.png");
+        assert!(lru.get("a.png", &fingerprint).is_none());
+        assert_eq!(lru.len(), 1);
+    }
+}
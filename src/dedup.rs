@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+
+use usvg::{ImageKind, Options};
+
+use crate::normalize::UrlNormalizer;
+use crate::svg_cache::OptionsFingerprint;
+use crate::HrefStringResolver;
+
+type Key = (String, OptionsFingerprint);
+
+/// An `href` being resolved, with the result once it is known.
+#[derive(Default)]
+struct Flight {
+    result: Mutex<Option<Option<ImageKind>>>,
+    done: Condvar,
+}
+
+impl Flight {
+    fn finish(&self, result: Option<ImageKind>) {
+        let mut slot = self.result.lock().unwrap_or_else(|e| e.into_inner());
+        *slot = Some(result);
+        self.done.notify_all();
+    }
+
+    fn wait(&self) -> Option<ImageKind> {
+        let slot = self.result.lock().unwrap_or_else(|e| e.into_inner());
+        let slot = self
+            .done
+            .wait_while(slot, |slot| slot.is_none())
+            .unwrap_or_else(|e| e.into_inner());
+        slot.clone().flatten()
+    }
+}
+
+/// A resolver that coalesces concurrent requests for the same `href`, so the inner resolver
+/// resolves it once and every waiting caller gets a copy of the result.
+///
+/// This helps when several threads resolve the same images at once, e.g. parallel renders of
+/// similar documents or the workers of a [`FetchQueue`](crate::queue::FetchQueue). The `href`s of
+/// one document are resolved one after another, so repeated references within a document are not
+/// concurrent; combine this with a [`CachedResolver`](crate::decoded_cache::CachedResolver) to
+/// reuse finished results as well. Requests are keyed by the `href`, normalized if a normalizer is
+/// set with [`with_normalizer`](Self::with_normalizer), and the [`OptionsFingerprint`] of the
+/// [`Options`].
+///
+/// ```
+/// use usvg_remote_resolvers::dedup::DedupResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let resolver = DedupResolver::new(DefaultResolver);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+pub struct DedupResolver<T> {
+    inner: T,
+    in_flight: Mutex<HashMap<Key, Arc<Flight>>>,
+    normalizer: Option<Arc<dyn UrlNormalizer>>,
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for DedupResolver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DedupResolver")
+            .field("inner", &self.inner)
+            .field("in_flight", &self.in_flight().len())
+            .finish()
+    }
+}
+
+impl<T> DedupResolver<T> {
+    /// Create a new `DedupResolver` wrapping the given resolver.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            in_flight: Mutex::default(),
+            normalizer: None,
+        }
+    }
+
+    /// Coalesce the requests for `href`s that are equal after normalizing them with `normalizer`.
+    ///
+    /// The inner resolver is called with the `href` of the first request.
+    pub fn with_normalizer(mut self, normalizer: impl UrlNormalizer + 'static) -> Self {
+        self.normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the `href`s being resolved right now, normalized if there is a normalizer.
+    pub fn in_flight(&self) -> Vec<String> {
+        let in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        let mut hrefs: Vec<_> = in_flight.keys().map(|(href, _)| href.clone()).collect();
+        hrefs.sort();
+        hrefs.dedup();
+        hrefs
+    }
+}
+
+/// Finishes the flight of the leader and removes it, even if the inner resolver panics.
+struct Landing<'a> {
+    in_flight: &'a Mutex<HashMap<Key, Arc<Flight>>>,
+    key: Key,
+    flight: Arc<Flight>,
+    result: Option<ImageKind>,
+}
+
+impl Drop for Landing<'_> {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+        self.flight.finish(self.result.take());
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for DedupResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let key = (
+            crate::normalize::normalize(self.normalizer.as_ref(), href).into_owned(),
+            OptionsFingerprint::new(options),
+        );
+        let flight = {
+            let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(flight) = in_flight.get(&key) {
+                let flight = flight.clone();
+                drop(in_flight);
+                return flight.wait();
+            }
+            let flight = Arc::new(Flight::default());
+            in_flight.insert(key.clone(), flight.clone());
+            flight
+        };
+
+        let mut landing = Landing {
+            in_flight: &self.in_flight,
+            key,
+            flight,
+            result: None,
+        };
+        let result = self.inner.get_image_kind(href, options);
+        landing.result = result.clone();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+    use std::time::Duration;
+
+    struct SlowResolver(AtomicUsize);
+
+    impl HrefStringResolver<'_> for SlowResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(200));
+            crate::DefaultResolver.get_image_kind(href, options)
+        }
+    }
+
+    #[test]
+    fn coalesces_concurrent_requests() {
+        let resolver = DedupResolver::new(SlowResolver(AtomicUsize::new(0)));
+        let barrier = Barrier::new(8);
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    barrier.wait();
+                    let kind = resolver.get_image_kind("./test_data/gray.png", &Options::default());
+                    assert!(matches!(kind, Some(ImageKind::PNG(_))));
+                });
+            }
+        });
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 1);
+        assert!(resolver.in_flight().is_empty());
+
+        resolver.get_image_kind("./test_data/gray.png", &Options::default());
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn coalesces_normalized_hrefs() {
+        let resolver = DedupResolver::new(SlowResolver(AtomicUsize::new(0)))
+            .with_normalizer(crate::normalize::DefaultNormalizer::new());
+        let barrier = Barrier::new(4);
+        std::thread::scope(|s| {
+            for i in 0..4 {
+                let (resolver, barrier) = (&resolver, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    let href = format!("https://example.com/gray.png?utm_source={i}");
+                    resolver.get_image_kind(&href, &Options::default());
+                });
+            }
+        });
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 1);
+    }
+}
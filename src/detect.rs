@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::utils::ImageKindTypes;
+
+/// Where [`detect`] found the image type of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DetectionSource {
+    /// The `Content-Type` header.
+    ContentType,
+    /// The file extension of the `href`.
+    Extension,
+    /// The magic bytes at the start of the body.
+    MagicBytes,
+}
+
+/// The image type of a response detected by [`detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DetectedType {
+    /// The image type.
+    pub image_type: ImageKindTypes,
+    /// Where the image type was found.
+    pub source: DetectionSource,
+}
+
+/// The reason [`detect`] could not detect the image type of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DetectError {
+    /// The `Content-Type` is not a valid MIME type, and the `href` has no known extension.
+    InvalidContentType(String),
+    /// The `Content-Type` is a MIME type of something other than a supported image, and the
+    /// `href` has no known extension.
+    UnsupportedContentType(String),
+    /// The `Content-Type` is missing or generic, the `href` has no known extension, and the body
+    /// is empty.
+    EmptyBody,
+    /// The `Content-Type` is missing or generic, the `href` has no known extension, and the body
+    /// doesn't start like a supported image.
+    UnrecognizedBody,
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidContentType(content_type) => {
+                write!(f, "invalid content-type {:?}", content_type)
+            }
+            Self::UnsupportedContentType(content_type) => {
+                write!(f, "unsupported content-type {:?}", content_type)
+            }
+            Self::EmptyBody => f.write_str("empty body"),
+            Self::UnrecognizedBody => f.write_str("unrecognized image format"),
+        }
+    }
+}
+
+impl std::error::Error for DetectError {}
+
+/// Detect the image type of a response body `data` fetched from `href` with the `content_type`
+/// header, as the crate's resolvers do.
+///
+/// The `content_type` is used if it is a supported image type, then the file extension of the
+/// `href`, then the magic bytes of `data` if `content_type` is missing or generic, like
+/// `application/octet-stream`. Only the start of `data` is read, and the result depends on the
+/// arguments alone, so it can be fuzzed with arbitrary headers and bodies.
+///
+/// ```
+/// use usvg_remote_resolvers::detect::{detect, DetectError, DetectionSource};
+/// use usvg_remote_resolvers::ImageKindTypes;
+///
+/// let detected = detect(Some("application/octet-stream"), "/download", b"GIF89a...").unwrap();
+/// assert_eq!(detected.image_type, ImageKindTypes::Gif);
+/// assert_eq!(detected.source, DetectionSource::MagicBytes);
+/// assert_eq!(
+///     detect(Some("text/html"), "/download", b"<html>"),
+///     Err(DetectError::UnsupportedContentType("text/html".to_string()))
+/// );
+/// ```
+pub fn detect(
+    content_type: Option<&str>,
+    href: &str,
+    data: &[u8],
+) -> Result<DetectedType, DetectError> {
+    let detected = |image_type, source| Ok(DetectedType { image_type, source });
+    if let Some(image_type) = content_type.and_then(ImageKindTypes::from_content_type) {
+        return detected(image_type, DetectionSource::ContentType);
+    }
+    if let Some(image_type) =
+        crate::utils::href_extension(href).and_then(|ext| ImageKindTypes::from_extension(&ext))
+    {
+        return detected(image_type, DetectionSource::Extension);
+    }
+    if let Some(content_type) = content_type.filter(|c| !ImageKindTypes::is_generic(Some(c))) {
+        return Err(match content_type.trim().parse::<mime::Mime>() {
+            Ok(_) => DetectError::UnsupportedContentType(content_type.to_string()),
+            Err(_) => DetectError::InvalidContentType(content_type.to_string()),
+        });
+    }
+    if data.is_empty() {
+        return Err(DetectError::EmptyBody);
+    }
+    match ImageKindTypes::from_bytes(data) {
+        Some(image_type) => detected(image_type, DetectionSource::MagicBytes),
+        None => Err(DetectError::UnrecognizedBody),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_why_detection_failed() {
+        let png = include_bytes!("../test_data/gray.png");
+        let source = |content_type, href| detect(content_type, href, png).map(|d| d.source);
+        assert_eq!(
+            source(Some("image/png"), "a.svg"),
+            Ok(DetectionSource::ContentType)
+        );
+        assert_eq!(
+            source(Some("text/plain"), "a.png"),
+            Ok(DetectionSource::Extension)
+        );
+        assert_eq!(source(None, "a"), Ok(DetectionSource::MagicBytes));
+
+        assert_eq!(
+            detect(Some("image/;;\u{0}"), "a", png),
+            Err(DetectError::InvalidContentType("image/;;\u{0}".to_string()))
+        );
+        assert_eq!(
+            detect(Some("image/avif"), "a", png),
+            Err(DetectError::UnsupportedContentType(
+                "image/avif".to_string()
+            ))
+        );
+        assert_eq!(detect(None, "a", b""), Err(DetectError::EmptyBody));
+        assert_eq!(
+            detect(Some("binary/octet-stream"), "a", b"\x89PN"),
+            Err(DetectError::UnrecognizedBody)
+        );
+    }
+}
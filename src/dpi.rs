@@ -0,0 +1,267 @@
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// The DPI that corresponds to a scale factor of 1.
+const BASE_DPI: f32 = 96.0;
+
+/// A resolver that rewrites `href`s to high-resolution variants based on [`Options::dpi`].
+///
+/// Each variant has a minimum scale factor (`dpi / 96`) and a URL template. The variant with the
+/// highest scale factor not above the current one is used, and the original `href` is resolved if
+/// no variant applies or the variant fails to resolve.
+///
+/// The template supports the following placeholders:
+///
+/// - `{href}`: the original `href`.
+/// - `{stem}`: the `href` without its query, fragment and file extension.
+/// - `{ext}`: the file extension, without the leading dot.
+/// - `{suffix}`: the query and fragment of the `href` (e.g. `?v=1`), or an empty string.
+/// - `{scale}`: the scale factor of the variant, as an integer.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver, dpi::DpiVariantResolver};
+///
+/// let resolver = DpiVariantResolver::new(DefaultResolver)
+///     .with_variant(2.0, "{stem}@2x.{ext}{suffix}")
+///     .with_variant(3.0, "{stem}@3x.{ext}{suffix}");
+/// let options = usvg::Options { dpi: 192.0, ..usvg::Options::default() };
+/// assert_eq!(
+///     resolver.variant_href("https://example.com/logo.png?v=1", &options).as_deref(),
+///     Some("https://example.com/logo@2x.png?v=1"),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct DpiVariantResolver<T> {
+    inner: T,
+    variants: Vec<(f32, String)>,
+}
+
+impl<T> DpiVariantResolver<T> {
+    /// Create a new `DpiVariantResolver` with no variants.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Add a variant `template` used when the scale factor is at least `min_scale`.
+    pub fn with_variant(mut self, min_scale: f32, template: impl Into<String>) -> Self {
+        self.variants.push((min_scale, template.into()));
+        self.variants.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the rewritten `href` for the DPI in `options`, or `None` if no variant applies.
+    pub fn variant_href(&self, href: &str, options: &Options) -> Option<String> {
+        let scale = options.dpi / BASE_DPI;
+        let (min_scale, template) = self.variants.iter().rev().find(|(s, _)| *s <= scale)?;
+
+        Some(
+            crate::utils::expand_href_template(template, href)
+                .replace("{scale}", &min_scale.round().to_string()),
+        )
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for DpiVariantResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        if let Some(variant) = self.variant_href(href, options) {
+            if self.inner.is_target(&variant) {
+                if let Some(kind) = self.inner.get_image_kind(&variant, options) {
+                    return Some(kind);
+                }
+            }
+            crate::utils::log_warn!(
+                "failed to resolve DPI variant '{}', falling back to '{}'",
+                crate::redact::redact(&variant),
+                crate::redact::redact(href)
+            );
+        }
+        self.inner.get_image_kind(href, options)
+    }
+}
+
+/// A resolver that picks one of the candidates of a multi-candidate `href` like
+/// `image.png 1x, image@2x.png 2x` based on [`Options::dpi`].
+///
+/// Candidates are separated by commas and may be followed by a pixel density descriptor (`2x`,
+/// `1.5x`), defaulting to `1x` as in `srcset`. The candidate with the lowest density not below
+/// the scale factor (`dpi / 96`) is preferred, then the highest one below it, skipping the ones
+/// the inner resolver doesn't target. An `href` that isn't a valid candidate list, e.g. one with
+/// width descriptors, is passed on unchanged.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, dpi::SrcsetResolver};
+///
+/// let resolver = SrcsetResolver::new(DefaultResolver);
+/// let options = usvg::Options { dpi: 192.0, ..usvg::Options::default() };
+/// assert_eq!(
+///     resolver.select("logo.png, logo@2x.png 2x, logo@3x.png 3x", &options),
+///     Some("logo@2x.png"),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct SrcsetResolver<T> {
+    inner: T,
+}
+
+impl<T> SrcsetResolver<T> {
+    /// Create a new `SrcsetResolver` wrapping the given resolver.
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the candidates of `href` with their densities, or `None` if `href` is not a valid
+    /// candidate list.
+    pub fn candidates(href: &str) -> Option<Vec<(&str, f32)>> {
+        let mut candidates = Vec::new();
+        let mut rest = href;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+            if rest.is_empty() {
+                break;
+            }
+            let end = rest
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(rest.len());
+            let (url, after) = rest.split_at(end);
+            let (url, descriptors, after) = match url.strip_suffix(',') {
+                Some(url) => (url.trim_end_matches(','), "", after),
+                None => {
+                    let end = after.find(',').unwrap_or(after.len());
+                    (url, &after[..end], &after[end..])
+                }
+            };
+            let mut descriptors = descriptors.split_ascii_whitespace();
+            let density = match (descriptors.next(), descriptors.next()) {
+                (None, _) => 1.0,
+                (Some(density), None) => density
+                    .strip_suffix('x')?
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|density| density.is_finite() && *density > 0.0)?,
+                (Some(_), Some(_)) => return None,
+            };
+            candidates.push((url, density));
+            rest = after;
+        }
+        (!candidates.is_empty()).then_some(candidates)
+    }
+
+    /// Get the candidate of `href` to resolve for the DPI in `options`, or `href` itself if it is
+    /// not a candidate list, or `None` if the inner resolver targets none of the candidates.
+    pub fn select<'a, 'h>(&self, href: &'h str, options: &Options) -> Option<&'h str>
+    where
+        T: HrefStringResolver<'a>,
+    {
+        let Some(mut candidates) = Self::candidates(href) else {
+            return self.inner.is_target(href).then_some(href);
+        };
+        let scale = options.dpi / BASE_DPI;
+        candidates.sort_by(|a, b| match (a.1 >= scale, b.1 >= scale) {
+            (true, true) => a.1.total_cmp(&b.1),
+            (false, false) => b.1.total_cmp(&a.1),
+            (a, b) => b.cmp(&a),
+        });
+        candidates
+            .into_iter()
+            .map(|(url, _)| url)
+            .find(|url| self.inner.is_target(url))
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for SrcsetResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        match Self::candidates(href) {
+            Some(candidates) => candidates.iter().any(|(url, _)| self.inner.is_target(url)),
+            None => self.inner.is_target(href),
+        }
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let Some(candidate) = self.select(href, options) else {
+            crate::utils::log_warn!(
+                "no candidate of '{}' is a target",
+                crate::redact::redact(href)
+            );
+            return None;
+        };
+        self.inner.get_image_kind(candidate, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(dpi: f32) -> Options<'static> {
+        Options {
+            dpi,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn selects_variant_by_dpi() {
+        let resolver = DpiVariantResolver::new(crate::DefaultResolver)
+            .with_variant(3.0, "{href}?dpr={scale}")
+            .with_variant(2.0, "{stem}@2x.{ext}");
+
+        assert_eq!(resolver.variant_href("a/b.png", &options(96.0)), None);
+        assert_eq!(
+            resolver.variant_href("a/b.png", &options(200.0)).as_deref(),
+            Some("a/b@2x.png")
+        );
+        assert_eq!(
+            resolver.variant_href("a/b.png", &options(300.0)).as_deref(),
+            Some("a/b.png?dpr=3")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_original() {
+        let resolver =
+            DpiVariantResolver::new(crate::DefaultResolver).with_variant(2.0, "{stem}@2x.{ext}");
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options(192.0))
+            .is_some());
+    }
+
+    #[test]
+    fn selects_srcset_candidate() {
+        let resolver = SrcsetResolver::new(
+            crate::memory::MemoryResolver::new()
+                .insert_png("gray.png", include_bytes!("../test_data/gray.png")),
+        );
+        let srcset = "a.png 0.5x, gray.png, b.png 2x";
+        assert_eq!(resolver.select(srcset, &options(96.0)), Some("gray.png"));
+        assert_eq!(resolver.select(srcset, &options(120.0)), Some("gray.png"));
+        assert_eq!(resolver.select(srcset, &options(40.0)), Some("gray.png"));
+        assert!(resolver.get_image_kind(srcset, &options(192.0)).is_some());
+        assert!(!resolver.is_target("a.png 1x, b.png 2x"));
+
+        assert_eq!(
+            SrcsetResolver::<()>::candidates("a,b.png 1.5x,c.png,, d.png"),
+            Some(vec![("a,b.png", 1.5), ("c.png", 1.0), ("d.png", 1.0)])
+        );
+        assert_eq!(SrcsetResolver::<()>::candidates("a.png 100w"), None);
+        assert_eq!(SrcsetResolver::<()>::candidates("my image.png"), None);
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::{self, Read};
+
+/// Decode `body` according to the value of a `Content-Encoding` header.
+///
+/// The reqwest based resolvers leave decompression to reqwest; this is for resolvers built on
+/// other HTTP clients, which have to decode the body before the image type is detected.
+///
+/// Multiple encodings (e.g. `gzip, deflate`) are undone in reverse order of application.
+/// `gzip`, `x-gzip`, `deflate` (zlib or raw) and `identity` are supported; any other encoding,
+/// such as `br` or `zstd`, returns an error of kind [`io::ErrorKind::Unsupported`].
+///
+/// ```
+/// use usvg_remote_resolvers::encoding::decode_content;
+///
+/// assert_eq!(decode_content(None, b"data".to_vec()).unwrap(), b"data");
+/// assert!(decode_content(Some("br"), b"data".to_vec()).is_err());
+/// ```
+pub fn decode_content(content_encoding: Option<&str>, mut body: Vec<u8>) -> io::Result<Vec<u8>> {
+    let Some(content_encoding) = content_encoding else {
+        return Ok(body);
+    };
+    for encoding in content_encoding.rsplit(',').map(str::trim) {
+        body = match encoding.to_ascii_lowercase().as_str() {
+            "" | "identity" => body,
+            "gzip" | "x-gzip" => read_all(flate2::read::MultiGzDecoder::new(body.as_slice()))?,
+            "deflate" => read_all(flate2::read::ZlibDecoder::new(body.as_slice()))
+                .or_else(|_| read_all(flate2::read::DeflateDecoder::new(body.as_slice())))?,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported content encoding '{encoding}'"),
+                ))
+            }
+        };
+    }
+    Ok(body)
+}
+
+fn read_all(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    reader.read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decodes_encodings() {
+        let data = include_bytes!("../test_data/gray.png");
+        assert_eq!(decode_content(Some("GZIP"), gzip(data)).unwrap(), data);
+
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&gzip(data)).unwrap();
+        let body = encoder.finish().unwrap();
+        assert_eq!(decode_content(Some("gzip, deflate"), body).unwrap(), data);
+
+        let err = decode_content(Some("zstd"), data.to_vec()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
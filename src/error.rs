@@ -0,0 +1,195 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// The reason an `href` could not be resolved by a [`TryHrefStringResolver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The request failed, e.g. because the connection could not be established.
+    Network(String),
+    /// The request timed out.
+    Timeout,
+    /// The server responded with an unsuccessful status code.
+    Status(u16),
+    /// The server responded without a body, e.g. with 204 No Content.
+    EmptyBody,
+    /// The image type could not be detected from the given `Content-Type` or the `href`.
+    UnsupportedType(Option<String>),
+    /// The body could not be read or decoded, e.g. a nested SVG that failed to parse.
+    Decode(String),
+    /// The `href` was rejected by a policy, such as a target filter or a size limit.
+    Policy(String),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "network error: {}", e),
+            Self::Timeout => f.write_str("request timed out"),
+            Self::Status(status) => write!(f, "unsuccessful status {}", status),
+            Self::EmptyBody => f.write_str("empty response body"),
+            Self::UnsupportedType(content_type) => {
+                write!(
+                    f,
+                    "unsupported image type (content-type: {:?})",
+                    content_type
+                )
+            }
+            Self::Decode(e) => write!(f, "failed to decode image: {}", e),
+            Self::Policy(e) => write!(f, "rejected by policy: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Like [`HrefStringResolver`], but reporting why an `href` could not be resolved.
+///
+/// Use [`into_resolver`](`Self::into_resolver`) to install it into the [`Options`], and the
+/// [`ErrorSink`] of the returned adapter to collect the errors after parsing.
+pub trait TryHrefStringResolver<'a>: Send + Sync {
+    /// Check if the `href` is the target of this resolver.
+    fn is_target(&self, href: &str) -> bool;
+    /// Resolve the `href`, or return the reason it could not be resolved.
+    fn try_get_image_kind(&self, href: &str, options: &Options) -> Result<ImageKind, ResolveError>;
+    /// Convert this resolver into an [`HrefStringResolver`] recording its errors.
+    fn into_resolver(self) -> TryResolverAdapter<Self>
+    where
+        Self: Sized,
+    {
+        TryResolverAdapter::new(self)
+    }
+}
+
+/// Handle to the errors recorded by a [`TryResolverAdapter`].
+///
+/// It stays valid after the adapter is moved into the [`Options`].
+#[derive(Debug, Clone, Default)]
+pub struct ErrorSink {
+    errors: Arc<Mutex<Vec<(String, ResolveError)>>>,
+}
+
+impl ErrorSink {
+    fn push(&self, href: &str, error: ResolveError) {
+        if let Ok(mut errors) = self.errors.lock() {
+            errors.push((href.to_string(), error));
+        }
+    }
+
+    /// Take the recorded `href`s and their errors, in the order they were encountered.
+    ///
+    /// The sink is emptied, so it can be reused for the next parse.
+    pub fn take(&self) -> Vec<(String, ResolveError)> {
+        self.errors
+            .lock()
+            .map(|mut errors| std::mem::take(&mut *errors))
+            .unwrap_or_default()
+    }
+}
+
+/// An [`HrefStringResolver`] backed by a [`TryHrefStringResolver`], recording every error in an
+/// [`ErrorSink`].
+///
+/// ```
+/// # #[cfg(feature = "reqwest_blocking")]
+/// # {
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::error::TryHrefStringResolver;
+/// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+///
+/// let resolver = BlockingReqwestResolver::default().into_resolver();
+/// let errors = resolver.sink();
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// // Parse the document, then report the errors.
+/// for (href, error) in errors.take() {
+///     eprintln!("{href}: {error}");
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TryResolverAdapter<T> {
+    inner: T,
+    sink: ErrorSink,
+}
+
+impl<T> TryResolverAdapter<T> {
+    /// Create a new `TryResolverAdapter` wrapping the given resolver.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            sink: ErrorSink::default(),
+        }
+    }
+
+    /// Get a handle to the errors recorded by this adapter.
+    pub fn sink(&self) -> ErrorSink {
+        self.sink.clone()
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: TryHrefStringResolver<'a>> HrefStringResolver<'a> for TryResolverAdapter<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        match self.inner.try_get_image_kind(href, options) {
+            Ok(kind) => Some(kind),
+            Err(error) => {
+                self.sink.push(href, error);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Rejecting;
+
+    impl TryHrefStringResolver<'_> for Rejecting {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn try_get_image_kind(&self, href: &str, _: &Options) -> Result<ImageKind, ResolveError> {
+            Err(ResolveError::Policy(format!("'{href}' is not allowed")))
+        }
+    }
+
+    #[test]
+    fn collects_errors() {
+        let resolver = Rejecting.into_resolver();
+        let sink = resolver.sink();
+        let mut options = Options::default();
+        resolver.set_into_options(&mut options);
+        usvg::Tree::from_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="a.png"/></svg>"#,
+            &options,
+        )
+        .unwrap();
+
+        let errors = sink.take();
+        assert_eq!(
+            errors,
+            [(
+                "a.png".to_string(),
+                ResolveError::Policy("'a.png' is not allowed".to_string())
+            )]
+        );
+        assert_eq!(
+            errors[0].1.to_string(),
+            "rejected by policy: 'a.png' is not allowed"
+        );
+        assert!(sink.take().is_empty());
+    }
+}
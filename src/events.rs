@@ -0,0 +1,189 @@
+use std::cell::Cell;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use usvg::{ImageKind, Options};
+
+use crate::utils::json_string;
+use crate::HrefStringResolver;
+
+thread_local! {
+    static CACHE_HIT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Record that the image being resolved on this thread was served from a cache.
+pub(crate) fn record_cache_hit() {
+    CACHE_HIT.with(|hit| hit.set(true));
+}
+
+/// Run `f`, returning whether it recorded a cache hit on this thread.
+pub(crate) fn observe_cache_hit<R>(f: impl FnOnce() -> R) -> (R, bool) {
+    let outer = CACHE_HIT.with(|hit| hit.replace(false));
+    let result = f();
+    (result, CACHE_HIT.with(|hit| hit.replace(outer)))
+}
+
+/// The name of the format of `kind`, e.g. `"png"`.
+pub(crate) fn kind_name(kind: &ImageKind) -> &'static str {
+    match kind {
+        ImageKind::JPEG(_) => "jpeg",
+        ImageKind::PNG(_) => "png",
+        ImageKind::GIF(_) => "gif",
+        ImageKind::WEBP(_) => "webp",
+        ImageKind::SVG(_) => "svg",
+    }
+}
+
+/// A resolver that writes a JSON event for every stage of resolving an `href` to a writer, one
+/// object per line.
+///
+/// The events are, with the (redacted) `href` as `"href"`:
+///
+/// - `{"event":"start","href":...}` before the inner resolver is called.
+/// - `{"event":"success","href":...,"kind":"png","elapsed_ms":12}` when it resolved the image.
+/// - `{"event":"cache_hit",...}` with the same fields instead of `success` when the image was
+///   served by one of the crate's caches without downloading it.
+/// - `{"event":"failure","href":...,"elapsed_ms":12}` when it failed.
+///
+/// This is independent of the `log` feature, so per-document asset reports can be collected
+/// without parsing log output. Write errors are ignored.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver, events::EventResolver};
+///
+/// let resolver = EventResolver::new(DefaultResolver, std::io::stderr());
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Clone)]
+pub struct EventResolver<T> {
+    inner: T,
+    writer: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for EventResolver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventResolver")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> EventResolver<T> {
+    /// Create a new `EventResolver` writing the events to `writer`.
+    pub fn new(inner: T, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    fn emit(&self, event: &str, href: &str, fields: &str) {
+        let line = format!(
+            "{{\"event\":{},\"href\":{}{}}}\n",
+            json_string(event),
+            json_string(&crate::redact::redact(href)),
+            fields
+        );
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush());
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for EventResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        self.emit("start", href, "");
+        let start = Instant::now();
+        let (kind, hit) = observe_cache_hit(|| self.inner.get_image_kind(href, options));
+        let elapsed_ms = start.elapsed().as_millis();
+        match &kind {
+            Some(kind) => self.emit(
+                if hit { "cache_hit" } else { "success" },
+                href,
+                &format!(
+                    ",\"kind\":{},\"elapsed_ms\":{elapsed_ms}",
+                    json_string(kind_name(kind))
+                ),
+            ),
+            None => self.emit("failure", href, &format!(",\"elapsed_ms\":{elapsed_ms}")),
+        }
+        kind
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_events() {
+        let buffer = Buffer::default();
+        let resolver = EventResolver::new(crate::DefaultResolver, buffer.clone());
+        let options = Options::default();
+        resolver.get_image_kind("./test_data/gray.png", &options);
+        resolver.get_image_kind("./test_data/missing.png", &options);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<_> = output.lines().collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            r#"{"event":"start","href":"./test_data/gray.png"}"#
+        );
+        assert!(events[1].starts_with(
+            r#"{"event":"success","href":"./test_data/gray.png","kind":"png","elapsed_ms":"#
+        ));
+        assert!(events[3]
+            .starts_with(r#"{"event":"failure","href":"./test_data/missing.png","elapsed_ms":"#));
+    }
+
+    #[test]
+    fn reports_cache_hits() {
+        struct Cached;
+        impl HrefStringResolver<'_> for Cached {
+            fn is_target(&self, _: &str) -> bool {
+                true
+            }
+            fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+                record_cache_hit();
+                crate::DefaultResolver.get_image_kind(href, options)
+            }
+        }
+
+        let buffer = Buffer::default();
+        let resolver = EventResolver::new(Cached, buffer.clone());
+        resolver.get_image_kind("./test_data/gray.png", &Options::default());
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(output
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with(r#"{"event":"cache_hit""#));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::HrefStringResolver;
+
+/// What a layer of a composed resolver does with an `href`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The layer would resolve the `href`, or pass it on to the layers below.
+    Matched,
+    /// The `href` is not a target of the layer.
+    NotTarget,
+    /// The layer would reject the `href` without resolving it, for the given reason.
+    Denied(String),
+}
+
+/// One layer of a [`ResolutionTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// How deep the layer is nested, `0` for the outermost one.
+    pub depth: usize,
+    /// The type name of the layer, without its module path and generic arguments.
+    pub layer: &'static str,
+    /// What the layer does with the `href`.
+    pub outcome: Outcome,
+}
+
+/// A report of how the layers of a composed resolver handle an `href`, created with
+/// [`HrefStringResolver::explain`].
+///
+/// Nothing is fetched or read while explaining, so a layer that matched may still fail to resolve
+/// the `href`, and only the checks a layer can make up front (target filters, sandboxes, recently
+/// failed `href`s) are reported as denials.
+///
+/// ```
+/// use usvg_remote_resolvers::explain::Outcome;
+/// use usvg_remote_resolvers::memory::MemoryResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let resolver = MemoryResolver::new()
+///     .insert_png("logo.png", Vec::new())
+///     .with_fallback(DefaultResolver);
+/// let trace = resolver.explain("photo.png");
+/// assert_eq!(trace.steps()[1].layer, "MemoryResolver");
+/// assert_eq!(trace.steps()[1].outcome, Outcome::NotTarget);
+/// println!("{trace}");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResolutionTrace {
+    href: String,
+    steps: Vec<TraceStep>,
+    depth: usize,
+}
+
+impl ResolutionTrace {
+    /// Create a new empty trace for `href`.
+    pub fn new(href: impl Into<String>) -> Self {
+        Self {
+            href: href.into(),
+            steps: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    /// Get the traced `href`.
+    pub fn href(&self) -> &str {
+        &self.href
+    }
+
+    /// Get the recorded layers, outermost first.
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    /// Returns `true` if the outermost layer matched the `href`.
+    pub fn is_target(&self) -> bool {
+        self.steps
+            .first()
+            .is_some_and(|step| step.outcome == Outcome::Matched)
+    }
+
+    /// Get the first layer that denied the `href`, if any.
+    pub fn denied_by(&self) -> Option<&TraceStep> {
+        self.steps
+            .iter()
+            .find(|step| matches!(step.outcome, Outcome::Denied(_)))
+    }
+
+    /// Record what the layer `R` does with the `href`, at the current depth.
+    pub fn push<R: ?Sized>(&mut self, outcome: Outcome) {
+        self.steps.push(TraceStep {
+            depth: self.depth,
+            layer: short_type_name::<R>(),
+            outcome,
+        });
+    }
+
+    /// Record the layers below the last one with `f`.
+    pub fn nested(&mut self, f: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        f(self);
+        self.depth -= 1;
+    }
+
+    /// Record the layer `R`, which passes the `href` on to `inner`, followed by the layers of
+    /// `inner`.
+    pub fn wrap<'a, R: ?Sized>(&mut self, inner: &(impl HrefStringResolver<'a> + ?Sized)) {
+        let outcome = if inner.is_target(&self.href) {
+            Outcome::Matched
+        } else {
+            Outcome::NotTarget
+        };
+        self.push::<R>(outcome);
+        self.nested(|trace| inner.explain_into(trace));
+    }
+}
+
+impl std::fmt::Display for ResolutionTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}'", crate::redact::redact(&self.href))?;
+        for step in &self.steps {
+            write!(
+                f,
+                "\n{:indent$}{}: ",
+                "",
+                step.layer,
+                indent = step.depth * 2
+            )?;
+            match &step.outcome {
+                Outcome::Matched => f.write_str("matched")?,
+                Outcome::NotTarget => f.write_str("not a target")?,
+                Outcome::Denied(reason) => write!(f, "denied ({reason})")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of `T` without its module path and generic arguments.
+fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split_once('<').map_or(name, |(name, _)| name);
+    name.rsplit_once("::").map_or(name, |(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryResolver;
+    use crate::target::{PolicyResolver, TargetFilter};
+    use crate::{DefaultResolver, SandboxedLocalResolver};
+
+    #[test]
+    fn explains_composed_resolvers() {
+        let resolver = crate::strict::StrictResolver::new(
+            PolicyResolver::new(
+                MemoryResolver::new().insert_png("https://example.com/a.png", Vec::new()),
+                TargetFilter::new().with_denied_host_suffix("example.com"),
+            )
+            .with_fallback(SandboxedLocalResolver::new("./test_data")),
+        );
+
+        let trace = resolver.explain("https://example.com/a.png");
+        let layers: Vec<_> = trace.steps().iter().map(|s| (s.depth, s.layer)).collect();
+        assert_eq!(
+            layers,
+            [
+                (0, "StrictResolver"),
+                (1, "FallbackResolver"),
+                (2, "PolicyResolver"),
+                (2, "SandboxedLocalResolver"),
+            ]
+        );
+        assert_eq!(trace.denied_by().unwrap().layer, "PolicyResolver");
+        assert_eq!(trace.steps()[3].outcome, Outcome::NotTarget);
+
+        let trace = resolver.explain("../Cargo.toml");
+        assert_eq!(trace.denied_by().unwrap().layer, "SandboxedLocalResolver");
+        assert!(trace
+            .to_string()
+            .contains("\n    SandboxedLocalResolver: denied"));
+
+        let trace = DefaultResolver.explain("gray.png");
+        assert!(trace.is_target());
+        assert_eq!(trace.steps().len(), 1);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A lock service electing one fetcher per cache key among the processes sharing a cache store,
+/// e.g. the workers of a render farm, used with
+/// [`with_fetch_lock`](crate::reqwest_blocking::CachedBlockingReqwestResolver::with_fetch_lock).
+///
+/// Implement this over a shared lock service, e.g. `SET key NX PX ttl` in Redis or a lease in
+/// etcd. The process holding the lock of a key fetches it and stores it in the cache, while the
+/// others wait for the entry to show up there.
+pub trait FetchLock: std::fmt::Debug + Send + Sync {
+    /// Try to take the lock of the cache key `key`, returning `true` if it was taken.
+    ///
+    /// The lock must expire after `ttl` even if it is never released, so a crashed process doesn't
+    /// hold it forever.
+    fn try_lock(&self, key: &str, ttl: Duration) -> bool;
+    /// Release the lock of `key` taken with [`try_lock`](Self::try_lock).
+    fn unlock(&self, key: &str);
+}
+
+impl<L: FetchLock + ?Sized> FetchLock for Arc<L> {
+    fn try_lock(&self, key: &str, ttl: Duration) -> bool {
+        (**self).try_lock(key, ttl)
+    }
+    fn unlock(&self, key: &str) {
+        (**self).unlock(key)
+    }
+}
+
+/// A [`FetchLock`] in memory, electing one fetcher among the threads of a process.
+///
+/// Clones share the same locks.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFetchLock(Arc<Mutex<HashMap<String, Instant>>>);
+
+impl MemoryFetchLock {
+    /// Create a new `MemoryFetchLock` with no key locked.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl FetchLock for MemoryFetchLock {
+    fn try_lock(&self, key: &str, ttl: Duration) -> bool {
+        let mut locks = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        match locks.get(key) {
+            Some(expires) if now < *expires => false,
+            _ => {
+                locks.insert(key.to_string(), now + ttl);
+                true
+            }
+        }
+    }
+    fn unlock(&self, key: &str) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_lock_expires() {
+        let lock = MemoryFetchLock::new();
+        assert!(lock.try_lock("a", Duration::from_secs(60)));
+        assert!(!lock.clone().try_lock("a", Duration::from_secs(60)));
+        assert!(lock.try_lock("b", Duration::from_secs(60)));
+        lock.unlock("a");
+        assert!(lock.try_lock("a", Duration::from_millis(10)));
+
+        // The lock of a holder that never releases it becomes stale after its ttl.
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(lock.try_lock("a", Duration::from_secs(60)));
+
+        // A panic while the locks are held doesn't break them.
+        let poisoned = lock.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoned.0.lock().unwrap();
+            panic!("poison the locks");
+        })
+        .join();
+        assert!(!lock.try_lock("a", Duration::from_secs(60)));
+        lock.unlock("a");
+        assert!(lock.try_lock("a", Duration::from_secs(60)));
+    }
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn one_fetcher_per_key() {
+        use crate::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+        use crate::HrefStringResolver;
+
+        let mut s = mockito::Server::new();
+        let mock = s
+            .mock("GET", "/gray.png")
+            .with_header("content-type", "image/png")
+            .with_header("etag", "\"a\"")
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_millis(200));
+                w.write_all(include_bytes!("../test_data/gray.png"))
+            })
+            .expect(1)
+            .create();
+        let href = format!("{}/gray.png", s.url());
+        let store = Arc::new(MemoryHttpCacheStore::new());
+        let lock = MemoryFetchLock::new();
+        // Two workers sharing a cache store and a lock service.
+        let workers: Vec<_> = (0..2)
+            .map(|_| {
+                BlockingReqwestResolver::default()
+                    .with_cache(store.clone())
+                    .with_fetch_lock(lock.clone(), Duration::from_secs(5))
+            })
+            .collect();
+        let barrier = std::sync::Barrier::new(workers.len());
+        std::thread::scope(|s| {
+            for worker in &workers {
+                let (href, barrier) = (&href, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    let kind = worker.get_image_kind(href, &usvg::Options::default());
+                    assert!(matches!(kind, Some(usvg::ImageKind::PNG(_))));
+                });
+            }
+        });
+        mock.assert();
+        assert!(lock.try_lock(&workers[0].cache_key(&href), Duration::from_secs(60)));
+    }
+}
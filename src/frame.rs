@@ -0,0 +1,305 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// Which frame of an animated image is rendered by [`FrameResolver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSelection {
+    /// The frame at the given index, or the last frame if the animation is shorter.
+    Index(usize),
+    /// The frame shown at the given time since the start of the animation, or the last frame if
+    /// the animation is shorter. The animation is not looped.
+    Time(Duration),
+}
+
+impl Default for FrameSelection {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+/// Decode the frame chosen by `selection` from an animated GIF or WebP image and re-encode it as a
+/// static PNG image.
+///
+/// Returns `None` if `kind` is not an animated GIF or WebP image, can't be decoded, or has more
+/// than 64 megapixels.
+pub fn select_frame(kind: &ImageKind, selection: FrameSelection) -> Option<ImageKind> {
+    select_frame_within(kind, selection, crate::utils::DEFAULT_MAX_PIXELS)
+}
+
+fn select_frame_within(
+    kind: &ImageKind,
+    selection: FrameSelection,
+    max_pixels: u64,
+) -> Option<ImageKind> {
+    let frame = match kind {
+        ImageKind::GIF(data) => gif_frame(data, selection, max_pixels),
+        ImageKind::WEBP(data) => webp_frame(data, selection, max_pixels),
+        _ => None,
+    }?;
+    Some(ImageKind::PNG(Arc::new(frame.encode_png()?)))
+}
+
+struct Frame {
+    width: u32,
+    height: u32,
+    color: png::ColorType,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    fn encode_png(&self) -> Option<Vec<u8>> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(self.color);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().ok()?;
+        writer.write_image_data(&self.pixels).ok()?;
+        writer.finish().ok()?;
+        Some(png)
+    }
+}
+
+/// Tracks the elapsed time of an animation to find the selected frame.
+struct Selector {
+    selection: FrameSelection,
+    index: usize,
+    elapsed: Duration,
+}
+
+impl Selector {
+    fn new(selection: FrameSelection) -> Self {
+        Self {
+            selection,
+            index: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Advance past a frame shown for `duration`, returning whether it is the selected one.
+    fn is_selected(&mut self, duration: Duration) -> bool {
+        self.elapsed += duration;
+        self.index += 1;
+        match self.selection {
+            FrameSelection::Index(index) => self.index > index,
+            FrameSelection::Time(time) => self.elapsed > time,
+        }
+    }
+}
+
+fn gif_frame(data: &[u8], selection: FrameSelection, max_pixels: u64) -> Option<Frame> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(data).ok()?;
+    let (width, height) = (usize::from(decoder.width()), usize::from(decoder.height()));
+    let size = crate::utils::pixel_buffer_size(width as u32, height as u32, 4, max_pixels)?;
+    let mut canvas = vec![0; size];
+    let mut selector = Selector::new(selection);
+    let mut frames = 0;
+    while let Some(frame) = decoder.read_next_frame().ok()? {
+        frames += 1;
+        let previous = (frame.dispose == gif::DisposalMethod::Previous).then(|| canvas.clone());
+        let (left, top) = (usize::from(frame.left), usize::from(frame.top));
+        let frame_width = usize::from(frame.width);
+        let rows = frame.buffer.chunks_exact(frame_width * 4);
+        for (y, row) in (top..height).zip(rows) {
+            for (x, pixel) in (left..width).zip(row.chunks_exact(4)) {
+                if pixel[3] != 0 {
+                    let i = (y * width + x) * 4;
+                    canvas[i..i + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+        if selector.is_selected(Duration::from_millis(u64::from(frame.delay) * 10)) {
+            break;
+        }
+        match frame.dispose {
+            gif::DisposalMethod::Background => {
+                let bottom = (top + usize::from(frame.height)).min(height);
+                let right = (left + frame_width).min(width);
+                for y in top..bottom {
+                    canvas[(y * width + left.min(right)) * 4..(y * width + right) * 4].fill(0);
+                }
+            }
+            gif::DisposalMethod::Previous => canvas = previous?,
+            _ => {}
+        }
+    }
+    // A static image is rendered as is.
+    if frames <= 1 && decoder.next_frame_info().ok()?.is_none() {
+        return None;
+    }
+    Some(Frame {
+        width: width as u32,
+        height: height as u32,
+        color: png::ColorType::Rgba,
+        pixels: canvas,
+    })
+}
+
+fn webp_frame(data: &[u8], selection: FrameSelection, max_pixels: u64) -> Option<Frame> {
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(data)).ok()?;
+    if !decoder.is_animated() {
+        return None;
+    }
+    let (width, height) = decoder.dimensions();
+    crate::utils::pixel_buffer_size(width, height, 4, max_pixels)?;
+    let mut pixels = vec![0; decoder.output_buffer_size()?];
+    let mut selector = Selector::new(selection);
+    for _ in 0..decoder.num_frames() {
+        let duration = decoder.read_frame(&mut pixels).ok()?;
+        if selector.is_selected(Duration::from_millis(u64::from(duration))) {
+            break;
+        }
+    }
+    Some(Frame {
+        width,
+        height,
+        color: if decoder.has_alpha() {
+            png::ColorType::Rgba
+        } else {
+            png::ColorType::Rgb
+        },
+        pixels,
+    })
+}
+
+/// A resolver that replaces animated GIF and WebP images resolved by the inner resolver with a
+/// static PNG image of the frame chosen by a [`FrameSelection`].
+///
+/// resvg renders a single frame of animated images, and which one is not specified. This makes the
+/// rendered frame explicit. Static images, images that can't be decoded and images larger than
+/// the pixel budget (64 megapixels by default) are passed through unchanged.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+/// use usvg_remote_resolvers::frame::{FrameResolver, FrameSelection};
+///
+/// let resolver = FrameResolver::new(DefaultResolver).with_selection(FrameSelection::Index(2));
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameResolver<T> {
+    inner: T,
+    selection: FrameSelection,
+    max_pixels: u64,
+}
+
+impl<T> FrameResolver<T> {
+    /// Create a new `FrameResolver` selecting the first frame.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            selection: FrameSelection::default(),
+            max_pixels: crate::utils::DEFAULT_MAX_PIXELS,
+        }
+    }
+
+    /// Set which frame is selected.
+    pub fn with_selection(mut self, selection: FrameSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Set the maximum number of pixels of the images whose frames are decoded.
+    ///
+    /// Decoding allocates a canvas of the size declared in the header of the image, so larger
+    /// images are passed through without decoding them.
+    pub fn with_max_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for FrameResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let kind = self.inner.get_image_kind(href, options)?;
+        Some(select_frame_within(&kind, self.selection, self.max_pixels).unwrap_or(kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    fn animated_gif(frames: &[[u8; 4]]) -> ImageKind {
+        let mut data = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut data, 1, 1, &[]).unwrap();
+        for color in frames {
+            let mut frame = gif::Frame::from_rgba(1, 1, &mut color.clone());
+            frame.delay = 10;
+            encoder.write_frame(&frame).unwrap();
+        }
+        drop(encoder);
+        ImageKind::GIF(Arc::new(data))
+    }
+
+    fn first_pixel(kind: &ImageKind) -> [u8; 4] {
+        let ImageKind::PNG(data) = kind else {
+            panic!("not a PNG image");
+        };
+        let mut reader = png::Decoder::new(Cursor::new(&data[..]))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+        pixels[..4].try_into().unwrap()
+    }
+
+    #[test]
+    fn selects_gif_frame() {
+        let gif = animated_gif(&[RED, BLUE]);
+        let first = select_frame(&gif, FrameSelection::default()).unwrap();
+        assert_eq!(first_pixel(&first), RED);
+        let second = select_frame(&gif, FrameSelection::Index(1)).unwrap();
+        assert_eq!(first_pixel(&second), BLUE);
+        let last = select_frame(&gif, FrameSelection::Index(5)).unwrap();
+        assert_eq!(first_pixel(&last), BLUE);
+        let timed = select_frame(&gif, FrameSelection::Time(Duration::from_millis(150))).unwrap();
+        assert_eq!(first_pixel(&timed), BLUE);
+    }
+
+    #[test]
+    fn skips_images_over_the_pixel_budget() {
+        let mut data = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut data, u16::MAX, u16::MAX, &[]).unwrap();
+        for color in [RED, BLUE] {
+            let frame = gif::Frame::from_rgba(1, 1, &mut color.clone());
+            encoder.write_frame(&frame).unwrap();
+        }
+        drop(encoder);
+        assert!(data.len() < 100);
+        let oversized = ImageKind::GIF(Arc::new(data));
+        assert!(select_frame(&oversized, FrameSelection::default()).is_none());
+
+        let gif = animated_gif(&[RED, BLUE]);
+        assert!(select_frame_within(&gif, FrameSelection::default(), 0).is_none());
+        assert!(select_frame_within(&gif, FrameSelection::default(), 1).is_some());
+    }
+
+    #[test]
+    fn keeps_static_images() {
+        assert!(select_frame(&animated_gif(&[RED]), FrameSelection::default()).is_none());
+        let png = ImageKind::PNG(Arc::new(std::fs::read("./test_data/gray.png").unwrap()));
+        assert!(select_frame(&png, FrameSelection::default()).is_none());
+    }
+}
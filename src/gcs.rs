@@ -0,0 +1,356 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::hooks::Hook;
+use crate::utils::ImageKindTypes;
+use crate::HrefStringResolver;
+
+/// The Cloud Storage endpoint used by default.
+const DEFAULT_ENDPOINT: &str = "https://storage.googleapis.com";
+/// The metadata server endpoint issuing tokens for the service account of the instance.
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+/// The OAuth 2.0 endpoint exchanging refresh tokens for access tokens.
+const OAUTH_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+/// How long before its expiry a cached access token is refreshed.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Check if the `href` is a Google Cloud Storage URL (`gs://`).
+pub fn is_gcs_url(href: &str) -> bool {
+    href.starts_with("gs://")
+}
+
+/// Parse a `gs://bucket/object` URL into `(bucket, object)`.
+fn parse_gcs_url(href: &str) -> Option<(&str, &str)> {
+    let rest = href.strip_prefix("gs://")?;
+    let (bucket, object) = rest.split_once('/')?;
+    if bucket.is_empty() || object.is_empty() {
+        return None;
+    }
+    Some((bucket, object))
+}
+
+type TokenProvider = Hook<dyn Fn() -> Option<String> + Send + Sync>;
+
+#[derive(Debug, Clone)]
+enum Credentials {
+    Anonymous,
+    /// The cached access token with its expiry.
+    ApplicationDefault(Arc<Mutex<Option<(String, Instant)>>>),
+    Provider(TokenProvider),
+}
+
+/// A resolver for `gs://bucket/object` URLs, downloading objects through the Cloud Storage JSON
+/// API with the blocking reqwest client.
+///
+/// By default, requests are authenticated with application default credentials, looked up in
+/// this order:
+///
+/// 1. The credentials file named by the `GOOGLE_APPLICATION_CREDENTIALS` environment variable.
+/// 2. The file written by `gcloud auth application-default login`.
+/// 3. The metadata server, on Compute Engine, Cloud Run, GKE and other Google Cloud runtimes.
+///
+/// Only user credentials (`"type": "authorized_user"`) are supported in credentials files, since
+/// service account keys need an RSA signer; use [`with_token_provider`](Self::with_token_provider)
+/// with an auth library for those. Access tokens are cached until shortly before they expire.
+///
+/// The image type is detected from the content type of the object, or the extension of its name.
+///
+/// ```no_run
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::gcs::GcsResolver;
+///
+/// let resolver = GcsResolver::default();
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GcsResolver {
+    client: reqwest::blocking::Client,
+    endpoint: String,
+    credentials: Credentials,
+}
+
+impl Default for GcsResolver {
+    fn default() -> Self {
+        Self::new(reqwest::blocking::Client::new())
+    }
+}
+
+impl GcsResolver {
+    /// Create a new `GcsResolver` with the given client, authenticated with application default
+    /// credentials.
+    pub fn new(client: reqwest::blocking::Client) -> Self {
+        Self {
+            client,
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            credentials: Credentials::ApplicationDefault(Arc::default()),
+        }
+    }
+
+    /// Get the underlying [`Client`](`reqwest::blocking::Client`) of this resolver.
+    pub fn client(&self) -> &reqwest::blocking::Client {
+        &self.client
+    }
+
+    /// Send requests to `endpoint` instead of `https://storage.googleapis.com`, e.g. to a local
+    /// emulator.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Authenticate requests with the access token returned by `provider`, which is called for
+    /// every request and should cache the token itself.
+    ///
+    /// The object is not fetched if `provider` returns `None`.
+    pub fn with_token_provider(
+        mut self,
+        provider: impl Fn() -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.credentials = Credentials::Provider(Hook(Arc::new(provider)));
+        self
+    }
+
+    /// Send requests without credentials, for public buckets.
+    pub fn without_credentials(mut self) -> Self {
+        self.credentials = Credentials::Anonymous;
+        self
+    }
+
+    /// Get the access token to authenticate requests with, `Ok(None)` for anonymous requests.
+    fn token(&self) -> Result<Option<String>, ()> {
+        match &self.credentials {
+            Credentials::Anonymous => Ok(None),
+            Credentials::Provider(provider) => provider().map(Some).ok_or(()),
+            Credentials::ApplicationDefault(cached) => {
+                let mut cached = cached.lock().map_err(|_| ())?;
+                if let Some((token, expiry)) = &*cached {
+                    if Instant::now() + TOKEN_REFRESH_MARGIN < *expiry {
+                        return Ok(Some(token.clone()));
+                    }
+                }
+                let (token, expires_in) = application_default_token(&self.client).ok_or(())?;
+                *cached = Some((token.clone(), Instant::now() + expires_in));
+                Ok(Some(token))
+            }
+        }
+    }
+}
+
+/// The path of the credentials file written by `gcloud auth application-default login`.
+fn well_known_credentials_path() -> Option<PathBuf> {
+    let config = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else {
+        PathBuf::from(std::env::var_os("HOME")?).join(".config")
+    };
+    Some(config.join("gcloud/application_default_credentials.json"))
+}
+
+/// Get an access token with its lifetime from the application default credentials.
+fn application_default_token(client: &reqwest::blocking::Client) -> Option<(String, Duration)> {
+    let path = match std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => well_known_credentials_path().filter(|path| path.is_file()),
+    };
+    let Some(path) = path else {
+        let resp = client
+            .get(METADATA_TOKEN_URL)
+            .header("metadata-flavor", "Google")
+            .send();
+        return parse_token_response(resp);
+    };
+
+    let credentials = match std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            serde_json::from_slice::<serde_json::Value>(&data).map_err(|e| e.to_string())
+        }) {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            crate::utils::log_warn!("failed to read credentials '{}': {}", path.display(), e);
+            return None;
+        }
+    };
+    match credentials["type"].as_str() {
+        Some("authorized_user") => {
+            let field = |name: &str| credentials[name].as_str().unwrap_or_default();
+            let resp = client
+                .post(OAUTH_TOKEN_URL)
+                .form(&[
+                    ("grant_type", "refresh_token"),
+                    ("client_id", field("client_id")),
+                    ("client_secret", field("client_secret")),
+                    ("refresh_token", field("refresh_token")),
+                ])
+                .send();
+            parse_token_response(resp)
+        }
+        other => {
+            crate::utils::log_warn!(
+                "unsupported credentials type {:?} in '{}'",
+                other,
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Parse an OAuth 2.0 token response into the access token and its lifetime.
+fn parse_token_response(
+    resp: reqwest::Result<reqwest::blocking::Response>,
+) -> Option<(String, Duration)> {
+    let resp = match resp.and_then(|resp| resp.error_for_status()) {
+        Ok(resp) => resp,
+        Err(e) => {
+            crate::utils::log_warn!("failed to get an access token: {}", e);
+            return None;
+        }
+    };
+    let body: serde_json::Value = serde_json::from_slice(&resp.bytes().ok()?).ok()?;
+    let token = body["access_token"].as_str()?.to_string();
+    let expires_in = body["expires_in"].as_u64().unwrap_or(3600);
+    Some((token, Duration::from_secs(expires_in)))
+}
+
+impl HrefStringResolver<'_> for GcsResolver {
+    fn is_target(&self, href: &str) -> bool {
+        is_gcs_url(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
+        let Some((bucket, object)) = parse_gcs_url(href) else {
+            crate::utils::log_warn!("invalid GCS URL: '{}'", crate::redact::redact(href));
+            return None;
+        };
+        let Some(mut url) = url::Url::parse(&self.endpoint)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+        else {
+            crate::utils::log_warn!("invalid GCS endpoint: '{}'", self.endpoint);
+            return None;
+        };
+        url.path_segments_mut()
+            .ok()?
+            .pop_if_empty()
+            .extend(["storage", "v1", "b", bucket, "o", object]);
+        url.set_query(Some("alt=media"));
+
+        let mut req = self.client.get(url);
+        match self.token() {
+            Ok(Some(token)) => req = req.bearer_auth(token),
+            Ok(None) => {}
+            Err(()) => {
+                crate::utils::log_warn!(
+                    "no GCS credentials; cannot resolve '{}'",
+                    crate::redact::redact(href)
+                );
+                return None;
+            }
+        }
+        let resp = match req.send() {
+            Ok(resp) if resp.status().is_success() => resp,
+            Ok(resp) => {
+                crate::utils::log_warn!(
+                    "GCS request failed for '{}': status {}",
+                    crate::redact::redact(href),
+                    resp.status()
+                );
+                return None;
+            }
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "GCS request failed for '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
+                );
+                return None;
+            }
+        };
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let unsupported = || {
+            crate::utils::log_warn!(
+                "unsupported image type for '{}' (content-type: {:?})",
+                crate::redact::redact(href),
+                content_type
+            );
+        };
+        if ImageKindTypes::get_image_type(content_type.as_deref(), object).is_none()
+            && !ImageKindTypes::is_generic(content_type.as_deref())
+        {
+            unsupported();
+            return None;
+        }
+        let body = match resp.bytes() {
+            Ok(body) => body.to_vec(),
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "failed to read GCS response body for '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
+                );
+                return None;
+            }
+        };
+        if crate::utils::is_empty_body(href, &body) {
+            return None;
+        }
+        let Some(image_type) = ImageKindTypes::detect(content_type.as_deref(), object, &body)
+        else {
+            unsupported();
+            return None;
+        };
+        image_type.into_image_kind_from(body.into(), href, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gcs_url() {
+        assert_eq!(
+            parse_gcs_url("gs://my-bucket/path/to/image.png"),
+            Some(("my-bucket", "path/to/image.png"))
+        );
+        assert_eq!(parse_gcs_url("gs://my-bucket/"), None);
+        assert_eq!(parse_gcs_url("gs:///key"), None);
+        assert_eq!(parse_gcs_url("s3://bucket/key"), None);
+    }
+
+    #[test]
+    fn gcs_resolver() {
+        let mut s = mockito::Server::new();
+        let mock = s
+            .mock("GET", "/storage/v1/b/bucket/o/img%2Fgray.png")
+            .match_query(mockito::Matcher::UrlEncoded("alt".into(), "media".into()))
+            .match_header("authorization", "Bearer secret")
+            .with_status(200)
+            .with_header("content-type", "application/octet-stream")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let resolver = GcsResolver::default()
+            .with_endpoint(s.url())
+            .with_token_provider(|| Some("secret".to_string()));
+        assert!(resolver.is_target("gs://bucket/img/gray.png"));
+        let options = usvg::Options::default();
+        assert!(matches!(
+            resolver.get_image_kind("gs://bucket/img/gray.png", &options),
+            Some(usvg::ImageKind::PNG(_))
+        ));
+        mock.assert();
+
+        let resolver = resolver.with_token_provider(|| None);
+        assert!(resolver
+            .get_image_kind("gs://bucket/img/gray.png", &options)
+            .is_none());
+    }
+}
@@ -0,0 +1,190 @@
+/// Metadata about the HTTP response an image was fetched from.
+///
+/// This is passed to the response hooks of the HTTP resolvers (e.g.
+/// [`BlockingReqwestResolver::with_response_hook`](`crate::reqwest_blocking::BlockingReqwestResolver::with_response_hook`))
+/// together with the resolved image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseMetadata {
+    /// The `href` that was requested.
+    pub href: String,
+    /// The final URL of the response, after following redirects.
+    pub final_url: String,
+    /// The URLs that answered with a redirect before `final_url`, in order, starting with the
+    /// `href`. Empty if there were no redirects, or if the resolver does not record them (see
+    /// [`ClientConfig::with_max_redirects`](`crate::client::ClientConfig::with_max_redirects`)).
+    pub redirects: Vec<String>,
+    /// The HTTP status code of the response.
+    pub status: u16,
+    /// The response headers. Names are lowercase.
+    pub headers: Vec<(String, String)>,
+}
+
+impl ResponseMetadata {
+    #[cfg(any(
+        feature = "reqwest",
+        feature = "reqwest_blocking",
+        feature = "reqwest_http_cache"
+    ))]
+    pub(crate) fn from_reqwest(
+        href: &str,
+        final_url: &reqwest::Url,
+        status: reqwest::StatusCode,
+        headers: &reqwest::header::HeaderMap,
+    ) -> Self {
+        Self {
+            href: href.to_string(),
+            final_url: final_url.to_string(),
+            redirects: Vec::new(),
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+        }
+    }
+
+    /// The hosts of the redirect chain, including the host of the final URL, with consecutive
+    /// duplicates removed.
+    pub fn redirect_hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = self
+            .redirects
+            .iter()
+            .chain(std::iter::once(&self.final_url))
+            .filter_map(|url| Some(url::Url::parse(url).ok()?.host_str()?.to_string()))
+            .collect();
+        hosts.dedup();
+        hosts
+    }
+
+    /// Get the first value of the header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A hook stored in a resolver, shared between clones.
+pub(crate) struct Hook<F: ?Sized>(pub(crate) std::sync::Arc<F>);
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Self(std::sync::Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> std::fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+impl<F: ?Sized> std::ops::Deref for Hook<F> {
+    type Target = F;
+    fn deref(&self) -> &F {
+        &self.0
+    }
+}
+
+/// Hook called with the response metadata and the image resolved from it.
+#[cfg(any(
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "reqwest_http_cache"
+))]
+pub(crate) type ResponseHook = Hook<dyn Fn(&ResponseMetadata, &usvg::ImageKind) + Send + Sync>;
+
+/// Hook called with the `href`, the content type and the raw bytes of every fetched body.
+#[cfg(any(
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "reqwest_http_cache"
+))]
+pub(crate) type BodyHook = Hook<dyn Fn(&str, Option<&str>, std::sync::Arc<Vec<u8>>) + Send + Sync>;
+
+/// Verifier called with the `href`, the body and the detached signature of every SVG image.
+#[cfg(any(
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "reqwest_http_cache",
+    feature = "hyper"
+))]
+pub(crate) type SvgVerifier = Hook<dyn Fn(&str, &[u8], &[u8]) -> bool + Send + Sync>;
+
+/// Check the detached `signature` of the SVG image `body` of `href` with `verifier`, logging why
+/// the image is rejected. Images whose signature could not be fetched are rejected.
+#[cfg(any(feature = "reqwest", feature = "reqwest_http_cache", feature = "hyper"))]
+pub(crate) fn verify_svg(
+    verifier: &SvgVerifier,
+    href: &str,
+    body: &[u8],
+    signature: Option<&[u8]>,
+) -> bool {
+    let Some(signature) = signature else {
+        crate::utils::log_warn!(
+            "failed to fetch the signature of '{}'",
+            crate::redact::redact(href)
+        );
+        return false;
+    };
+    if !verifier(href, body, signature) {
+        crate::utils::log_warn!(
+            "rejected SVG with an invalid signature: '{}'",
+            crate::redact::redact(href)
+        );
+        return false;
+    }
+    true
+}
+
+#[cfg(all(
+    test,
+    any(
+        feature = "reqwest",
+        feature = "reqwest_blocking",
+        feature = "reqwest_http_cache"
+    )
+))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_from_redirected_response() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Content-Type", "image/png".parse().unwrap());
+        headers.append("x-cache", "HIT".parse().unwrap());
+        headers.append("x-cache", "MISS".parse().unwrap());
+        headers.insert(
+            "x-binary",
+            reqwest::header::HeaderValue::from_bytes(b"\xff").unwrap(),
+        );
+        let final_url = reqwest::Url::parse("https://cdn.example.net/gray.png").unwrap();
+        let metadata = ResponseMetadata {
+            redirects: vec![
+                "https://example.com/gray.png".to_string(),
+                "https://example.com/moved/gray.png".to_string(),
+            ],
+            ..ResponseMetadata::from_reqwest(
+                "https://example.com/gray.png",
+                &final_url,
+                reqwest::StatusCode::OK,
+                &headers,
+            )
+        };
+
+        assert_eq!(metadata.href, "https://example.com/gray.png");
+        assert_eq!(metadata.final_url, "https://cdn.example.net/gray.png");
+        assert_eq!(metadata.status, 200);
+        assert_eq!(metadata.header("CONTENT-TYPE"), Some("image/png"));
+        assert_eq!(metadata.header("X-Cache"), Some("HIT"));
+        assert_eq!(metadata.header("x-binary"), None);
+        assert_eq!(metadata.header("etag"), None);
+        assert_eq!(
+            metadata.redirect_hosts(),
+            ["example.com", "cdn.example.net"]
+        );
+    }
+}
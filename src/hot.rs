@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::reqwest_blocking::{HttpCacheEntry, HttpCacheStore};
+use crate::utils::ImageKindTypes;
+
+/// An [`HttpCacheStore`] counting how often each entry of the inner store is used, to export the
+/// most used ones into the application with [`write_module`](Self::write_module).
+///
+/// Every lookup of a stored entry counts as a use, whether it is fresh or revalidated. Share the
+/// store through an [`Arc`](std::sync::Arc) to keep access to the counts after passing it to a
+/// resolver.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use usvg_remote_resolvers::hot::UsageCountingStore;
+/// use usvg_remote_resolvers::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+///
+/// let store = Arc::new(UsageCountingStore::new(MemoryHttpCacheStore::new()));
+/// let resolver = BlockingReqwestResolver::default().with_cache(store.clone());
+/// // ... render for a while ...
+/// store.write_module(50, "src/hot_assets.rs").unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct UsageCountingStore<S> {
+    inner: S,
+    uses: Mutex<HashMap<String, u64>>,
+}
+
+impl<S: HttpCacheStore> UsageCountingStore<S> {
+    /// Create a new `UsageCountingStore` counting the uses of the entries of `inner`.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            uses: Mutex::default(),
+        }
+    }
+
+    /// Get the inner store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get the cache keys of the `n` most used entries with their uses, most used first.
+    pub fn most_used(&self, n: usize) -> Vec<(String, u64)> {
+        let uses = self.uses.lock().unwrap_or_else(|e| e.into_inner());
+        let mut most_used: Vec<_> = uses.iter().map(|(k, n)| (k.clone(), *n)).collect();
+        most_used.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        most_used.truncate(n);
+        most_used
+    }
+
+    /// Write a Rust module embedding the `n` most used images to `path`, returning how many were
+    /// written.
+    ///
+    /// The images are written into a directory next to the module, named after it without the
+    /// extension (`hot_assets/` for `hot_assets.rs`), and included with `include_bytes!`. The
+    /// module defines `pub fn resolver() -> usvg_remote_resolvers::memory::MemoryResolver`
+    /// serving them by their cache keys, which are their `href`s unless the resolver normalizes
+    /// them. Entries whose image type can't be detected are skipped.
+    pub fn write_module(&self, n: usize, path: impl AsRef<Path>) -> std::io::Result<usize> {
+        let path = path.as_ref();
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| std::io::Error::other("the module path has no UTF-8 file name"))?;
+        let dir = path.with_file_name(stem);
+        std::fs::create_dir_all(&dir)?;
+
+        let mut source = String::from(
+            "// Generated by usvg-remote-resolvers from cache usage. Do not edit.\n\n\
+             /// Get a resolver serving the embedded images.\n\
+             pub fn resolver() -> usvg_remote_resolvers::memory::MemoryResolver {\n    \
+             usvg_remote_resolvers::memory::MemoryResolver::new()\n",
+        );
+        let mut written = 0;
+        for (key, _) in self.most_used(n) {
+            let Some(entry) = self.inner.get(&key) else {
+                continue;
+            };
+            let Some(image_type) =
+                ImageKindTypes::detect(entry.content_type.as_deref(), &key, &entry.body)
+            else {
+                continue;
+            };
+            let (method, ext) = match image_type {
+                ImageKindTypes::Jpeg => ("insert_jpeg", "jpg"),
+                ImageKindTypes::Png => ("insert_png", "png"),
+                ImageKindTypes::Gif => ("insert_gif", "gif"),
+                ImageKindTypes::Webp => ("insert_webp", "webp"),
+                ImageKindTypes::Svg => ("insert_svg", "svg"),
+            };
+            let file = format!("{written}.{ext}");
+            std::fs::write(dir.join(&file), &*entry.body)?;
+            let _ = writeln!(
+                source,
+                "        .{method}({key:?}, include_bytes!(\"{stem}/{file}\").as_slice())"
+            );
+            written += 1;
+        }
+        source.push_str("}\n");
+        std::fs::write(path, source)?;
+        Ok(written)
+    }
+}
+
+impl<S: HttpCacheStore> HttpCacheStore for UsageCountingStore<S> {
+    fn get(&self, key: &str) -> Option<HttpCacheEntry> {
+        let entry = self.inner.get(key)?;
+        let mut uses = self.uses.lock().unwrap_or_else(|e| e.into_inner());
+        *uses.entry(key.to_string()).or_default() += 1;
+        Some(entry)
+    }
+    fn put(&self, key: &str, entry: HttpCacheEntry) {
+        self.inner.put(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqwest_blocking::MemoryHttpCacheStore;
+    use std::sync::Arc;
+
+    fn entry(content_type: &str, body: &[u8]) -> HttpCacheEntry {
+        HttpCacheEntry {
+            etag: None,
+            last_modified: None,
+            content_type: Some(content_type.to_string()),
+            body: Arc::new(body.to_vec()),
+            fresh_until: None,
+        }
+    }
+
+    #[test]
+    fn writes_most_used_assets() {
+        let store = UsageCountingStore::new(MemoryHttpCacheStore::new());
+        let png = include_bytes!("../test_data/gray.png");
+        store.put("https://example.com/a.png", entry("image/png", png));
+        store.put(
+            "https://example.com/\"b\".svg",
+            entry("image/svg+xml", b"<svg/>"),
+        );
+        store.put("https://example.com/c.bin", entry("text/plain", b"?"));
+        for _ in 0..3 {
+            store.get("https://example.com/\"b\".svg");
+        }
+        store.get("https://example.com/a.png");
+        store.get("https://example.com/c.bin");
+        store.get("https://example.com/missing.png");
+        assert_eq!(
+            store.most_used(2),
+            [
+                ("https://example.com/\"b\".svg".to_string(), 3),
+                ("https://example.com/a.png".to_string(), 1),
+            ]
+        );
+
+        let dir =
+            std::env::temp_dir().join(format!("usvg-remote-resolvers-hot-{}", std::process::id()));
+        let module = dir.join("hot_assets.rs");
+        assert_eq!(store.write_module(10, &module).unwrap(), 2);
+        let source = std::fs::read_to_string(&module).unwrap();
+        assert!(source.contains(
+            r#".insert_svg("https://example.com/\"b\".svg", include_bytes!("hot_assets/0.svg").as_slice())"#
+        ));
+        assert!(source.contains(
+            r#".insert_png("https://example.com/a.png", include_bytes!("hot_assets/1.png").as_slice())"#
+        ));
+        assert_eq!(
+            std::fs::read(dir.join("hot_assets/1.png")).unwrap(),
+            png.as_slice()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
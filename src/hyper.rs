@@ -0,0 +1,512 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_body_util::{BodyExt, Empty, Limited};
+use hyper::body::Bytes;
+use hyper_util::client::legacy::connect::{Connect, HttpConnector};
+use hyper_util::client::legacy::Client;
+
+use crate::bridge::BackgroundRuntime;
+use crate::cache_control::RequestCacheControl;
+use crate::hooks::{Hook, SvgVerifier};
+use crate::target::TargetFilter;
+use crate::utils::ImageKindTypes;
+use crate::HrefStringResolver;
+
+/// The maximum number of redirects followed by default.
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// A resolver built directly on the [`hyper_util`] client, for applications that already use
+/// hyper and don't want the reqwest dependency tree.
+///
+/// The connection pool is configured on the client, e.g. with
+/// [`pool_max_idle_per_host`](hyper_util::client::legacy::Builder::pool_max_idle_per_host) and
+/// [`pool_idle_timeout`](hyper_util::client::legacy::Builder::pool_idle_timeout). HTTPS needs a
+/// TLS connector such as `hyper-rustls`, since the default [`HttpConnector`] only speaks plain
+/// HTTP.
+///
+/// Requests are driven on a runtime owned by this resolver, with its own worker thread, so it
+/// works outside of any runtime and inside runtimes of any flavor. Redirects are followed up to
+/// ten times, and only to URLs accepted by the [`TargetFilter`] of the resolver. Clones share the
+/// same runtime and connection pool.
+///
+/// ```
+/// use std::time::Duration;
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::hyper::HyperResolver;
+///
+/// let client = hyper_util::client::legacy::Client::builder(
+///     hyper_util::rt::TokioExecutor::new(),
+/// )
+/// .pool_max_idle_per_host(4)
+/// .pool_idle_timeout(Duration::from_secs(30))
+/// .build_http();
+/// let resolver = HyperResolver::new(client).unwrap();
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HyperResolver<C = HttpConnector> {
+    client: Client<C, Empty<Bytes>>,
+    target: TargetFilter,
+    max_redirects: usize,
+    max_body_size: Option<usize>,
+    timeout: Option<Duration>,
+    svg_verifier: Option<SvgVerifier>,
+    content_type_overrides: Vec<(TargetFilter, String)>,
+    runtime: Arc<BackgroundRuntime>,
+}
+
+impl HyperResolver {
+    /// Create a new `HyperResolver` with a plain HTTP client and the default pool settings.
+    ///
+    /// Fails if the runtime cannot be started.
+    pub fn http() -> std::io::Result<Self> {
+        let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build_http();
+        Self::new(client)
+    }
+}
+
+impl<C> HyperResolver<C> {
+    /// Create a new `HyperResolver` with the given client.
+    ///
+    /// Fails if the runtime cannot be started.
+    pub fn new(client: Client<C, Empty<Bytes>>) -> std::io::Result<Self> {
+        Ok(Self {
+            client,
+            target: TargetFilter::default(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            max_body_size: None,
+            timeout: None,
+            svg_verifier: None,
+            content_type_overrides: Vec::new(),
+            runtime: Arc::new(BackgroundRuntime::new()?),
+        })
+    }
+
+    /// Get the underlying client of this resolver.
+    pub fn client(&self) -> &Client<C, Empty<Bytes>> {
+        &self.client
+    }
+
+    /// Set the [`TargetFilter`] deciding which `href`s this resolver fetches.
+    pub fn with_target(mut self, target: TargetFilter) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Only fetch the `href`s passing `predicate`, in addition to the checks of the current
+    /// [`TargetFilter`].
+    ///
+    /// This is a shorthand for setting [`TargetFilter::with_predicate`], and replaces a predicate
+    /// set before.
+    pub fn with_target_filter(
+        self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            target: self.target.with_predicate(predicate),
+            ..self
+        }
+    }
+
+    /// Follow at most `max_redirects` redirects, or none with `0`.
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Fail requests that take longer than `timeout` in total, from sending the first request to
+    /// reading the last byte of the body, including the redirects.
+    ///
+    /// The time to connect is a setting of the connector of the client, e.g.
+    /// [`HttpConnector::set_connect_timeout`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail responses whose body is larger than `max_body_size` bytes.
+    ///
+    /// A `Content-Length` above the limit is rejected before the body is read, and other bodies are
+    /// read until they exceed the limit, so an oversized body is never buffered whole. By default,
+    /// bodies of any size are accepted.
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// Only accept nested SVG images whose detached signature is accepted by `verifier`.
+    ///
+    /// The signature is fetched from the `href` of the SVG image with `.sig` appended, and
+    /// `verifier` is called with the `href`, the SVG body and the signature before the SVG is
+    /// parsed. SVG images are rejected if the signature can't be fetched or `verifier` returns
+    /// `false`. Raster images are not affected.
+    pub fn with_svg_verifier(
+        mut self,
+        verifier: impl Fn(&str, &[u8], &[u8]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.svg_verifier = Some(Hook(Arc::new(verifier)));
+        self
+    }
+
+    /// Treat responses for `href`s matching `filter` as `content_type`, whatever their
+    /// `Content-Type` header says, e.g. for origins known to mislabel their images. When several
+    /// overrides match an `href`, the first one added wins.
+    pub fn with_content_type_override(
+        mut self,
+        filter: TargetFilter,
+        content_type: impl Into<String>,
+    ) -> Self {
+        self.content_type_overrides
+            .push((filter, content_type.into()));
+        self
+    }
+}
+
+impl<C> HrefStringResolver<'_> for HyperResolver<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn is_target(&self, href: &str) -> bool {
+        self.target.matches(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
+        let (content_type, body) = self.run_fetch(href)?;
+        if crate::utils::is_empty_body(href, &body) {
+            return None;
+        }
+        let content_type = crate::target::override_content_type(
+            &self.content_type_overrides,
+            href,
+            content_type.as_deref(),
+        );
+
+        let image_type = match ImageKindTypes::detect(content_type, href, &body) {
+            Some(t) => t,
+            None => {
+                crate::utils::log_warn!(
+                    "unsupported image type for '{}' (content-type: {:?})",
+                    crate::redact::redact(href),
+                    content_type
+                );
+                return None;
+            }
+        };
+        if matches!(image_type, ImageKindTypes::Svg) && !self.verify_svg(href, &body) {
+            return None;
+        }
+        image_type.into_image_kind_from(body.into(), href, options)
+    }
+}
+
+impl<C> HyperResolver<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    /// Fetch `href` on the runtime of this resolver, within the timeout set with
+    /// [`with_timeout`](`Self::with_timeout`).
+    fn run_fetch(&self, href: &str) -> Option<(Option<String>, Vec<u8>)> {
+        let resolver = self.clone();
+        let owned_href = href.to_string();
+        let cache_control = RequestCacheControl::current().header_value();
+        let (requests, response) = self.runtime.run(async move {
+            let mut requests = 0;
+            let fetch = resolver.fetch(&owned_href, cache_control, &mut requests);
+            let response = match resolver.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, fetch)
+                    .await
+                    .unwrap_or_else(|_| {
+                        crate::utils::log_warn!(
+                            "failed to fetch '{}': timed out",
+                            crate::redact::redact(&owned_href)
+                        );
+                        None
+                    }),
+                None => fetch.await,
+            };
+            (requests, response)
+        })?;
+        // The requests are sent on the runtime's thread, so they are accounted for here.
+        for _ in 0..requests {
+            crate::accounting::record_request();
+        }
+        let (content_type, body) = response?;
+        crate::accounting::record_bytes(body.len());
+        Some((content_type, body))
+    }
+
+    /// Check the detached signature of the SVG image `body` with the verifier set with
+    /// [`with_svg_verifier`](`Self::with_svg_verifier`), if any.
+    fn verify_svg(&self, href: &str, body: &[u8]) -> bool {
+        let Some(verifier) = &self.svg_verifier else {
+            return true;
+        };
+        let signature = self.run_fetch(&format!("{href}.sig"));
+        crate::hooks::verify_svg(
+            verifier,
+            href,
+            body,
+            signature.as_ref().map(|(_, sig)| &sig[..]),
+        )
+    }
+
+    /// Fetch `href`, following redirects, and return the content type and body of the final
+    /// response, counting the requests sent in `requests`.
+    async fn fetch(
+        &self,
+        href: &str,
+        cache_control: Option<String>,
+        requests: &mut usize,
+    ) -> Option<(Option<String>, Vec<u8>)> {
+        let mut url = href.to_string();
+        while *requests <= self.max_redirects {
+            let mut req = hyper::Request::get(url.as_str());
+            if let Some(value) = &cache_control {
+                req = req.header(hyper::header::CACHE_CONTROL, value);
+            }
+            let req = match req.body(Empty::new()) {
+                Ok(req) => req,
+                Err(e) => {
+                    crate::utils::log_warn!(
+                        "invalid request for '{}': {}",
+                        crate::redact::redact(href),
+                        crate::redact::redact_error(&e, href)
+                    );
+                    return None;
+                }
+            };
+            *requests += 1;
+            let resp = match self.client.request(req).await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    crate::utils::log_warn!(
+                        "failed to fetch '{}': {}",
+                        crate::redact::redact(href),
+                        crate::redact::redact_error(&e, href)
+                    );
+                    return None;
+                }
+            };
+            let header = |name| {
+                resp.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            };
+            if resp.status().is_redirection() {
+                let location = header(hyper::header::LOCATION)
+                    .and_then(|location| url::Url::parse(&url).ok()?.join(&location).ok());
+                if let Some(location) = location {
+                    if !self.target.matches(location.as_str()) {
+                        crate::utils::log_warn!(
+                            "rejected redirect of '{}' to '{}'",
+                            crate::redact::redact(href),
+                            crate::redact::redact(location.as_str())
+                        );
+                        return None;
+                    }
+                    url = location.into();
+                    continue;
+                }
+            }
+            if !resp.status().is_success() {
+                crate::utils::log_warn!(
+                    "failed to fetch '{}': status {}",
+                    crate::redact::redact(href),
+                    resp.status()
+                );
+                return None;
+            }
+            let content_type = header(hyper::header::CONTENT_TYPE);
+            let max_body_size = self.max_body_size.unwrap_or(usize::MAX);
+            let content_length =
+                header(hyper::header::CONTENT_LENGTH).and_then(|v| v.parse::<usize>().ok());
+            if content_length.is_some_and(|length| length > max_body_size) {
+                crate::utils::log_warn!(
+                    "response body of '{}' exceeds {} bytes",
+                    crate::redact::redact(href),
+                    max_body_size
+                );
+                return None;
+            }
+            return match Limited::new(resp.into_body(), max_body_size)
+                .collect()
+                .await
+            {
+                Ok(body) => Some((content_type, body.to_bytes().to_vec())),
+                Err(e) => {
+                    crate::utils::log_warn!(
+                        "failed to read response body for '{}': {}",
+                        crate::redact::redact(href),
+                        crate::redact::redact_error(&e, href)
+                    );
+                    None
+                }
+            };
+        }
+        crate::utils::log_warn!(
+            "failed to fetch '{}': too many redirects",
+            crate::redact::redact(href)
+        );
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use usvg::Options;
+
+    #[test]
+    fn hyper_resolver() {
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/old.png")
+            .with_status(301)
+            .with_header("location", "/gray.png")
+            .create();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+        s.mock("GET", "/loop.png")
+            .with_status(302)
+            .with_header("location", "/loop.png")
+            .create();
+
+        let resolver = HyperResolver::http().unwrap();
+        let options = Options::default();
+        assert!(matches!(
+            resolver.get_image_kind(&format!("{}/old.png", s.url()), &options),
+            Some(usvg::ImageKind::PNG(_))
+        ));
+        assert!(resolver
+            .get_image_kind(&format!("{}/loop.png", s.url()), &options)
+            .is_none());
+    }
+
+    #[test]
+    fn content_type_override() {
+        let mut s = mockito::Server::new();
+        for path in ["/legacy/gray", "/other/gray"] {
+            s.mock("GET", path)
+                .with_status(200)
+                .with_header("content-type", "text/plain")
+                .with_body(include_bytes!("../test_data/gray.png"))
+                .create();
+        }
+        let filter = TargetFilter::new().with_predicate(|href| href.contains("/legacy/"));
+        let resolver = HyperResolver::http()
+            .unwrap()
+            .with_content_type_override(filter, "image/png");
+        let options = Options::default();
+        assert!(matches!(
+            resolver.get_image_kind(&format!("{}/legacy/gray", s.url()), &options),
+            Some(usvg::ImageKind::PNG(_))
+        ));
+        assert!(resolver
+            .get_image_kind(&format!("{}/other/gray", s.url()), &options)
+            .is_none());
+    }
+
+    #[test]
+    fn svg_verifier() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+        let mut s = mockito::Server::new();
+        for name in ["signed", "forged", "unsigned"] {
+            s.mock("GET", format!("/{name}.svg").as_str())
+                .with_status(200)
+                .with_header("content-type", "image/svg+xml")
+                .with_body(svg)
+                .create();
+        }
+        s.mock("GET", "/signed.svg.sig").with_body("good").create();
+        s.mock("GET", "/forged.svg.sig").with_body("bad").create();
+        s.mock("GET", "/unsigned.svg.sig").with_status(404).create();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let resolver = HyperResolver::http()
+            .unwrap()
+            .with_svg_verifier(|_, body, signature| !body.is_empty() && signature == b"good");
+        let options = Options::default();
+        let resolve =
+            |name: &str| resolver.get_image_kind(&format!("{}/{name}", s.url()), &options);
+        assert!(matches!(
+            resolve("signed.svg"),
+            Some(usvg::ImageKind::SVG(_))
+        ));
+        assert!(resolve("forged.svg").is_none());
+        assert!(resolve("unsigned.svg").is_none());
+        assert!(matches!(resolve("gray.png"), Some(usvg::ImageKind::PNG(_))));
+    }
+
+    #[test]
+    fn rejects_redirects_to_denied_hosts() {
+        let mut s = mockito::Server::new();
+        let port = s.socket_address().port();
+        s.mock("GET", "/a.png")
+            .with_status(302)
+            .with_header(
+                "location",
+                &format!("http://localhost:{port}/meta-data.png"),
+            )
+            .create();
+        let meta_data = s.mock("GET", "/meta-data.png").expect(0).create();
+
+        let resolver = HyperResolver::http()
+            .unwrap()
+            .with_target(TargetFilter::new().with_denied_host_suffix("localhost"));
+        assert!(resolver
+            .get_image_kind(&format!("{}/a.png", s.url()), &Options::default())
+            .is_none());
+        meta_data.assert();
+    }
+
+    #[test]
+    fn timeout_and_max_body_size() {
+        let body = include_bytes!("../test_data/gray.png");
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(body)
+            .create();
+        s.mock("GET", "/chunked.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| w.write_all(include_bytes!("../test_data/gray.png")))
+            .create();
+        s.mock("GET", "/stalled.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                w.write_all(include_bytes!("../test_data/gray.png"))
+            })
+            .create();
+        let options = Options::default();
+        let resolves = |resolver: &HyperResolver, path: &str| {
+            resolver
+                .get_image_kind(&format!("{}{path}", s.url()), &options)
+                .is_some()
+        };
+
+        let resolver = HyperResolver::http().unwrap();
+        assert!(resolves(
+            &resolver.clone().with_max_body_size(body.len()),
+            "/gray.png"
+        ));
+        let limited = resolver.clone().with_max_body_size(body.len() - 1);
+        assert!(!resolves(&limited, "/gray.png"));
+        assert!(!resolves(&limited, "/chunked.png"));
+
+        let resolver = resolver.with_timeout(Duration::from_millis(100));
+        assert!(resolves(&resolver, "/gray.png"));
+        assert!(!resolves(&resolver, "/stalled.png"));
+    }
+}
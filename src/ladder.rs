@@ -0,0 +1,126 @@
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// A resolver that tries format-specific variants of an `href` in order, e.g. `.avif`, then
+/// `.webp`, then `.png`, and uses the first one that resolves.
+///
+/// Each variant is a URL template with the same placeholders as
+/// [`DpiVariantResolver`](crate::dpi::DpiVariantResolver), except for `{scale}`. A variant that
+/// the inner resolver doesn't target, that doesn't exist or that fails to decode is skipped, and
+/// the original `href` is resolved last if no variant does. usvg only supports the formats of
+/// [`ImageKind`], so a variant in another format such as AVIF is only useful if the inner resolver
+/// converts it.
+///
+/// ```
+/// use usvg_remote_resolvers::ladder::FormatLadderResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let resolver = FormatLadderResolver::new(DefaultResolver)
+///     .with_extension("webp")
+///     .with_variant("{stem}.min.{ext}{suffix}");
+/// assert_eq!(
+///     resolver.variant_hrefs("https://example.com/logo.png?v=1"),
+///     [
+///         "https://example.com/logo.webp?v=1",
+///         "https://example.com/logo.min.png?v=1",
+///     ],
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct FormatLadderResolver<T> {
+    inner: T,
+    variants: Vec<String>,
+}
+
+impl<T> FormatLadderResolver<T> {
+    /// Create a new `FormatLadderResolver` with no variants.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Add a variant `template`, tried after the ones added before.
+    pub fn with_variant(mut self, template: impl Into<String>) -> Self {
+        self.variants.push(template.into());
+        self
+    }
+
+    /// Add a variant replacing the file extension of the `href` with `ext`, given without the
+    /// leading dot.
+    pub fn with_extension(self, ext: &str) -> Self {
+        self.with_variant(format!("{{stem}}.{ext}{{suffix}}"))
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the variants of `href`, in the order they are tried, without the ones equal to `href`.
+    pub fn variant_hrefs(&self, href: &str) -> Vec<String> {
+        let mut hrefs: Vec<String> = Vec::with_capacity(self.variants.len());
+        for template in &self.variants {
+            let variant = crate::utils::expand_href_template(template, href);
+            if variant != href && !hrefs.contains(&variant) {
+                hrefs.push(variant);
+            }
+        }
+        hrefs
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for FormatLadderResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        for variant in self.variant_hrefs(href) {
+            if self.inner.is_target(&variant) {
+                if let Some(kind) = self.inner.get_image_kind(&variant, options) {
+                    return Some(kind);
+                }
+            }
+            crate::utils::log_warn!(
+                "failed to resolve format variant '{}' of '{}'",
+                crate::redact::redact(&variant),
+                crate::redact::redact(href)
+            );
+        }
+        self.inner.get_image_kind(href, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryResolver;
+
+    #[test]
+    fn tries_variants_in_order() {
+        let png = include_bytes!("../test_data/gray.png");
+        let resolver = FormatLadderResolver::new(
+            MemoryResolver::new()
+                .insert_svg_str("a.webp", "not an image")
+                .insert_png("a.png", png)
+                .insert_png("b.png", png),
+        )
+        .with_extension("avif")
+        .with_extension("webp")
+        .with_extension("png");
+        let options = Options::default();
+
+        assert_eq!(resolver.variant_hrefs("a.png"), ["a.avif", "a.webp"]);
+        assert!(matches!(
+            resolver.get_image_kind("a.png", &options),
+            Some(ImageKind::PNG(_))
+        ));
+        assert!(resolver.get_image_kind("b.jpg", &options).is_some());
+        assert!(resolver.get_image_kind("c.png", &options).is_none());
+    }
+}
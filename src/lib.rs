@@ -34,16 +34,83 @@ use std::path::PathBuf;
 
 use usvg::{ImageHrefStringResolverFn, ImageKind, Options};
 
+use crate::explain::ResolutionTrace;
+
+pub mod accounting;
+#[cfg(feature = "http_auth")]
+pub mod auth;
+pub mod base_url;
+#[cfg(any(feature = "hyper", feature = "reqwest", feature = "reqwest_http_cache"))]
+mod bridge;
+pub mod budget;
+pub mod cache_control;
+#[cfg(feature = "cas")]
+pub mod cas;
+pub mod chain;
+#[cfg(any(
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "reqwest_http_cache"
+))]
+pub mod client;
+pub mod clock;
+pub mod data;
+pub mod decoded_cache;
+pub mod dedup;
+pub mod detect;
+pub mod dpi;
+#[cfg(feature = "content_encoding")]
+pub mod encoding;
+pub mod error;
+pub mod events;
+pub mod explain;
+pub mod fetch_lock;
+#[cfg(feature = "frame_selection")]
+pub mod frame;
+#[cfg(feature = "gcs")]
+pub mod gcs;
+pub mod hooks;
+#[cfg(feature = "reqwest_blocking")]
+pub mod hot;
+#[cfg(feature = "hyper")]
+pub mod hyper;
+pub mod ladder;
+pub mod memory;
+#[cfg(feature = "reqwest_blocking")]
+pub mod mirror;
+pub mod nesting;
+pub mod normalize;
+pub mod options_override;
+#[cfg(feature = "reqwest")]
+pub mod prefetch;
+pub mod queue;
+pub mod race;
+pub mod rate_limit;
+pub mod redact;
+pub mod registry;
+pub mod report;
 #[cfg(feature = "reqwest")]
 pub mod reqwest;
 #[cfg(feature = "reqwest_blocking")]
 pub mod reqwest_blocking;
 #[cfg(feature = "reqwest_http_cache")]
 pub mod reqwest_http_cache;
+pub mod retry;
 #[cfg(feature = "s3")]
 pub mod s3;
+pub mod scan;
+pub mod strict;
+pub mod strip;
+pub mod svg_cache;
+pub mod swap;
+pub mod target;
+#[cfg(feature = "thumbnail")]
+pub mod thumbnail;
+pub mod tree_size;
 mod utils;
 
+pub use crate::utils::ImageKindTypes;
+
 /// HrefStringResolver is a trait that is used to resolve the `href` attribute of the `<image>` tag.
 /// It will be converted to [`ImageHrefResolver`](`usvg::ImageHrefResolver`) to be set in the [`Options`](`usvg::Options`).
 pub trait HrefStringResolver<'a>: Send + Sync {
@@ -53,6 +120,11 @@ pub trait HrefStringResolver<'a>: Send + Sync {
     /// This is where the logic for resolving the `href` is implemented.
     fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind>;
     /// Convert this resolver to put into [`ImageHrefResolver`](`usvg::ImageHrefResolver`).
+    ///
+    /// The implementations for `&T`, `Box<T>` and `Arc<T>` can't forward this by-value call to
+    /// `T`, so they always use this default. Resolvers should implement their behaviour in
+    /// [`is_target`](`Self::is_target`) and [`get_image_kind`](`Self::get_image_kind`) rather than
+    /// by overriding this, so that they act the same once wrapped.
     fn into_fn(self) -> ImageHrefStringResolverFn<'a>
     where
         Self: Sized + 'a,
@@ -61,13 +133,16 @@ pub trait HrefStringResolver<'a>: Send + Sync {
             if self.is_target(href) {
                 let result = self.get_image_kind(href, options);
                 if result.is_none() {
-                    crate::utils::log_warn!("failed to resolve image href: '{}'", href);
+                    crate::utils::log_warn!(
+                        "failed to resolve image href: '{}'",
+                        crate::redact::redact(href)
+                    );
                 }
                 result
             } else {
                 crate::utils::log_warn!(
                     "image href '{}' is not a target for this resolver",
-                    href
+                    crate::redact::redact(href)
                 );
                 None
             }
@@ -89,6 +164,59 @@ pub trait HrefStringResolver<'a>: Send + Sync {
     {
         options.image_href_resolver.resolve_string = self.into_fn();
     }
+    /// Set this shared resolver into the [`Options`](`usvg::Options`), keeping it usable for other
+    /// [`Options`](`usvg::Options`), e.g. to share its connection pool and caches between the
+    /// request handlers of a server.
+    ///
+    /// This is the same as calling [`set_into_options`](`Self::set_into_options`) on a clone of
+    /// the [`Arc`](`std::sync::Arc`).
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use usvg_remote_resolvers::{HrefStringResolver, reqwest_blocking::BlockingReqwestResolver};
+    ///
+    /// let resolver = Arc::new(BlockingReqwestResolver::default());
+    /// for _ in 0..2 {
+    ///     let mut options = usvg::Options::default();
+    ///     resolver.clone().set_into_options_shared(&mut options);
+    /// }
+    /// ```
+    fn set_into_options_shared(self: std::sync::Arc<Self>, options: &mut Options<'a>)
+    where
+        Self: Sized + 'a,
+    {
+        self.set_into_options(options);
+    }
+    /// Explain how this resolver, and the resolvers it is composed of, would handle `href`, without
+    /// resolving it.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+    ///
+    /// let trace = DefaultResolver.explain("image.png");
+    /// assert!(trace.is_target());
+    /// ```
+    fn explain(&self, href: &str) -> ResolutionTrace
+    where
+        Self: Sized,
+    {
+        let mut trace = ResolutionTrace::new(href);
+        self.explain_into(&mut trace);
+        trace
+    }
+    /// Record how this resolver handles the `href` of `trace` into it.
+    ///
+    /// By default, this records whether the `href` is a target of this resolver. Resolvers
+    /// wrapping other resolvers override this to record those as well, with
+    /// [`ResolutionTrace::wrap`] or [`ResolutionTrace::nested`].
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        let outcome = if self.is_target(trace.href()) {
+            explain::Outcome::Matched
+        } else {
+            explain::Outcome::NotTarget
+        };
+        trace.push::<Self>(outcome);
+    }
     /// Add a fallback to this resolver in case if the url is not the target of this resolver, or if
     /// it fails to resolve.
     fn with_fallback<T>(self, fallback: T) -> FallbackResolver<Self, T>
@@ -100,6 +228,48 @@ pub trait HrefStringResolver<'a>: Send + Sync {
     }
 }
 
+/// A shared reference to a resolver is a resolver, so one resolver can be set into several
+/// [`Options`](`usvg::Options`) without being consumed.
+impl<'a, T: HrefStringResolver<'a> + ?Sized> HrefStringResolver<'a> for &T {
+    fn is_target(&self, href: &str) -> bool {
+        (**self).is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        (**self).get_image_kind(href, options)
+    }
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        (**self).explain_into(trace)
+    }
+}
+
+/// A boxed resolver, including `Box<dyn HrefStringResolver>`, is a resolver, so resolvers of
+/// different types can be stored together.
+impl<'a, T: HrefStringResolver<'a> + ?Sized> HrefStringResolver<'a> for Box<T> {
+    fn is_target(&self, href: &str) -> bool {
+        (**self).is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        (**self).get_image_kind(href, options)
+    }
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        (**self).explain_into(trace)
+    }
+}
+
+/// A resolver behind an [`Arc`](`std::sync::Arc`), including `Arc<dyn HrefStringResolver>`, is a
+/// resolver, so it can be shared across threads.
+impl<'a, T: HrefStringResolver<'a> + ?Sized> HrefStringResolver<'a> for std::sync::Arc<T> {
+    fn is_target(&self, href: &str) -> bool {
+        (**self).is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        (**self).get_image_kind(href, options)
+    }
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        (**self).explain_into(trace)
+    }
+}
+
 /// Resolver using [`default_string_resolver`](`usvg::ImageHrefResolver::default_string_resolver`)
 #[derive(Debug, Default, Clone, Copy)]
 pub struct DefaultResolver;
@@ -111,9 +281,6 @@ impl<'a> HrefStringResolver<'a> for DefaultResolver {
     fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
         usvg::ImageHrefResolver::default_string_resolver()(href, options)
     }
-    fn into_fn(self) -> ImageHrefStringResolverFn<'a> {
-        usvg::ImageHrefResolver::default_string_resolver()
-    }
 }
 
 /// Resolver for `file://` URLs.
@@ -171,22 +338,128 @@ impl<'a> HrefStringResolver<'a> for FileResolver {
         }
         usvg::ImageHrefResolver::default_string_resolver()(path, options)
     }
-    fn into_fn(self) -> ImageHrefStringResolverFn<'a> {
-        let default = usvg::ImageHrefResolver::default_string_resolver();
-        Box::new(move |href, options| {
-            let path = Self::strip_file_scheme(href)?;
-            if !self.is_path_allowed(path) {
-                return None;
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        let outcome = match Self::strip_file_scheme(trace.href()) {
+            None => explain::Outcome::NotTarget,
+            Some(path) if !self.is_path_allowed(path) => {
+                explain::Outcome::Denied("outside of the allowed directories".to_string())
             }
-            default(path, options)
+            Some(_) => explain::Outcome::Matched,
+        };
+        trace.push::<Self>(outcome);
+    }
+}
+
+/// Resolver for local file paths that only allows access inside a root directory.
+///
+/// Unlike [`DefaultResolver`], which reads any path it is given, this only resolves relative
+/// paths that stay inside `root`. Absolute paths, `..` components and symlinks pointing outside of
+/// `root` are rejected, which makes it suitable for rendering untrusted documents.
+///
+/// `file://` URLs are resolved as well, if their canonical path is inside `root`.
+///
+/// Data URIs are decoded by usvg before string resolvers are called, so they keep working.
+///
+/// ```
+/// use usvg_remote_resolvers::{HrefStringResolver, SandboxedLocalResolver};
+///
+/// let resolver = SandboxedLocalResolver::new("./test_data");
+/// assert!(resolver.is_target("gray.png"));
+/// assert!(resolver.is_target("file:///srv/assets/gray.png"));
+/// assert!(!resolver.is_target("https://example.com/gray.png"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SandboxedLocalResolver {
+    root: PathBuf,
+}
+
+impl SandboxedLocalResolver {
+    /// Create a new `SandboxedLocalResolver` that resolves paths relative to `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Get the root directory of this resolver.
+    pub fn root(&self) -> &std::path::Path {
+        &self.root
+    }
+
+    fn has_scheme(href: &str) -> bool {
+        href.split_once(':').is_some_and(|(scheme, _)| {
+            scheme.len() > 1
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
         })
     }
+
+    fn is_file_url(href: &str) -> bool {
+        href.get(..5)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file:"))
+    }
+
+    /// Resolve `href` to a canonical path inside the root, or `None` if it would escape it.
+    fn sandboxed_path(&self, href: &str) -> Option<PathBuf> {
+        use std::path::Component;
+
+        if Self::is_file_url(href) {
+            let path = url::Url::parse(href).ok()?.to_file_path().ok()?;
+            return self.contained(href, &path);
+        }
+        let path = std::path::Path::new(href);
+        let is_relative = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !is_relative {
+            crate::utils::log_warn!(
+                "rejected path outside of the sandbox: '{}'",
+                crate::redact::redact(href)
+            );
+            return None;
+        }
+        self.contained(href, path)
+    }
+
+    /// Canonicalize `path`, relative to the root, and check that it stays inside the root.
+    fn contained(&self, href: &str, path: &std::path::Path) -> Option<PathBuf> {
+        let root = self.root.canonicalize().ok()?;
+        let canonical = root.join(path).canonicalize().ok()?;
+        if !canonical.starts_with(&root) {
+            crate::utils::log_warn!(
+                "rejected path escaping the sandbox: '{}'",
+                crate::redact::redact(href)
+            );
+            return None;
+        }
+        Some(canonical)
+    }
+}
+
+impl<'a> HrefStringResolver<'a> for SandboxedLocalResolver {
+    fn is_target(&self, href: &str) -> bool {
+        !Self::has_scheme(href) || Self::is_file_url(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let path = self.sandboxed_path(href)?;
+        usvg::ImageHrefResolver::default_string_resolver()(path.to_str()?, options)
+    }
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        let outcome = if !self.is_target(trace.href()) {
+            explain::Outcome::NotTarget
+        } else if self.sandboxed_path(trace.href()).is_none() {
+            explain::Outcome::Denied("outside of the sandbox".to_string())
+        } else {
+            explain::Outcome::Matched
+        };
+        trace.push::<Self>(outcome);
+    }
 }
 
 /// A resolver that tries the `primary` resolver first, and falls back to the `fallback` resolver
 /// if the primary does not handle the `href` or fails to resolve it.
 ///
 /// This can be created using [`HrefStringResolver::with_fallback`] or [`From`] tuple conversions.
+/// For longer chains, see [`ChainResolver`](crate::chain::ChainResolver).
 ///
 /// ```
 /// use usvg_remote_resolvers::{DefaultResolver, FallbackResolver};
@@ -233,6 +506,18 @@ where
                     .flatten()
             })
     }
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        let outcome = if self.is_target(trace.href()) {
+            explain::Outcome::Matched
+        } else {
+            explain::Outcome::NotTarget
+        };
+        trace.push::<Self>(outcome);
+        trace.nested(|trace| {
+            self.primary.explain_into(trace);
+            self.fallback.explain_into(trace);
+        });
+    }
 }
 
 impl<T, U> From<(T, U)> for FallbackResolver<T, U> {
@@ -249,10 +534,121 @@ impl<T, U, V> From<(T, U, V)> for FallbackResolver<T, FallbackResolver<U, V>> {
     }
 }
 
+type FnTarget = std::sync::Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// A resolver calling a closure, for one-off resolvers written inline.
+///
+/// Every `href` is a target of the closure, unless a predicate is set with
+/// [`with_target`](`Self::with_target`).
+///
+/// ```
+/// use usvg_remote_resolvers::{FnResolver, HrefStringResolver};
+///
+/// let resolver = FnResolver::new(|href, _options| {
+///     let data = std::fs::read(href.strip_prefix("assets:")?).ok()?;
+///     Some(usvg::ImageKind::PNG(data.into()))
+/// })
+/// .with_target(|href| href.starts_with("assets:"));
+/// assert!(resolver.is_target("assets:logo.png"));
+/// assert!(!resolver.is_target("logo.png"));
+///
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Clone)]
+pub struct FnResolver<F> {
+    resolve: F,
+    target: Option<FnTarget>,
+}
+
+impl<F> std::fmt::Debug for FnResolver<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FnResolver")
+            .field("target", &self.target.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> FnResolver<F>
+where
+    F: Fn(&str, &Options) -> Option<ImageKind> + Send + Sync,
+{
+    /// Create a new `FnResolver` resolving every `href` with `resolve`.
+    pub fn new(resolve: F) -> Self {
+        Self {
+            resolve,
+            target: None,
+        }
+    }
+
+    /// Only resolve the `href`s passing `target`.
+    pub fn with_target(mut self, target: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.target = Some(std::sync::Arc::new(target));
+        self
+    }
+}
+
+impl<'a, F> HrefStringResolver<'a> for FnResolver<F>
+where
+    F: Fn(&str, &Options) -> Option<ImageKind> + Send + Sync,
+{
+    fn is_target(&self, href: &str) -> bool {
+        self.target.as_ref().is_none_or(|target| target(href))
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        (self.resolve)(href, options)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
+    fn fn_resolver() {
+        let resolver = FnResolver::new(|href, options| {
+            DefaultResolver.get_image_kind(&format!("./test_data/{href}"), options)
+        });
+        assert!(resolver.is_target("anything"));
+        let resolver = resolver.with_target(|href| href.ends_with(".png"));
+        assert!(!resolver.is_target("gray.svg"));
+
+        let mut options = Options::default();
+        resolver.set_into_options(&mut options);
+        let tree = usvg::Tree::from_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="gray.png" /></svg>"#,
+            &options,
+        )
+        .unwrap();
+        assert!(tree.root().has_children());
+    }
+    #[test]
+    fn resolver_references_and_pointers() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="./test_data/gray.png" /></svg>"#;
+        let resolver = DefaultResolver;
+        for _ in 0..2 {
+            let mut options = Options::default();
+            (&resolver).set_into_options(&mut options);
+            assert!(usvg::Tree::from_str(svg, &options)
+                .unwrap()
+                .root()
+                .has_children());
+        }
+
+        let resolvers: Vec<Box<dyn HrefStringResolver>> =
+            vec![Box::new(FileResolver::new()), Box::new(DefaultResolver)];
+        let chain = resolvers
+            .into_iter()
+            .fold(chain::ChainResolver::new(), |chain, r| chain.push(r));
+        let shared: std::sync::Arc<dyn HrefStringResolver> = std::sync::Arc::new(chain);
+        assert!(shared.is_target("./test_data/gray.png"));
+        let mut options = Options::default();
+        shared.clone().set_into_options(&mut options);
+        assert!(usvg::Tree::from_str(svg, &options)
+            .unwrap()
+            .root()
+            .has_children());
+    }
+    #[test]
     fn default_resolver() {
         let resolver = DefaultResolver;
         let mut options = Options::default();
@@ -330,17 +726,15 @@ mod tests {
 
     #[test]
     fn file_resolver_allowed_dirs() {
-        let test_data_dir = std::path::Path::new("./test_data")
-            .canonicalize()
-            .unwrap();
+        let test_data_dir = std::path::Path::new("./test_data").canonicalize().unwrap();
         let abs_path = std::path::Path::new("./test_data/gray.png")
             .canonicalize()
             .unwrap();
         let file_url = format!("file://{}", abs_path.display());
 
         // Allowed dir includes test_data -> should resolve
-        let resolver =
-            FileResolver::with_allowed_dirs(vec![test_data_dir.clone()]).with_fallback(DefaultResolver);
+        let resolver = FileResolver::with_allowed_dirs(vec![test_data_dir.clone()])
+            .with_fallback(DefaultResolver);
         let mut options = Options::default();
         resolver.set_into_options(&mut options);
 
@@ -370,8 +764,8 @@ mod tests {
         let file_url = format!("file://{}", abs_path.display());
 
         // Allowed dir is /tmp -> test_data path should be blocked
-        let resolver =
-            FileResolver::with_allowed_dirs(vec![PathBuf::from("/tmp")]).with_fallback(DefaultResolver);
+        let resolver = FileResolver::with_allowed_dirs(vec![PathBuf::from("/tmp")])
+            .with_fallback(DefaultResolver);
         let mut options = Options::default();
         resolver.set_into_options(&mut options);
 
@@ -393,4 +787,64 @@ mod tests {
             resvg::tiny_skia::PremultipliedColorU8::from_rgba(0, 0, 0, 0).unwrap()
         );
     }
+
+    #[test]
+    fn wrapped_resolvers_act_the_same() {
+        let test_data_dir = std::path::Path::new("./test_data").canonicalize().unwrap();
+        let file_url = |name: &str| format!("file://{}", test_data_dir.join(name).display());
+        let allowed = FileResolver::with_allowed_dirs(vec![test_data_dir.clone()]);
+        let denied = FileResolver::with_allowed_dirs(vec![PathBuf::from("/tmp")]);
+
+        for (resolver, resolved) in [(allowed, true), (denied, false)] {
+            let fns = [
+                (&resolver).into_fn(),
+                Box::new(resolver.clone()).into_fn(),
+                std::sync::Arc::new(resolver.clone()).into_fn(),
+                resolver.clone().into_fn(),
+            ];
+            for f in &fns {
+                let options = Options::default();
+                assert_eq!(f(&file_url("gray.png"), &options).is_some(), resolved);
+                assert!(f("./test_data/gray.png", &options).is_none());
+            }
+        }
+
+        let fns = [
+            (&DefaultResolver).into_fn(),
+            Box::new(DefaultResolver).into_fn(),
+            DefaultResolver.into_fn(),
+        ];
+        for f in &fns {
+            let options = Options::default();
+            assert!(f("./test_data/gray.png", &options).is_some());
+            assert!(f("./test_data/missing.png", &options).is_none());
+        }
+    }
+
+    #[test]
+    fn sandboxed_local_resolver() {
+        let resolver = SandboxedLocalResolver::new("./test_data");
+        let options = Options::default();
+        assert!(resolver.get_image_kind("gray.png", &options).is_some());
+        assert!(resolver.get_image_kind("./gray.png", &options).is_some());
+        assert!(resolver
+            .get_image_kind("../test_data/gray.png", &options)
+            .is_none());
+
+        let abs_path = std::path::Path::new("./test_data/gray.png")
+            .canonicalize()
+            .unwrap();
+        assert!(resolver
+            .get_image_kind(abs_path.to_str().unwrap(), &options)
+            .is_none());
+
+        let file_url = url::Url::from_file_path(&abs_path).unwrap();
+        assert!(resolver
+            .get_image_kind(file_url.as_str(), &options)
+            .is_some());
+        assert!(resolver
+            .get_image_kind("file:///etc/passwd", &options)
+            .is_none());
+        assert!(!resolver.is_target("https://example.com/gray.png"));
+    }
 }
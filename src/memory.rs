@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Image(Box<ImageKind>),
+    /// SVG source, parsed with the options of the document referencing it.
+    Svg(Arc<Vec<u8>>),
+}
+
+/// A resolver serving images from memory, without any I/O.
+///
+/// Useful in tests and in applications that bundle their images. Only the inserted `href`s are a
+/// target of this resolver, so other `href`s fall back to the next resolver.
+///
+/// SVG images inserted as source are parsed every time they are resolved, with the options of
+/// the document referencing them.
+///
+/// ```
+/// use usvg_remote_resolvers::{HrefStringResolver, memory::MemoryResolver};
+///
+/// let resolver = MemoryResolver::new()
+///     .insert_png("logo.png", include_bytes!("../test_data/gray.png").to_vec())
+///     .insert_svg_str("icon.svg", r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#);
+/// assert!(resolver.is_target("logo.png"));
+/// assert!(!resolver.is_target("https://example.com/logo.png"));
+///
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    images: HashMap<String, Entry>,
+}
+
+impl MemoryResolver {
+    /// Create a new empty `MemoryResolver`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `image` for `href`, replacing any previous image.
+    pub fn insert(mut self, href: impl Into<String>, image: ImageKind) -> Self {
+        self.images
+            .insert(href.into(), Entry::Image(Box::new(image)));
+        self
+    }
+
+    /// Serve the PNG image `data` for `href`.
+    pub fn insert_png(self, href: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.insert(href, ImageKind::PNG(Arc::new(data.into())))
+    }
+
+    /// Serve the JPEG image `data` for `href`.
+    pub fn insert_jpeg(self, href: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.insert(href, ImageKind::JPEG(Arc::new(data.into())))
+    }
+
+    /// Serve the GIF image `data` for `href`.
+    pub fn insert_gif(self, href: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.insert(href, ImageKind::GIF(Arc::new(data.into())))
+    }
+
+    /// Serve the WebP image `data` for `href`.
+    pub fn insert_webp(self, href: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.insert(href, ImageKind::WEBP(Arc::new(data.into())))
+    }
+
+    /// Serve the SVG image `data` for `href`. It may be gzip-compressed, as in `.svgz` files.
+    pub fn insert_svg(mut self, href: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.images
+            .insert(href.into(), Entry::Svg(Arc::new(data.into())));
+        self
+    }
+
+    /// Serve the SVG image with the source `svg` for `href`.
+    pub fn insert_svg_str(self, href: impl Into<String>, svg: &str) -> Self {
+        self.insert_svg(href, svg.as_bytes())
+    }
+
+    /// Check if an image is served for `href`.
+    pub fn contains(&self, href: &str) -> bool {
+        self.images.contains_key(href)
+    }
+
+    /// Get the number of `href`s served.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Check if no `href`s are served.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+impl<'a> HrefStringResolver<'a> for MemoryResolver {
+    fn is_target(&self, href: &str) -> bool {
+        self.contains(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        match self.images.get(href)? {
+            Entry::Image(image) => Some((**image).clone()),
+            Entry::Svg(data) => match usvg::Tree::from_data(data, options) {
+                Ok(tree) => Some(ImageKind::SVG(tree)),
+                Err(e) => {
+                    crate::utils::log_warn!(
+                        "failed to parse SVG for '{}': {}",
+                        crate::redact::redact(href),
+                        e
+                    );
+                    None
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_from_memory() {
+        let resolver = MemoryResolver::new()
+            .insert_png("gray.png", include_bytes!("../test_data/gray.png").to_vec())
+            .insert_svg_str(
+                "nested.svg",
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10"/>"#,
+            )
+            .insert_svg_str("broken.svg", "<svg");
+        let options = Options::default();
+
+        assert!(matches!(
+            resolver.get_image_kind("gray.png", &options),
+            Some(ImageKind::PNG(_))
+        ));
+        let Some(ImageKind::SVG(tree)) = resolver.get_image_kind("nested.svg", &options) else {
+            panic!("not an SVG image");
+        };
+        assert_eq!(tree.size(), usvg::Size::from_wh(20.0, 10.0).unwrap());
+        assert!(resolver.get_image_kind("broken.svg", &options).is_none());
+        assert!(resolver.get_image_kind("missing.png", &options).is_none());
+        assert_eq!(resolver.len(), 3);
+
+        let mut options = Options::default();
+        resolver.set_into_options(&mut options);
+        let tree = usvg::Tree::from_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="gray.png"/></svg>"#,
+            &options,
+        )
+        .unwrap();
+        assert!(tree.root().has_children());
+    }
+}
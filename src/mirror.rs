@@ -0,0 +1,163 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use usvg::{ImageKind, Options};
+
+use crate::reqwest_blocking::BlockingReqwestResolver;
+use crate::utils::ImageKindTypes;
+use crate::HrefStringResolver;
+
+/// A resolver serving images from a local mirror directory, fetching the missing ones over HTTP
+/// and writing them back into the directory.
+///
+/// The directory is laid out by URL, so it can be inspected, pre-populated with `wget --mirror`
+/// or shipped along with an application: `https://example.com/img/logo.png` is stored at
+/// `<dir>/example.com/img/logo.png`, with the port appended to the host as `example.com_8080`
+/// if the URL has one and `index` as the file name of paths ending with `/`.
+///
+/// Files are never revalidated, so the mirror suits immutable assets. URLs with a query string
+/// are fetched without being mirrored, since the query has no place in the layout. The type of a
+/// mirrored image is detected from its extension, or from its content if it has none.
+///
+/// ```no_run
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::mirror::MirrorCacheResolver;
+/// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+///
+/// let resolver = MirrorCacheResolver::new(BlockingReqwestResolver::default(), "assets/mirror");
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MirrorCacheResolver {
+    resolver: BlockingReqwestResolver,
+    dir: PathBuf,
+}
+
+impl MirrorCacheResolver {
+    /// Create a new `MirrorCacheResolver` mirroring into `dir`, which is created on the first write.
+    pub fn new(resolver: BlockingReqwestResolver, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            resolver,
+            dir: dir.into(),
+        }
+    }
+
+    /// Get the mirror directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the path `href` is mirrored at, or `None` if it is not mirrored.
+    pub fn path(&self, href: &str) -> Option<PathBuf> {
+        let url = url::Url::parse(href).ok()?;
+        if url.query().is_some() {
+            return None;
+        }
+        let host = url.host_str()?;
+        let mut path = match url.port() {
+            Some(port) => self.dir.join(format!("{host}_{port}")),
+            None => self.dir.join(host),
+        };
+        let mut segments = url.path_segments()?.peekable();
+        while let Some(segment) = segments.next() {
+            if matches!(segment, "." | "..") || segment.contains(['/', '\\']) {
+                return None;
+            }
+            if !segment.is_empty() {
+                path.push(segment);
+            } else if segments.peek().is_none() {
+                path.push("index");
+            }
+        }
+        Some(path)
+    }
+}
+
+impl HrefStringResolver<'_> for MirrorCacheResolver {
+    fn is_target(&self, href: &str) -> bool {
+        self.resolver.is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let path = self.path(href);
+        let mirrored = path.as_deref().and_then(|path| std::fs::read(path).ok());
+        if let Some(data) = mirrored.filter(|data| !data.is_empty()) {
+            if let Some(image_type) = ImageKindTypes::detect(None, href, &data) {
+                crate::events::record_cache_hit();
+                return image_type.into_image_kind_from(Arc::new(data), href, options);
+            }
+        }
+
+        let fetched = self.resolver.fetch_body(href).ok()?;
+        if let Some(path) = path {
+            if let Err(e) = write(&path, &fetched.body) {
+                crate::utils::log_warn!(
+                    "failed to mirror '{}': {}",
+                    crate::redact::redact(href),
+                    e
+                );
+            }
+        }
+        let kind = fetched
+            .image_type
+            .into_image_kind_from(fetched.body, href, options)?;
+        self.resolver.call_response_hook(fetched.metadata, &kind);
+        Some(kind)
+    }
+}
+
+/// Write `data` to `path` through a temporary file, so readers never see a partial file.
+fn write(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_fetched_images() {
+        let dir = std::env::temp_dir().join(format!(
+            "usvg-remote-resolvers-mirror-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut s = mockito::Server::new();
+        let mock = s
+            .mock("GET", "/img/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+
+        let resolver = MirrorCacheResolver::new(BlockingReqwestResolver::default(), &dir);
+        let href = format!("{}/img/gray.png", s.url());
+        let path = resolver.path(&href).unwrap();
+        assert!(path.ends_with("img/gray.png"));
+        assert!(resolver.path(&format!("{href}?v=2")).is_none());
+
+        let options = Options::default();
+        for _ in 0..2 {
+            assert!(matches!(
+                resolver.get_image_kind(&href, &options),
+                Some(ImageKind::PNG(_))
+            ));
+        }
+        mock.assert();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            include_bytes!("../test_data/gray.png")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
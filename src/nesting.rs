@@ -0,0 +1,193 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use usvg::{ImageKind, Options};
+
+use crate::normalize::{DefaultNormalizer, UrlNormalizer};
+use crate::HrefStringResolver;
+
+thread_local! {
+    /// The normalized `href`s being resolved on this thread, keyed by the resolver (and its clones)
+    /// resolving them, outermost first.
+    static RESOLVING: RefCell<Vec<(usize, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A resolver that limits how deeply nested SVG images may be, and refuses SVG images that
+/// contain themselves.
+///
+/// Nested SVG images are parsed with the same options as the document, so their images are
+/// resolved by the same resolver, which may fetch more SVG images. Without a limit, an SVG image
+/// referring to itself, directly or through others, is fetched until the stack overflows.
+///
+/// The images of the document are at depth 1, the images of those at depth 2, and so on; deeper
+/// images are not resolved. An `href` is a cycle if it is already being resolved further up, as
+/// compared after normalizing with the [`DefaultNormalizer`] unless set otherwise. Wrap the
+/// outermost resolver, so every nested `href` goes through this one.
+///
+/// ```
+/// use usvg_remote_resolvers::nesting::NestingLimitResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let resolver = NestingLimitResolver::new(DefaultResolver).with_max_depth(4);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NestingLimitResolver<T> {
+    inner: T,
+    max_depth: usize,
+    normalizer: Arc<dyn UrlNormalizer>,
+    id: Arc<()>,
+}
+
+impl<T> NestingLimitResolver<T> {
+    /// Create a new `NestingLimitResolver` resolving images up to depth 8.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            max_depth: 8,
+            normalizer: Arc::new(DefaultNormalizer::new()),
+            id: Arc::new(()),
+        }
+    }
+
+    /// Resolve images up to depth `max_depth`.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Detect cycles by comparing the `href`s normalized with `normalizer`.
+    pub fn with_normalizer(mut self, normalizer: impl UrlNormalizer + 'static) -> Self {
+        self.normalizer = Arc::new(normalizer);
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the maximum depth of the images resolved.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// The key of this resolver and its clones in [`RESOLVING`].
+    fn key(&self) -> usize {
+        Arc::as_ptr(&self.id) as usize
+    }
+}
+
+/// Removes the `href` from [`RESOLVING`] once it is resolved, even if the inner resolver panics.
+struct Resolved;
+
+impl Drop for Resolved {
+    fn drop(&mut self) {
+        RESOLVING.with(|resolving| resolving.borrow_mut().pop());
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for NestingLimitResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let key = self.key();
+        let normalized = self.normalizer.normalize(href);
+        let (depth, cycle) = RESOLVING.with(|resolving| {
+            let resolving = resolving.borrow();
+            let mut ancestors = resolving.iter().filter(|(k, _)| *k == key);
+            (
+                ancestors.clone().count(),
+                ancestors.any(|(_, href)| *href == normalized),
+            )
+        });
+        if cycle {
+            crate::utils::log_warn!(
+                "'{}' contains itself, not resolving it",
+                crate::redact::redact(href)
+            );
+            return None;
+        }
+        if depth >= self.max_depth {
+            crate::utils::log_warn!(
+                "'{}' is nested deeper than {} levels, not resolving it",
+                crate::redact::redact(href),
+                self.max_depth
+            );
+            return None;
+        }
+
+        RESOLVING.with(|resolving| resolving.borrow_mut().push((key, normalized)));
+        let _resolved = Resolved;
+        self.inner.get_image_kind(href, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryResolver;
+
+    fn image_svg(href: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><image href="{href}" width="10" height="10"/></svg>"#
+        )
+    }
+
+    fn first_image(group: &usvg::Group) -> Option<&usvg::Image> {
+        group.children().iter().find_map(|node| match node {
+            usvg::Node::Image(image) => Some(&**image),
+            usvg::Node::Group(group) => first_image(group),
+            _ => None,
+        })
+    }
+
+    /// Get the kinds of the chain of first images in the document `a.svg`.
+    fn nested_kinds(resolver: NestingLimitResolver<MemoryResolver>) -> Vec<&'static str> {
+        let mut options = Options::default();
+        resolver.set_into_options(&mut options);
+        let tree = usvg::Tree::from_str(&image_svg("a.svg"), &options).unwrap();
+        let mut kinds = Vec::new();
+        let mut image = first_image(tree.root());
+        while let Some(kind) = image.map(usvg::Image::kind) {
+            kinds.push(crate::events::kind_name(kind));
+            image = match kind {
+                ImageKind::SVG(tree) => first_image(tree.root()),
+                _ => None,
+            };
+        }
+        kinds
+    }
+
+    #[test]
+    fn limits_depth_and_cycles() {
+        let inner = MemoryResolver::new()
+            .insert_svg_str("a.svg", &image_svg("b.svg"))
+            .insert_svg_str("b.svg", &image_svg("a.svg"));
+        assert_eq!(
+            nested_kinds(NestingLimitResolver::new(inner.clone())),
+            ["svg", "svg"]
+        );
+        assert_eq!(
+            nested_kinds(NestingLimitResolver::new(inner.clone()).with_max_depth(1)),
+            ["svg"]
+        );
+
+        let inner = inner
+            .insert_svg_str("b.svg", &image_svg("gray.png"))
+            .insert_png("gray.png", include_bytes!("../test_data/gray.png"));
+        assert_eq!(
+            nested_kinds(NestingLimitResolver::new(inner.clone())),
+            ["svg", "svg", "png"]
+        );
+        assert_eq!(
+            nested_kinds(NestingLimitResolver::new(inner).with_max_depth(2)),
+            ["svg", "svg"]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Decides which `href`s count as the same URL.
+///
+/// A normalizer can be set on the caches ([`SvgTreeCacheResolver`](crate::svg_cache::SvgTreeCacheResolver),
+/// [`CachedResolver`](crate::decoded_cache::CachedResolver) and the cached blocking resolver), the
+/// deduplicating [`FetchQueue`](crate::queue::FetchQueue) and
+/// [`DedupResolver`](crate::dedup::DedupResolver), and [`TargetFilter`](crate::target::TargetFilter). The normalized `href` is only
+/// used to compare and look up `href`s; requests are still made with the original `href`.
+pub trait UrlNormalizer: Debug + Send + Sync {
+    /// Get the normalized form of `href`.
+    fn normalize(&self, href: &str) -> String;
+}
+
+impl<N: UrlNormalizer + ?Sized> UrlNormalizer for Arc<N> {
+    fn normalize(&self, href: &str) -> String {
+        (**self).normalize(href)
+    }
+}
+
+/// Normalize `href` with `normalizer`, if there is one.
+pub(crate) fn normalize<'h>(
+    normalizer: Option<&Arc<dyn UrlNormalizer>>,
+    href: &'h str,
+) -> Cow<'h, str> {
+    match normalizer {
+        Some(normalizer) => Cow::Owned(normalizer.normalize(href)),
+        None => Cow::Borrowed(href),
+    }
+}
+
+/// The default [`UrlNormalizer`].
+///
+/// URLs are brought into their canonical form (lowercase scheme and host, no default port), the
+/// fragment is removed, and so are the query parameters starting with `utm_`. More parameters
+/// (e.g. signatures) can be ignored, and paths can be case-folded for origins with
+/// case-insensitive paths. `href`s that are not absolute URLs are kept as they are.
+///
+/// ```
+/// use usvg_remote_resolvers::normalize::{DefaultNormalizer, UrlNormalizer};
+///
+/// let normalizer = DefaultNormalizer::new().with_param("X-Amz-Signature");
+/// assert_eq!(
+///     normalizer.normalize("HTTPS://Example.com:443/a.png?v=1&utm_source=mail&x-amz-signature=abc#top"),
+///     "https://example.com/a.png?v=1",
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct DefaultNormalizer {
+    params: Vec<String>,
+    param_prefixes: Vec<String>,
+    fold_path_case: bool,
+}
+
+impl Default for DefaultNormalizer {
+    fn default() -> Self {
+        Self {
+            params: Vec::new(),
+            param_prefixes: vec!["utm_".to_string()],
+            fold_path_case: false,
+        }
+    }
+}
+
+impl DefaultNormalizer {
+    /// Create a new `DefaultNormalizer` ignoring `utm_` parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ignore the query parameter `name`, compared case-insensitively.
+    pub fn with_param(mut self, name: impl Into<String>) -> Self {
+        self.params.push(name.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Ignore the query parameters starting with `prefix`, compared case-insensitively.
+    pub fn with_param_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.param_prefixes.push(prefix.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Lowercase the path, for origins whose paths are case-insensitive (e.g. Windows servers).
+    pub fn with_case_folded_path(mut self) -> Self {
+        self.fold_path_case = true;
+        self
+    }
+
+    fn is_ignored(&self, param: &str) -> bool {
+        let name = param.split('=').next().unwrap_or_default();
+        let name = name.to_ascii_lowercase();
+        self.params.contains(&name) || self.param_prefixes.iter().any(|p| name.starts_with(p))
+    }
+}
+
+impl UrlNormalizer for DefaultNormalizer {
+    fn normalize(&self, href: &str) -> String {
+        let Ok(mut url) = url::Url::parse(href) else {
+            return href.to_string();
+        };
+        url.set_fragment(None);
+        if let Some(query) = url.query() {
+            let query = query
+                .split('&')
+                .filter(|param| !param.is_empty() && !self.is_ignored(param))
+                .collect::<Vec<_>>()
+                .join("&");
+            url.set_query((!query.is_empty()).then_some(query.as_str()));
+        }
+        if self.fold_path_case {
+            let path = url.path().to_lowercase();
+            url.set_path(&path);
+        }
+        url.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_normalizer() {
+        let normalizer = DefaultNormalizer::new();
+        assert_eq!(
+            normalizer.normalize("https://example.com/a.png?utm_source=x"),
+            "https://example.com/a.png"
+        );
+        assert_eq!(
+            normalizer.normalize("https://example.com/A.png?b=1&&a=2"),
+            "https://example.com/A.png?b=1&a=2"
+        );
+        assert_eq!(normalizer.normalize("./a.png#x"), "./a.png#x");
+        assert_eq!(
+            DefaultNormalizer::new()
+                .with_case_folded_path()
+                .normalize("https://example.com/Images/A.PNG?V=1"),
+            "https://example.com/images/a.png?V=1"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+type OptionsHook = Box<dyn Fn(&str, &mut Options) + Send + Sync>;
+
+/// A resolver that lets a hook adjust the [`Options`] used for each resolved image.
+///
+/// The hook receives the `href` and a copy of the current options, which it can modify before
+/// the inner resolver is called. This is mostly useful for nested SVG images, which are parsed
+/// with these options, e.g. to force a specific `dpi` or font for fragments from one service.
+///
+/// The copy keeps using the resolvers of the original options, so images nested inside a
+/// fetched SVG are still resolved the same way.
+///
+/// ```
+/// use usvg_remote_resolvers::{
+///     DefaultResolver, HrefStringResolver, options_override::OptionsOverrideResolver,
+/// };
+///
+/// let resolver = OptionsOverrideResolver::new(DefaultResolver, |href, options| {
+///     if href.starts_with("https://charts.example.com/") {
+///         options.dpi = 192.0;
+///         options.font_family = "Noto Sans".to_string();
+///     }
+/// });
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+pub struct OptionsOverrideResolver<T> {
+    inner: T,
+    hook: OptionsHook,
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for OptionsOverrideResolver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OptionsOverrideResolver")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> OptionsOverrideResolver<T> {
+    /// Create a new `OptionsOverrideResolver` calling `hook` before each image is resolved.
+    pub fn new(inner: T, hook: impl Fn(&str, &mut Options) + Send + Sync + 'static) -> Self {
+        Self {
+            inner,
+            hook: Box::new(hook),
+        }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for OptionsOverrideResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let mut options = crate::utils::borrow_options(options);
+        (self.hook)(href, &mut options);
+        self.inner.get_image_kind(href, &options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct DpiRecorder(Arc<Mutex<Vec<f32>>>);
+
+    impl HrefStringResolver<'_> for DpiRecorder {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, _: &str, options: &Options) -> Option<ImageKind> {
+            self.0.lock().unwrap().push(options.dpi);
+            None
+        }
+    }
+
+    #[test]
+    fn overrides_options_per_href() {
+        let dpis = Arc::new(Mutex::new(Vec::new()));
+        let resolver = OptionsOverrideResolver::new(DpiRecorder(dpis.clone()), |href, options| {
+            if href.ends_with(".svg") {
+                options.dpi = 300.0;
+            }
+        });
+        let options = Options::default();
+        resolver.get_image_kind("https://example.com/chart.svg", &options);
+        resolver.get_image_kind("https://example.com/photo.png", &options);
+        assert_eq!(*dpis.lock().unwrap(), vec![300.0, 96.0]);
+    }
+}
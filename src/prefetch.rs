@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::target::TargetFilter;
+use crate::utils::ImageKindTypes;
+use crate::HrefStringResolver;
+
+/// Downloads the images of an SVG document concurrently before it is parsed.
+///
+/// The `href`s are extracted with [`image_hrefs`](crate::scan::image_hrefs) and fetched with the
+/// async [`reqwest::Client`], all at once, so parsing the tree with the returned
+/// [`PreloadedResolver`] does not wait for a round-trip per image.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), usvg::Error> {
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::prefetch::Prefetcher;
+///
+/// let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+///     <image href="https://example.com/a.png"/>
+/// </svg>"#;
+/// let resolver = Prefetcher::new(reqwest::Client::new())
+///     .scan_and_fetch(svg)
+///     .await?;
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// let tree = usvg::Tree::from_str(svg, &options)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Prefetcher {
+    client: reqwest::Client,
+    target: TargetFilter,
+}
+
+impl Prefetcher {
+    /// Create a new `Prefetcher` fetching with the given [`Client`](`reqwest::Client`).
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            target: TargetFilter::default(),
+        }
+    }
+
+    /// Set the [`TargetFilter`] deciding which `href`s are fetched.
+    ///
+    /// By default, only `http://` and `https://` URLs are fetched.
+    pub fn with_target(mut self, target: TargetFilter) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Scan `svg` for images and fetch them concurrently.
+    ///
+    /// Returns an error if `svg` is not a valid XML document.
+    pub async fn scan_and_fetch(&self, svg: &str) -> Result<PreloadedResolver, usvg::Error> {
+        let hrefs = crate::scan::image_hrefs(svg)?;
+        Ok(self.fetch(hrefs).await)
+    }
+
+    /// Fetch `hrefs` concurrently.
+    ///
+    /// `hrefs` that are not a target of this prefetcher are skipped, and the ones that fail are
+    /// listed in [`PreloadedResolver::failed`].
+    pub async fn fetch<I, S>(&self, hrefs: I) -> PreloadedResolver
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut tasks = tokio::task::JoinSet::new();
+        for href in hrefs.into_iter().map(Into::into) {
+            if !self.target.matches(&href) {
+                continue;
+            }
+            let client = self.client.clone();
+            tasks.spawn(async move {
+                let result = fetch(&client, &href).await;
+                (href, result)
+            });
+        }
+
+        let mut resolver = PreloadedResolver::default();
+        while let Some(joined) = tasks.join_next().await {
+            let Ok((href, result)) = joined else {
+                continue;
+            };
+            match result {
+                Some(asset) => {
+                    resolver.assets.insert(href, asset);
+                }
+                None => resolver.failed.push(href),
+            }
+        }
+        resolver.failed.sort();
+        resolver
+    }
+}
+
+async fn fetch(client: &reqwest::Client, href: &str) -> Option<Asset> {
+    let resp = match client.get(href).send().await {
+        Ok(resp) if resp.status().is_success() => resp,
+        Ok(resp) => {
+            crate::utils::log_warn!(
+                "failed to fetch '{}': status {}",
+                crate::redact::redact(href),
+                resp.status()
+            );
+            return None;
+        }
+        Err(e) => {
+            crate::utils::log_warn!(
+                "failed to fetch '{}': {}",
+                crate::redact::redact(href),
+                crate::redact::redact_error(&e, href)
+            );
+            return None;
+        }
+    };
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    match resp.bytes().await {
+        Ok(body) if crate::utils::is_empty_body(href, &body) => None,
+        Ok(body) => Some(Asset {
+            content_type,
+            body: Arc::new(body.to_vec()),
+        }),
+        Err(e) => {
+            crate::utils::log_warn!(
+                "failed to read response body for '{}': {}",
+                crate::redact::redact(href),
+                crate::redact::redact_error(&e, href)
+            );
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Asset {
+    content_type: Option<String>,
+    body: Arc<Vec<u8>>,
+}
+
+/// A resolver serving the images downloaded by a [`Prefetcher`] from memory.
+///
+/// Only the `href`s that were fetched successfully are a target of this resolver, so other
+/// `href`s fall back to the next resolver, e.g. with
+/// [`with_fallback`](HrefStringResolver::with_fallback).
+#[derive(Debug, Clone, Default)]
+pub struct PreloadedResolver {
+    assets: HashMap<String, Asset>,
+    failed: Vec<String>,
+}
+
+impl PreloadedResolver {
+    /// Check if `href` was fetched successfully.
+    pub fn contains(&self, href: &str) -> bool {
+        self.assets.contains_key(href)
+    }
+
+    /// Get the `href`s that could not be fetched, sorted.
+    pub fn failed(&self) -> &[String] {
+        &self.failed
+    }
+}
+
+impl HrefStringResolver<'_> for PreloadedResolver {
+    fn is_target(&self, href: &str) -> bool {
+        self.contains(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
+        let asset = self.assets.get(href)?;
+        let image_type =
+            match ImageKindTypes::detect(asset.content_type.as_deref(), href, &asset.body) {
+                Some(t) => t,
+                None => {
+                    crate::utils::log_warn!(
+                        "unsupported image type for '{}' (content-type: {:?})",
+                        crate::redact::redact(href),
+                        asset.content_type
+                    );
+                    return None;
+                }
+            };
+        image_type.into_image_kind_from(asset.body.clone(), href, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use usvg::Options;
+
+    #[tokio::test]
+    async fn scan_and_fetch() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create_async()
+            .await;
+        s.mock("GET", "/missing.png")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <image href="{0}/gray.png" />
+                <image href="{0}/missing.png" />
+                <image href="./local.png" />
+            </svg>"#,
+            s.url()
+        );
+        let resolver = Prefetcher::default().scan_and_fetch(&svg).await.unwrap();
+        assert!(resolver.contains(&format!("{}/gray.png", s.url())));
+        assert!(!resolver.contains("./local.png"));
+        assert_eq!(resolver.failed(), [format!("{}/missing.png", s.url())]);
+
+        let mut options = Options::default();
+        resolver.set_into_options(&mut options);
+        let tree = usvg::Tree::from_str(&svg, &options).unwrap();
+        assert!(tree.root().has_children());
+    }
+}
@@ -0,0 +1,610 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use usvg::{ImageKind, Options};
+
+use crate::normalize::UrlNormalizer;
+use crate::HrefStringResolver;
+
+/// The status of an `href` submitted to a [`FetchQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchStatus {
+    /// The `href` is waiting for a worker.
+    Queued,
+    /// A worker is resolving the `href`.
+    InProgress,
+    /// The `href` was resolved.
+    Done,
+    /// The inner resolver returned `None` for the `href`.
+    Failed,
+}
+
+#[derive(Default)]
+struct State {
+    /// The keys and `href`s waiting for a worker, by host.
+    pending: HashMap<String, VecDeque<(String, String)>>,
+    /// The hosts with pending `href`s, in the order they are served.
+    hosts: VecDeque<String>,
+    /// The number of `href`s being resolved, by host.
+    in_flight: HashMap<String, usize>,
+    max_per_host: Option<usize>,
+    statuses: HashMap<String, FetchStatus>,
+    results: HashMap<String, ImageKind>,
+    in_progress: usize,
+    shutdown: bool,
+    normalizer: Option<Arc<dyn UrlNormalizer>>,
+}
+
+impl State {
+    fn is_complete(&self) -> bool {
+        self.hosts.is_empty() && self.in_progress == 0
+    }
+
+    fn pending_len(&self) -> usize {
+        self.pending.values().map(VecDeque::len).sum()
+    }
+
+    fn is_below_limit(&self, host: &str) -> bool {
+        self.max_per_host
+            .is_none_or(|max| self.in_flight.get(host).copied().unwrap_or_default() < max)
+    }
+
+    fn has_ready(&self) -> bool {
+        self.hosts.iter().any(|host| self.is_below_limit(host))
+    }
+
+    fn push(&mut self, key: String, href: String) {
+        let host = host_of(&href);
+        let queue = self.pending.entry(host.clone()).or_default();
+        if queue.is_empty() {
+            self.hosts.push_back(host);
+        }
+        queue.push_back((key, href));
+    }
+
+    /// Take the next `href` of the first host below its limit, and move that host to the back, so
+    /// the hosts are served round-robin.
+    fn pop(&mut self) -> Option<(String, String, String)> {
+        let index = self
+            .hosts
+            .iter()
+            .position(|host| self.is_below_limit(host))?;
+        let host = self.hosts.remove(index)?;
+        let queue = self.pending.get_mut(&host)?;
+        let (key, href) = queue.pop_front()?;
+        if queue.is_empty() {
+            self.pending.remove(&host);
+        } else {
+            self.hosts.push_back(host.clone());
+        }
+        *self.in_flight.entry(host.clone()).or_default() += 1;
+        Some((host, key, href))
+    }
+
+    fn finish(&mut self, host: &str) {
+        if let Some(count) = self.in_flight.get_mut(host) {
+            *count -= 1;
+            if *count == 0 {
+                self.in_flight.remove(host);
+            }
+        }
+    }
+
+    fn key(&self, href: &str) -> String {
+        crate::normalize::normalize(self.normalizer.as_ref(), href).into_owned()
+    }
+}
+
+struct Shared<T> {
+    resolver: T,
+    options: Options<'static>,
+    state: Mutex<State>,
+    /// Notified when an `href` is submitted or the queue shuts down.
+    submitted: Condvar,
+    /// Notified when an `href` finishes.
+    finished: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A queue that resolves `href`s on worker threads ahead of parsing.
+///
+/// Submit all `href`s of a batch up front, wait for the set to complete with
+/// [`wait`](`Self::wait`), and then parse the documents with the queue as their resolver: it
+/// serves the images it already resolved, so the parse does not make any requests. The images
+/// are resolved by the inner resolver, so the crate's HTTP resolvers and caches can be used.
+///
+/// `href`s that were not submitted are not targets of the queue; combine it with
+/// [`with_fallback`](`HrefStringResolver::with_fallback`) to resolve them on demand.
+///
+/// ```no_run
+/// use usvg_remote_resolvers::queue::FetchQueue;
+/// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+/// use usvg_remote_resolvers::HrefStringResolver;
+///
+/// let queue = FetchQueue::new(BlockingReqwestResolver::default(), usvg::Options::default(), 8);
+/// queue.submit_all(["https://example.com/a.png", "https://example.com/b.png"]);
+/// queue.wait();
+///
+/// let mut options = usvg::Options::default();
+/// queue.set_into_options(&mut options);
+/// ```
+pub struct FetchQueue<T> {
+    shared: Arc<Shared<T>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T> std::fmt::Debug for FetchQueue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("FetchQueue")
+            .field("workers", &self.workers.len())
+            .field("pending", &state.pending_len())
+            .field("in_progress", &state.in_progress)
+            .field("resolved", &state.results.len())
+            .finish()
+    }
+}
+
+impl<T: HrefStringResolver<'static> + 'static> FetchQueue<T> {
+    /// Create a new `FetchQueue` resolving `href`s with `resolver` on `workers` threads.
+    ///
+    /// `options` is passed to the resolver, and is used to parse nested SVG images.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn new(resolver: T, options: Options<'static>, workers: usize) -> Self {
+        assert!(workers > 0, "a fetch queue needs at least one worker");
+        let shared = Arc::new(Shared {
+            resolver,
+            options,
+            state: Mutex::default(),
+            submitted: Condvar::new(),
+            finished: Condvar::new(),
+        });
+        let workers = (0..workers)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || work(&shared))
+            })
+            .collect();
+        Self { shared, workers }
+    }
+}
+
+impl<T> FetchQueue<T> {
+    /// Treat `href`s that are equal after normalizing them with `normalizer` as the same `href`.
+    ///
+    /// Only the first of them is resolved, with the `href` it was submitted with. This should be
+    /// set before submitting any `href`.
+    pub fn with_normalizer(self, normalizer: impl UrlNormalizer + 'static) -> Self {
+        self.shared.lock().normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Resolve at most `max_per_host` `href`s of the same host at a time, so a slow origin can't
+    /// occupy every worker while `href`s of other hosts wait.
+    ///
+    /// `href`s are always taken round-robin across hosts rather than in submission order, and the
+    /// host includes the port. `href`s that are not URLs share one host.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_per_host` is zero.
+    pub fn with_max_per_host(self, max_per_host: usize) -> Self {
+        assert!(
+            max_per_host > 0,
+            "a fetch queue needs at least one worker per host"
+        );
+        self.shared.lock().max_per_host = Some(max_per_host);
+        self
+    }
+
+    /// Submit an `href` to be resolved. `href`s that were already submitted are ignored.
+    pub fn submit(&self, href: impl Into<String>) {
+        let href = href.into();
+        let mut state = self.shared.lock();
+        let key = state.key(&href);
+        if state.statuses.contains_key(&key) {
+            return;
+        }
+        state.statuses.insert(key.clone(), FetchStatus::Queued);
+        state.push(key, href);
+        drop(state);
+        self.shared.submitted.notify_one();
+    }
+
+    /// Submit all of the given `href`s.
+    pub fn submit_all<I, S>(&self, hrefs: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for href in hrefs {
+            self.submit(href);
+        }
+    }
+
+    /// Get the status of a submitted `href`, or `None` if it was not submitted.
+    pub fn status(&self, href: &str) -> Option<FetchStatus> {
+        let state = self.shared.lock();
+        state.statuses.get(&state.key(href)).copied()
+    }
+
+    /// Returns `true` if every submitted `href` has finished.
+    pub fn is_complete(&self) -> bool {
+        self.shared.lock().is_complete()
+    }
+
+    /// Block until every submitted `href` has finished.
+    pub fn wait(&self) {
+        let state = self.shared.lock();
+        let _state = self
+            .shared
+            .finished
+            .wait_while(state, |state| !state.is_complete())
+            .unwrap_or_else(|e| e.into_inner());
+    }
+
+    /// Block until every submitted `href` has finished or `timeout` has passed.
+    ///
+    /// Returns `true` if every submitted `href` has finished.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let state = self.shared.lock();
+        let (state, _) = self
+            .shared
+            .finished
+            .wait_timeout_while(state, timeout, |state| !state.is_complete())
+            .unwrap_or_else(|e| e.into_inner());
+        state.is_complete()
+    }
+
+    /// Get a resolver serving the images resolved so far, without waiting for the rest.
+    ///
+    /// Use it after [`wait_timeout`](`Self::wait_timeout`) to render with whatever resolved in
+    /// time, e.g. for interactive previews.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use usvg_remote_resolvers::queue::FetchQueue;
+    /// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+    /// use usvg_remote_resolvers::HrefStringResolver;
+    ///
+    /// let queue = FetchQueue::new(BlockingReqwestResolver::default(), usvg::Options::default(), 8);
+    /// queue.submit_all(["https://example.com/a.png", "https://example.com/b.png"]);
+    /// queue.wait_timeout(Duration::from_millis(200));
+    ///
+    /// let snapshot = queue.snapshot();
+    /// let mut options = usvg::Options::default();
+    /// snapshot.clone().set_into_options(&mut options);
+    /// // Parse the documents, then check which images were left out.
+    /// let missing = snapshot.missing();
+    /// ```
+    pub fn snapshot(&self) -> QueueSnapshot<'_, T> {
+        QueueSnapshot {
+            queue: self,
+            placeholder: None,
+            missing: Arc::default(),
+        }
+    }
+
+    /// The submitted `href`s that failed to resolve, normalized if there is a normalizer.
+    pub fn failed(&self) -> Vec<String> {
+        let state = self.shared.lock();
+        let mut failed: Vec<_> = state
+            .statuses
+            .iter()
+            .filter(|(_, status)| **status == FetchStatus::Failed)
+            .map(|(href, _)| href.clone())
+            .collect();
+        failed.sort();
+        failed
+    }
+}
+
+/// The host of `href` with its port, or an empty string if it is not a URL with a host.
+fn host_of(href: &str) -> String {
+    let Ok(url) = url::Url::parse(href) else {
+        return String::new();
+    };
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        (None, _) => String::new(),
+    }
+}
+
+fn work<T: HrefStringResolver<'static>>(shared: &Shared<T>) {
+    loop {
+        let mut state = shared
+            .submitted
+            .wait_while(shared.lock(), |state| !state.has_ready() && !state.shutdown)
+            .unwrap_or_else(|e| e.into_inner());
+        if state.shutdown {
+            return;
+        }
+        let Some((host, key, href)) = state.pop() else {
+            continue;
+        };
+        state.in_progress += 1;
+        state.statuses.insert(key.clone(), FetchStatus::InProgress);
+        drop(state);
+
+        let result = if shared.resolver.is_target(&href) {
+            shared.resolver.get_image_kind(&href, &shared.options)
+        } else {
+            None
+        };
+
+        let mut state = shared.lock();
+        state.in_progress -= 1;
+        state.finish(&host);
+        let status = match result {
+            Some(kind) => {
+                state.results.insert(key.clone(), kind);
+                FetchStatus::Done
+            }
+            None => FetchStatus::Failed,
+        };
+        state.statuses.insert(key, status);
+        let limited = state.max_per_host.is_some();
+        drop(state);
+        shared.finished.notify_all();
+        if limited {
+            // A host may have dropped below its limit, so its pending `href`s are ready.
+            shared.submitted.notify_one();
+        }
+    }
+}
+
+impl<T> Drop for FetchQueue<T> {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.submitted.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<T> HrefStringResolver<'_> for FetchQueue<T>
+where
+    T: Send + Sync,
+{
+    fn is_target(&self, href: &str) -> bool {
+        self.status(href).is_some()
+    }
+    /// Get the resolved image, waiting for the `href` to finish if it has not yet.
+    fn get_image_kind(&self, href: &str, _options: &Options) -> Option<ImageKind> {
+        let state = self.shared.lock();
+        let key = state.key(href);
+        let state = self
+            .shared
+            .finished
+            .wait_while(state, |state| {
+                matches!(
+                    state.statuses.get(&key),
+                    Some(FetchStatus::Queued | FetchStatus::InProgress)
+                )
+            })
+            .unwrap_or_else(|e| e.into_inner());
+        state.results.get(&key).cloned()
+    }
+}
+
+/// A resolver serving the images a [`FetchQueue`] resolved so far, created with
+/// [`FetchQueue::snapshot`].
+///
+/// `href`s that are still queued or in progress are not waited for: they resolve to the
+/// placeholder, if one is set, and are recorded as missing. Clones share the record of missing
+/// `href`s, so a clone can be moved into the [`Options`] and the original checked afterwards.
+pub struct QueueSnapshot<'q, T> {
+    queue: &'q FetchQueue<T>,
+    placeholder: Option<ImageKind>,
+    missing: Arc<Mutex<Vec<String>>>,
+}
+
+impl<T> Clone for QueueSnapshot<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue,
+            placeholder: self.placeholder.clone(),
+            missing: self.missing.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for QueueSnapshot<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueueSnapshot")
+            .field("queue", self.queue)
+            .field("placeholder", &self.placeholder.is_some())
+            .field("missing", &self.missing())
+            .finish()
+    }
+}
+
+impl<T> QueueSnapshot<'_, T> {
+    /// Resolve `href`s that have not finished yet to `placeholder`, instead of leaving them out.
+    pub fn with_placeholder(mut self, placeholder: ImageKind) -> Self {
+        self.placeholder = Some(placeholder);
+        self
+    }
+
+    /// The `href`s that were requested from this snapshot before they finished, in request order.
+    pub fn missing(&self) -> Vec<String> {
+        self.missing
+            .lock()
+            .map(|missing| missing.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl<'q, T: Send + Sync> HrefStringResolver<'q> for QueueSnapshot<'q, T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.queue.is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, _options: &Options) -> Option<ImageKind> {
+        let state = self.queue.shared.lock();
+        let key = state.key(href);
+        match state.statuses.get(&key) {
+            Some(FetchStatus::Queued | FetchStatus::InProgress) => {
+                drop(state);
+                if let Ok(mut missing) = self.missing.lock() {
+                    if !missing.iter().any(|m| m == href) {
+                        missing.push(href.to_string());
+                    }
+                }
+                self.placeholder.clone()
+            }
+            _ => state.results.get(&key).cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingResolver(Arc<AtomicUsize>);
+
+    impl HrefStringResolver<'_> for CountingResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            crate::DefaultResolver.get_image_kind(href, options)
+        }
+    }
+
+    #[test]
+    fn resolves_submitted_hrefs() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let queue = FetchQueue::new(CountingResolver(count.clone()), Options::default(), 2);
+        queue.submit_all([
+            "./test_data/gray.png",
+            "./test_data/gray.png",
+            "./test_data/missing.png",
+        ]);
+        queue.wait();
+
+        assert!(queue.is_complete());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            queue.status("./test_data/gray.png"),
+            Some(FetchStatus::Done)
+        );
+        assert_eq!(queue.failed(), ["./test_data/missing.png"]);
+        assert_eq!(queue.status("./test_data/other.png"), None);
+
+        let mut options = Options::default();
+        queue.set_into_options(&mut options);
+        let tree = usvg::Tree::from_str(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="./test_data/gray.png"/></svg>"#,
+            &options,
+        )
+        .unwrap();
+        assert!(tree.root().has_children());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn normalized_hrefs_are_deduplicated() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let queue = FetchQueue::new(CountingResolver(count.clone()), Options::default(), 1)
+            .with_normalizer(crate::normalize::DefaultNormalizer::new());
+        queue.submit_all([
+            "file:///missing.png?utm_source=a",
+            "file:///missing.png?utm_source=b",
+        ]);
+        queue.wait();
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(queue.failed(), ["file:///missing.png"]);
+        assert!(queue.is_target("file:///missing.png#top"));
+    }
+
+    #[derive(Default)]
+    struct ConcurrencyResolver {
+        running: Mutex<HashMap<String, usize>>,
+        max: Mutex<HashMap<String, usize>>,
+    }
+
+    impl HrefStringResolver<'_> for ConcurrencyResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            let host = host_of(href);
+            {
+                let mut running = self.running.lock().unwrap();
+                let count = running.entry(host.clone()).or_default();
+                *count += 1;
+                let mut max = self.max.lock().unwrap();
+                let max = max.entry(host.clone()).or_default();
+                *max = (*max).max(*count);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+            *self.running.lock().unwrap().get_mut(&host).unwrap() -= 1;
+            crate::DefaultResolver.get_image_kind("./test_data/gray.png", options)
+        }
+    }
+
+    #[test]
+    fn limits_hrefs_per_host() {
+        let resolver = Arc::new(ConcurrencyResolver::default());
+        let queue = FetchQueue::new(resolver.clone(), Options::default(), 4).with_max_per_host(1);
+        queue.submit_all((0..4).map(|i| format!("https://slow.example/{i}.png")));
+        queue.submit_all((0..4).map(|i| format!("https://fast.example/{i}.png")));
+        queue.wait();
+
+        assert!(queue.failed().is_empty());
+        let max = resolver.max.lock().unwrap();
+        assert_eq!(max["slow.example"], 1);
+        assert_eq!(max["fast.example"], 1);
+    }
+
+    struct SlowResolver;
+
+    impl HrefStringResolver<'_> for SlowResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            if href.contains("slow") {
+                std::thread::sleep(Duration::from_millis(500));
+            }
+            crate::DefaultResolver.get_image_kind("./test_data/gray.png", options)
+        }
+    }
+
+    #[test]
+    fn snapshot_uses_placeholders() {
+        let queue = FetchQueue::new(SlowResolver, Options::default(), 2);
+        queue.submit_all(["fast.png", "slow.png"]);
+        assert!(!queue.wait_timeout(Duration::from_millis(200)));
+
+        let placeholder = ImageKind::PNG(Arc::new(Vec::new()));
+        let snapshot = queue.snapshot().with_placeholder(placeholder);
+        let options = Options::default();
+        assert!(matches!(
+            snapshot.clone().get_image_kind("fast.png", &options),
+            Some(ImageKind::PNG(data)) if !data.is_empty()
+        ));
+        assert!(matches!(
+            snapshot.clone().get_image_kind("slow.png", &options),
+            Some(ImageKind::PNG(data)) if data.is_empty()
+        ));
+        assert_eq!(snapshot.missing(), ["slow.png"]);
+        assert!(queue.wait_timeout(Duration::from_secs(5)));
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+
+use usvg::{ImageKind, Options};
+
+use crate::explain::{Outcome, ResolutionTrace};
+use crate::HrefStringResolver;
+
+type SharedResolver = Arc<dyn HrefStringResolver<'static>>;
+
+/// A message from a racing resolver to the thread waiting for the race.
+enum Message {
+    /// The resolver finished.
+    Done(Option<Box<ImageKind>>),
+    /// The resolver asks for an `href` nested in an SVG image to be resolved with the options of
+    /// the race.
+    Nested(String, Sender<Option<ImageKind>>),
+}
+
+/// A resolver that asks several resolvers for an `href` at once and uses the first image
+/// resolved, e.g. to hide the latency of one of redundant backends like S3 and an HTTP mirror.
+///
+/// Unlike a [`FallbackResolver`](crate::FallbackResolver), which tries its resolvers one after
+/// another, each resolver that targets the `href` runs on a thread of its own. The resolvers
+/// can't be interrupted, so the ones that lose the race finish in the background and their
+/// results are dropped. An `href` only one resolver targets is resolved on the calling thread.
+///
+/// The images nested in an SVG image are resolved on the calling thread, with the options passed
+/// to this resolver, while the race is on; once it is decided, the losers' nested images resolve
+/// to nothing. The other fields of the options are copied for the racing threads, except for the
+/// font resolver and the `data:` URL resolver, which are usvg's defaults there.
+///
+/// ```
+/// use usvg_remote_resolvers::memory::MemoryResolver;
+/// use usvg_remote_resolvers::race::RaceResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let resolver = RaceResolver::new()
+///     .with_resolver(MemoryResolver::new())
+///     .with_resolver(DefaultResolver);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Clone, Default)]
+pub struct RaceResolver {
+    resolvers: Vec<SharedResolver>,
+}
+
+impl std::fmt::Debug for RaceResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RaceResolver")
+            .field("resolvers", &self.resolvers.len())
+            .finish()
+    }
+}
+
+impl RaceResolver {
+    /// Create a new `RaceResolver` with no resolvers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `resolver` to the race.
+    pub fn with_resolver(mut self, resolver: impl HrefStringResolver<'static> + 'static) -> Self {
+        self.resolvers.push(Arc::new(resolver));
+        self
+    }
+
+    /// Get the number of resolvers in the race.
+    pub fn len(&self) -> usize {
+        self.resolvers.len()
+    }
+
+    /// Returns `true` if there are no resolvers in the race.
+    pub fn is_empty(&self) -> bool {
+        self.resolvers.is_empty()
+    }
+
+    fn race(
+        &self,
+        racers: Vec<SharedResolver>,
+        href: &str,
+        options: &Options,
+    ) -> Option<ImageKind> {
+        let (sender, receiver) = channel();
+        for resolver in racers {
+            let sender = sender.clone();
+            let href = href.to_string();
+            let options = racing_options(options, sender.clone());
+            std::thread::spawn(move || {
+                let kind = resolver.get_image_kind(&href, &options);
+                let _ = sender.send(Message::Done(kind.map(Box::new)));
+            });
+        }
+        drop(sender);
+
+        // The channel closes once every racer has finished without an image.
+        while let Ok(message) = receiver.recv() {
+            match message {
+                Message::Done(Some(kind)) => return Some(*kind),
+                Message::Done(None) => {}
+                Message::Nested(href, reply) => {
+                    let _ =
+                        reply.send((options.image_href_resolver.resolve_string)(&href, options));
+                }
+            }
+        }
+        crate::utils::log_warn!("no resolver resolved '{}'", crate::redact::redact(href));
+        None
+    }
+}
+
+/// Create a copy of `options` for a racing thread, whose string `href`s are resolved by the
+/// thread waiting for the race through `sender`.
+fn racing_options(options: &Options, sender: Sender<Message>) -> Options<'static> {
+    Options {
+        resources_dir: options.resources_dir.clone(),
+        dpi: options.dpi,
+        font_family: options.font_family.clone(),
+        font_size: options.font_size,
+        languages: options.languages.clone(),
+        shape_rendering: options.shape_rendering,
+        text_rendering: options.text_rendering,
+        image_rendering: options.image_rendering,
+        default_size: options.default_size,
+        image_href_resolver: usvg::ImageHrefResolver {
+            resolve_data: usvg::ImageHrefResolver::default_data_resolver(),
+            resolve_string: Box::new(move |href, _| {
+                let (reply, answer) = channel();
+                sender.send(Message::Nested(href.to_string(), reply)).ok()?;
+                answer.recv().ok()?
+            }),
+        },
+        font_resolver: usvg::FontResolver::default(),
+        fontdb: options.fontdb.clone(),
+        style_sheet: options.style_sheet.clone(),
+    }
+}
+
+impl<'a> HrefStringResolver<'a> for RaceResolver {
+    fn is_target(&self, href: &str) -> bool {
+        self.resolvers.iter().any(|r| r.is_target(href))
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let mut racers: Vec<_> = self
+            .resolvers
+            .iter()
+            .filter(|r| r.is_target(href))
+            .cloned()
+            .collect();
+        match racers.len() {
+            0 => None,
+            1 => racers.pop()?.get_image_kind(href, options),
+            _ => self.race(racers, href, options),
+        }
+    }
+    fn explain_into(&self, trace: &mut ResolutionTrace) {
+        let outcome = if self.is_target(trace.href()) {
+            Outcome::Matched
+        } else {
+            Outcome::NotTarget
+        };
+        trace.push::<Self>(outcome);
+        trace.nested(|trace| {
+            for resolver in &self.resolvers {
+                resolver.explain_into(trace);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryResolver;
+    use std::time::{Duration, Instant};
+
+    /// Resolves every `href` after a delay.
+    struct Slow(Duration);
+
+    impl HrefStringResolver<'_> for Slow {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, _: &str, _: &Options) -> Option<ImageKind> {
+            std::thread::sleep(self.0);
+            Some(ImageKind::PNG(Arc::new(Vec::new())))
+        }
+    }
+
+    #[test]
+    fn first_image_wins() {
+        let fast = MemoryResolver::new()
+            .insert_svg_str(
+                "a.svg",
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><image href="gray.png" width="10" height="10"/></svg>"#,
+            )
+            .insert_png("gray.png", include_bytes!("../test_data/gray.png"));
+        let resolver = RaceResolver::new()
+            .with_resolver(Slow(Duration::from_secs(5)))
+            .with_resolver(fast);
+        let mut options = Options::default();
+        resolver.clone().set_into_options(&mut options);
+
+        let start = Instant::now();
+        let Some(ImageKind::SVG(tree)) = resolver.get_image_kind("a.svg", &options) else {
+            panic!("expected the SVG image to win");
+        };
+        assert!(start.elapsed() < Duration::from_secs(4));
+        assert!(tree.root().has_children());
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use usvg::{ImageKind, Options};
+
+use crate::clock::{Clock, SystemClock};
+use crate::HrefStringResolver;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: SystemTime,
+}
+
+/// A token bucket refilled at `requests_per_second`.
+#[derive(Debug)]
+struct Limit {
+    requests_per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl Limit {
+    fn new(requests_per_second: f64, burst: u32, now: SystemTime) -> Self {
+        assert!(
+            requests_per_second.is_finite() && requests_per_second > 0.0,
+            "requests_per_second must be positive"
+        );
+        let burst = f64::from(burst.max(1));
+        Self {
+            requests_per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                last: now,
+            }),
+        }
+    }
+
+    fn reset(&mut self, now: SystemTime) {
+        if let Ok(bucket) = self.bucket.get_mut() {
+            bucket.tokens = self.burst;
+            bucket.last = now;
+        }
+    }
+
+    /// Reserve a slot in the bucket and return how long to wait before using it.
+    fn reserve(&self, now: SystemTime) -> Duration {
+        let Ok(mut bucket) = self.bucket.lock() else {
+            return Duration::ZERO;
+        };
+        let elapsed = now
+            .duration_since(bucket.last)
+            .unwrap_or_default()
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.burst);
+        bucket.last = now;
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.requests_per_second)
+        }
+    }
+}
+
+/// A resolver that caps the number of requests per second made through the inner resolver.
+///
+/// The limit is shared by every image resolved through this instance, regardless of the host,
+/// using a token bucket. When the budget is exhausted, the calling thread sleeps until the next
+/// request is allowed, so a burst of render jobs can't exceed the allocated egress rate.
+///
+/// Hosts can be exempted from the limit or given a limit of their own with
+/// [`with_exempt_host`](Self::with_exempt_host) and [`with_host_limit`](Self::with_host_limit).
+/// These overrides are checked in the order they were added, before the global limit.
+///
+/// Only `href`s that are a target of the inner resolver consume the budget.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver, rate_limit::RateLimitResolver};
+///
+/// let resolver = RateLimitResolver::new(DefaultResolver, 10.0)
+///     .with_burst(5)
+///     .with_exempt_host("assets.example.com");
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug)]
+pub struct RateLimitResolver<T> {
+    inner: T,
+    limit: Limit,
+    /// Host suffixes with their own limit, or `None` if they are not limited.
+    host_limits: Vec<(String, Option<Limit>)>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<T> RateLimitResolver<T> {
+    /// Create a new `RateLimitResolver` allowing `requests_per_second` requests with a burst of one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is not a positive finite number.
+    pub fn new(inner: T, requests_per_second: f64) -> Self {
+        Self {
+            inner,
+            limit: Limit::new(requests_per_second, 1, SystemClock.now()),
+            host_limits: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Allow up to `burst` requests to be made at once before the rate limit applies.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.limit.burst = f64::from(burst.max(1));
+        self.limit.reset(self.clock.now());
+        self
+    }
+
+    /// Don't limit requests to the host `suffix` or its subdomains (e.g. your own origin).
+    pub fn with_exempt_host(mut self, suffix: impl Into<String>) -> Self {
+        self.host_limits.push((host_suffix(suffix), None));
+        self
+    }
+
+    /// Limit requests to the host `suffix` or its subdomains to `requests_per_second` with a burst
+    /// of `burst`, instead of the global limit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is not a positive finite number.
+    pub fn with_host_limit(
+        mut self,
+        suffix: impl Into<String>,
+        requests_per_second: f64,
+        burst: u32,
+    ) -> Self {
+        let limit = Limit::new(requests_per_second, burst, self.clock.now());
+        self.host_limits.push((host_suffix(suffix), Some(limit)));
+        self
+    }
+
+    /// Use `clock` to measure time and wait, instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        let now = clock.now();
+        self.limit.reset(now);
+        for limit in self.host_limits.iter_mut().filter_map(|(_, l)| l.as_mut()) {
+            limit.reset(now);
+        }
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the limit applying to `href`, or `None` if it is exempt.
+    fn limit(&self, href: &str) -> Option<&Limit> {
+        let host = url::Url::parse(href)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+        let host_limit = host.and_then(|host| {
+            self.host_limits
+                .iter()
+                .find(|(suffix, _)| crate::target::host_has_suffix(&host, suffix))
+        });
+        match host_limit {
+            Some((_, limit)) => limit.as_ref(),
+            None => Some(&self.limit),
+        }
+    }
+}
+
+fn host_suffix(suffix: impl Into<String>) -> String {
+    suffix.into().trim_start_matches('.').to_ascii_lowercase()
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for RateLimitResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        if let Some(limit) = self.limit(href) {
+            let wait = limit.reserve(self.clock.now());
+            if !wait.is_zero() {
+                self.clock.sleep(wait);
+            }
+        }
+        self.inner.get_image_kind(href, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::DefaultResolver;
+    use std::time::Instant;
+
+    #[test]
+    fn limits_request_rate() {
+        let resolver = RateLimitResolver::new(DefaultResolver, 20.0);
+        let options = Options::default();
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            resolver.get_image_kind("./test_data/gray.png", &options);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn burst_is_not_delayed() {
+        let resolver = RateLimitResolver::new(DefaultResolver, 1.0).with_burst(3);
+        let options = Options::default();
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            resolver.get_image_kind("./test_data/gray.png", &options);
+        }
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn mock_clock() {
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH);
+        let resolver = RateLimitResolver::new(DefaultResolver, 2.0).with_clock(clock.clone());
+        let options = Options::default();
+
+        for _ in 0..3 {
+            resolver.get_image_kind("./test_data/gray.png", &options);
+        }
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn host_overrides() {
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH);
+        let resolver = RateLimitResolver::new(DefaultResolver, 1.0)
+            .with_exempt_host("example.com")
+            .with_host_limit("cdn.example.net", 4.0, 1)
+            .with_clock(clock.clone());
+        let options = Options::default();
+
+        for _ in 0..3 {
+            resolver.get_image_kind("https://img.example.com/a.png", &options);
+        }
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH);
+
+        for _ in 0..3 {
+            resolver.get_image_kind("https://cdn.example.net/a.png", &options);
+        }
+        assert_eq!(
+            clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(500)
+        );
+
+        for _ in 0..2 {
+            resolver.get_image_kind("https://other.example.org/a.png", &options);
+        }
+        assert_eq!(
+            clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1500)
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::borrow::Cow;
+use std::sync::RwLock;
+
+/// The replacement for redacted values.
+const REDACTED: &str = "REDACTED";
+
+/// Query parameters redacted by default: the signatures and tokens of common signed URL schemes.
+const DEFAULT_PARAMS: &[&str] = &[
+    "X-Amz-Signature",
+    "X-Amz-Credential",
+    "X-Amz-Security-Token",
+    "X-Goog-Signature",
+    "X-Goog-Credential",
+    "Signature",
+    "sig",
+    "token",
+    "access_token",
+];
+
+static REDACTOR: RwLock<Option<Redactor>> = RwLock::new(None);
+
+/// Redacts secrets from `href`s before they are logged or included in errors.
+///
+/// The values of the configured query parameters (compared case-insensitively) and any user
+/// info in the URL are replaced with `REDACTED`. The crate redacts every `href` it logs or puts
+/// into an error message with the redactor installed by [`set_redactor`], or with
+/// [`Redactor::default`] if none is installed.
+///
+/// ```
+/// use usvg_remote_resolvers::redact::Redactor;
+///
+/// let redactor = Redactor::default().with_param("key");
+/// assert_eq!(
+///     redactor.redact("https://user:pw@example.com/a.png?key=secret&v=1#top"),
+///     "https://REDACTED@example.com/a.png?key=REDACTED&v=1#top",
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    params: Vec<String>,
+}
+
+impl Default for Redactor {
+    /// A redactor for the signature and token parameters of common signed URL schemes, such as
+    /// `X-Amz-Signature`, `Signature` and `token`.
+    fn default() -> Self {
+        Self {
+            params: DEFAULT_PARAMS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl Redactor {
+    /// Create a new `Redactor` that only redacts user info.
+    pub fn new() -> Self {
+        Self { params: Vec::new() }
+    }
+
+    /// Also redact the query parameter `name`.
+    pub fn with_param(mut self, name: impl Into<String>) -> Self {
+        self.params.push(name.into());
+        self
+    }
+
+    /// Redact `href`, borrowing it if there is nothing to redact.
+    pub fn redact<'h>(&self, original: &'h str) -> Cow<'h, str> {
+        let (href, fragment) = original.split_at(original.find('#').unwrap_or(original.len()));
+        let (base, query) = match href.split_once('?') {
+            Some((base, query)) => (base, Some(query)),
+            None => (href, None),
+        };
+
+        let mut changed = false;
+        let base = match user_info_range(base) {
+            Some((start, end)) => {
+                changed = true;
+                Cow::Owned(format!("{}{}{}", &base[..start], REDACTED, &base[end..]))
+            }
+            None => Cow::Borrowed(base),
+        };
+        let query = query.map(|query| {
+            query
+                .split('&')
+                .map(|pair| match pair.split_once('=') {
+                    Some((name, _)) if self.is_redacted(name) => {
+                        changed = true;
+                        Cow::Owned(format!("{name}={REDACTED}"))
+                    }
+                    _ => Cow::Borrowed(pair),
+                })
+                .collect::<Vec<_>>()
+                .join("&")
+        });
+
+        if !changed {
+            return Cow::Borrowed(original);
+        }
+        let mut redacted = base.into_owned();
+        if let Some(query) = query {
+            redacted.push('?');
+            redacted.push_str(&query);
+        }
+        redacted.push_str(fragment);
+        Cow::Owned(redacted)
+    }
+
+    fn is_redacted(&self, name: &str) -> bool {
+        self.params.iter().any(|p| p.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Find the byte range of the user info (`user:password`) in the authority of a URL.
+fn user_info_range(base: &str) -> Option<(usize, usize)> {
+    let start = base.find("://")? + 3;
+    let authority_end = base[start..].find('/').map_or(base.len(), |i| start + i);
+    let end = base[start..authority_end].rfind('@')? + start;
+    Some((start, end))
+}
+
+/// Install the [`Redactor`] used by the crate for logs and error messages.
+pub fn set_redactor(redactor: Redactor) {
+    if let Ok(mut current) = REDACTOR.write() {
+        *current = Some(redactor);
+    }
+}
+
+/// Redact `href` with the installed [`Redactor`], or [`Redactor::default`] if none is installed.
+pub fn redact(href: &str) -> Cow<'_, str> {
+    match REDACTOR.read().as_deref() {
+        Ok(Some(redactor)) => redactor.redact(href),
+        _ => Redactor::default().redact(href),
+    }
+}
+
+/// Format `error`, redacting the occurrences of `href` in its message.
+///
+/// This is used for client errors that include the request URL, which may be normalized by the
+/// client, so the normalized form of `href` is redacted as well.
+#[cfg(any(
+    feature = "hyper",
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "reqwest_http_cache",
+    feature = "s3"
+))]
+pub(crate) fn redact_error(error: &impl std::fmt::Display, href: &str) -> String {
+    let mut message = error.to_string();
+    let normalized = url::Url::parse(href).ok();
+    for href in [Some(href), normalized.as_ref().map(url::Url::as_str)]
+        .into_iter()
+        .flatten()
+    {
+        let redacted = redact(href);
+        if redacted != href {
+            message = message.replace(href, &redacted);
+        }
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_params_and_user_info() {
+        let redactor = Redactor::default();
+        assert!(matches!(
+            redactor.redact("https://example.com/a.png?v=1"),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            redactor
+                .redact("https://b.s3.amazonaws.com/a.png?X-Amz-Expires=60&x-amz-signature=abc"),
+            "https://b.s3.amazonaws.com/a.png?X-Amz-Expires=60&x-amz-signature=REDACTED"
+        );
+        assert_eq!(
+            redactor.redact("https://token@example.com/@a.png"),
+            "https://REDACTED@example.com/@a.png"
+        );
+        assert_eq!(redactor.redact("./a.png?token=1"), "./a.png?token=REDACTED");
+        assert_eq!(
+            Redactor::new().redact("https://example.com/a.png?token=1"),
+            "https://example.com/a.png?token=1"
+        );
+    }
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn redacts_errors() {
+        assert_eq!(
+            redact_error(
+                &"error sending request for url (https://example.com/a.png?sig=abc)",
+                "https://example.com/a.png?sig=abc"
+            ),
+            "error sending request for url (https://example.com/a.png?sig=REDACTED)"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+type BoxedResolver<'a> = Box<dyn HrefStringResolver<'a> + 'a>;
+
+/// A resolver that dispatches `href`s to the resolver registered for their URL scheme.
+///
+/// Schemes are compared case-insensitively. `href`s without a scheme (e.g. relative paths) or with
+/// a scheme that has no resolver are passed to the default resolver, if one is set. Single-letter
+/// schemes are treated as Windows drive letters, so `C:\image.png` has no scheme.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, FileResolver, HrefStringResolver};
+/// use usvg_remote_resolvers::registry::ResolverRegistry;
+///
+/// let registry = ResolverRegistry::new()
+///     .register("file", FileResolver::new())
+///     .with_default(DefaultResolver);
+/// # #[cfg(feature = "reqwest_blocking")]
+/// let registry = registry
+///     .register("http", usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver::default())
+///     .register("https", usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver::default());
+/// let mut options = usvg::Options::default();
+/// registry.set_into_options(&mut options);
+/// ```
+#[derive(Default)]
+pub struct ResolverRegistry<'a> {
+    resolvers: HashMap<String, BoxedResolver<'a>>,
+    default: Option<BoxedResolver<'a>>,
+}
+
+impl std::fmt::Debug for ResolverRegistry<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut schemes: Vec<_> = self.resolvers.keys().collect();
+        schemes.sort();
+        f.debug_struct("ResolverRegistry")
+            .field("schemes", &schemes)
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
+impl<'a> ResolverRegistry<'a> {
+    /// Create a new empty `ResolverRegistry`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve `href`s with the given `scheme` (e.g. `"https"` or `"s3"`) with `resolver`,
+    /// replacing any resolver registered for it before.
+    pub fn register(
+        mut self,
+        scheme: impl Into<String>,
+        resolver: impl HrefStringResolver<'a> + 'a,
+    ) -> Self {
+        self.resolvers
+            .insert(scheme.into().to_ascii_lowercase(), Box::new(resolver));
+        self
+    }
+
+    /// Resolve `href`s without a registered scheme with `resolver`.
+    pub fn with_default(mut self, resolver: impl HrefStringResolver<'a> + 'a) -> Self {
+        self.default = Some(Box::new(resolver));
+        self
+    }
+
+    /// Returns `true` if a resolver is registered for `scheme`.
+    pub fn contains(&self, scheme: &str) -> bool {
+        self.resolvers.contains_key(&scheme.to_ascii_lowercase())
+    }
+
+    fn resolver(&self, href: &str) -> Option<&BoxedResolver<'a>> {
+        scheme(href)
+            .and_then(|scheme| self.resolvers.get(&scheme))
+            .or(self.default.as_ref())
+    }
+}
+
+/// Get the lowercase URL scheme of `href`, if it has one.
+fn scheme(href: &str) -> Option<String> {
+    let (scheme, _) = href.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    (valid && scheme.len() > 1).then(|| scheme.to_ascii_lowercase())
+}
+
+impl<'a> HrefStringResolver<'a> for ResolverRegistry<'a> {
+    fn is_target(&self, href: &str) -> bool {
+        self.resolver(href).is_some_and(|r| r.is_target(href))
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        match self.resolver(trace.href()) {
+            Some(resolver) => trace.wrap::<Self>(&**resolver),
+            None => trace.push::<Self>(crate::explain::Outcome::NotTarget),
+        }
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        self.resolver(href)?.get_image_kind(href, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(&'static str);
+
+    impl HrefStringResolver<'_> for Fixed {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, _: &str, options: &Options) -> Option<ImageKind> {
+            crate::DefaultResolver.get_image_kind(self.0, options)
+        }
+    }
+
+    #[test]
+    fn dispatches_by_scheme() {
+        let registry = ResolverRegistry::new().register("CAS", Fixed("./test_data/gray.png"));
+        let options = Options::default();
+        assert!(registry.contains("cas"));
+        assert!(registry.is_target("cas://anything"));
+        assert!(registry
+            .get_image_kind("Cas://anything", &options)
+            .is_some());
+        assert!(!registry.is_target("https://example.com/a.png"));
+        assert!(!registry.is_target("./test_data/gray.png"));
+
+        let registry = registry.with_default(crate::DefaultResolver);
+        assert!(registry.is_target("C:/test_data/gray.png"));
+        assert!(registry
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_some());
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use usvg::Options;
+
+/// How one external `href` of a document was resolved, part of a [`FetchReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchRecord {
+    /// The `href`, as it appears in the document.
+    pub href: String,
+    /// The format of the resolved image (`"png"`, `"svg"`, ...), or `None` if it failed.
+    pub kind: Option<&'static str>,
+    /// How long resolving the `href` took.
+    pub elapsed: Duration,
+    /// The number of requests sent, including retries and redirects.
+    pub requests: u64,
+    /// The number of response body bytes downloaded.
+    pub bytes: u64,
+    /// Whether the image was served by one of the crate's caches without downloading it.
+    pub cache_hit: bool,
+}
+
+impl FetchRecord {
+    /// Returns `true` if the `href` was resolved.
+    pub fn is_resolved(&self) -> bool {
+        self.kind.is_some()
+    }
+}
+
+/// The external `href`s of a document and how they were resolved, created by
+/// [`parse_with_report`].
+///
+/// The `href`s are listed in the order they finished resolving. The images of a nested SVG image
+/// are listed before it, and its record includes their requests, bytes and time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchReport {
+    records: Vec<FetchRecord>,
+}
+
+impl FetchReport {
+    /// Get the records of every `href`.
+    pub fn records(&self) -> &[FetchRecord] {
+        &self.records
+    }
+
+    /// Get the records of the `href`s that failed to resolve.
+    pub fn failures(&self) -> impl Iterator<Item = &FetchRecord> {
+        self.records.iter().filter(|record| !record.is_resolved())
+    }
+}
+
+/// Parse the SVG document `data` with `options` like [`usvg::Tree::from_data`], reporting how its
+/// external `href`s were resolved.
+///
+/// The `href`s are resolved by the resolver in `options`. Their request counts and sizes are
+/// reported by the crate's HTTP resolvers, and cache hits by its caches, wherever they are in the
+/// chain of resolvers; other resolvers report `0`.
+///
+/// ```
+/// use usvg_remote_resolvers::memory::MemoryResolver;
+/// use usvg_remote_resolvers::report::parse_with_report;
+/// use usvg_remote_resolvers::HrefStringResolver;
+///
+/// let mut options = usvg::Options::default();
+/// MemoryResolver::new()
+///     .insert_png("logo.png", Vec::new())
+///     .set_into_options(&mut options);
+///
+/// let svg = br#"<svg xmlns="http://www.w3.org/2000/svg"><image href="photo.png"/></svg>"#;
+/// let (_tree, report) = parse_with_report(svg, &options).unwrap();
+/// assert_eq!(report.failures().next().unwrap().href, "photo.png");
+/// ```
+pub fn parse_with_report(
+    data: &[u8],
+    options: &Options,
+) -> Result<(usvg::Tree, FetchReport), usvg::Error> {
+    let records = Mutex::new(Vec::new());
+    let mut reporting = crate::utils::borrow_options(options);
+    reporting.image_href_resolver.resolve_string = Box::new(|href, opts| {
+        let start = Instant::now();
+        let ((kind, usage), cache_hit) = crate::events::observe_cache_hit(|| {
+            crate::accounting::measure(|| (options.image_href_resolver.resolve_string)(href, opts))
+        });
+        records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(FetchRecord {
+                href: href.to_string(),
+                kind: kind.as_ref().map(crate::events::kind_name),
+                elapsed: start.elapsed(),
+                requests: usage.requests,
+                bytes: usage.bytes,
+                cache_hit,
+            });
+        kind
+    });
+
+    let tree = usvg::Tree::from_data(data, &reporting)?;
+    drop(reporting);
+    let records = records.into_inner().unwrap_or_else(|e| e.into_inner());
+    Ok((tree, FetchReport { records }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HrefStringResolver;
+    use usvg::ImageKind;
+
+    /// Serves `gray.png` from a pretend cache, and downloads everything else.
+    struct Cached;
+
+    impl HrefStringResolver<'_> for Cached {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            if href == "gray.png" {
+                crate::events::record_cache_hit();
+            } else {
+                crate::accounting::record_request();
+                crate::accounting::record_bytes(100);
+            }
+            crate::DefaultResolver.get_image_kind(&format!("./test_data/{href}"), options)
+        }
+    }
+
+    #[test]
+    fn reports_every_href() {
+        let mut options = Options::default();
+        Cached.set_into_options(&mut options);
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg">
+            <image href="gray.png"/>
+            <image href="missing.png"/>
+        </svg>"#;
+
+        let (tree, report) = parse_with_report(svg, &options).unwrap();
+        assert!(tree.root().has_children());
+        let records: Vec<_> = report
+            .records()
+            .iter()
+            .map(|r| (r.href.as_str(), r.kind, r.requests, r.bytes, r.cache_hit))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("gray.png", Some("png"), 0, 0, true),
+                ("missing.png", None, 1, 100, false),
+            ]
+        );
+        assert_eq!(report.failures().count(), 1);
+    }
+}
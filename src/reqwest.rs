@@ -1,85 +1,380 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bridge::BackgroundRuntime;
+use crate::cache_control::RequestCacheControl;
+use crate::client::{ClientConfig, RedirectGuard, RedirectLog};
+use crate::hooks::{BodyHook, Hook, ResponseHook, ResponseMetadata, SvgVerifier};
+use crate::target::TargetFilter;
+use crate::utils::ImageKindTypes;
 use crate::HrefStringResolver;
 
 /// A resolver that uses reqwest to fetch images.
 ///
 /// This resolver can be used inside a [`tokio`] runtime,
 /// but it will block the current thread when resolving images.
-/// And it *panics* if it is used with a current_thread runtime.
+/// And it *panics* if it is used with a current_thread runtime, unless it drives its requests on
+/// its own runtime with [`with_background_runtime`](`Self::with_background_runtime`).
 #[derive(Debug, Default, Clone)]
 pub struct ReqwestResolver {
     client: reqwest::Client,
+    target: TargetFilter,
+    response_hook: Option<ResponseHook>,
+    body_hook: Option<BodyHook>,
+    redirects: Option<Arc<RedirectLog>>,
+    runtime: Option<Arc<BackgroundRuntime>>,
+    timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    svg_verifier: Option<SvgVerifier>,
+    content_type_overrides: Vec<(TargetFilter, String)>,
+    /// Keeps a client shared through [`ClientConfig`] alive while this resolver uses it.
+    _shared_client: Option<Arc<reqwest::Client>>,
 }
 
 impl ReqwestResolver {
     /// Create a new `ReqwestResolver` with the given [`Client`](`reqwest::Client`).
     pub fn new(client: reqwest::Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            target: TargetFilter::default(),
+            response_hook: None,
+            body_hook: None,
+            redirects: None,
+            runtime: None,
+            timeout: None,
+            max_body_size: None,
+            svg_verifier: None,
+            content_type_overrides: Vec::new(),
+            _shared_client: None,
+        }
+    }
+
+    /// Create a new `ReqwestResolver` with a client built from the given [`ClientConfig`].
+    ///
+    /// Resolvers created from equal configs share the same client and connection pool.
+    pub fn from_config(config: &ClientConfig) -> reqwest::Result<Self> {
+        let client = config.shared()?;
+        Ok(Self {
+            redirects: config.redirect_log(),
+            _shared_client: Some(client.clone()),
+            ..Self::new((*client).clone())
+        })
     }
 
     /// Get the underlying [`Client`](`reqwest::Client`) of this resolver.
     pub fn client(&self) -> &reqwest::Client {
         &self.client
     }
+
+    /// Set the [`TargetFilter`] deciding which `href`s this resolver fetches.
+    pub fn with_target(mut self, target: TargetFilter) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Only fetch the `href`s passing `predicate`, in addition to the checks of the current
+    /// [`TargetFilter`].
+    ///
+    /// This is a shorthand for setting [`TargetFilter::with_predicate`], and replaces a predicate
+    /// set before.
+    pub fn with_target_filter(
+        self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            target: self.target.with_predicate(predicate),
+            ..self
+        }
+    }
+
+    /// Drive requests on a runtime owned by this resolver, with its own worker thread, instead of
+    /// the caller's runtime.
+    ///
+    /// The resolver then works outside of any runtime and inside runtimes of any flavor, including
+    /// current_thread runtimes, although it still blocks the calling thread while fetching. Clones
+    /// share the same runtime.
+    ///
+    /// Fails if the runtime cannot be started.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::HrefStringResolver;
+    /// use usvg_remote_resolvers::reqwest::ReqwestResolver;
+    ///
+    /// let resolver = ReqwestResolver::default().with_background_runtime().unwrap();
+    /// let mut options = usvg::Options::default();
+    /// resolver.set_into_options(&mut options);
+    /// ```
+    pub fn with_background_runtime(mut self) -> std::io::Result<Self> {
+        self.runtime = Some(Arc::new(BackgroundRuntime::new()?));
+        Ok(self)
+    }
+
+    /// Fail requests that take longer than `timeout` in total, from sending the request to reading
+    /// the last byte of the body, overriding the timeout of the client.
+    ///
+    /// The time to connect is a setting of the client; see
+    /// [`ClientConfig::with_connect_timeout`](`crate::client::ClientConfig::with_connect_timeout`).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail responses whose body is larger than `max_body_size` bytes.
+    ///
+    /// A `Content-Length` above the limit is rejected before the body is read, and other bodies are
+    /// read chunk by chunk until they exceed the limit, so an oversized body is never buffered
+    /// whole. By default, bodies of any size are accepted.
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// Set a hook that is called with the [`ResponseMetadata`] of every successfully resolved image.
+    pub fn with_response_hook(
+        mut self,
+        hook: impl Fn(&ResponseMetadata, &usvg::ImageKind) + Send + Sync + 'static,
+    ) -> Self {
+        self.response_hook = Some(Hook(Arc::new(hook)));
+        self
+    }
+
+    /// Set a hook that is called with the `href`, the content type and the raw bytes of every
+    /// successfully fetched body, e.g. to persist images to an application's own storage without
+    /// downloading them again.
+    ///
+    /// The hook is called before the image is decoded.
+    pub fn with_body_hook(
+        mut self,
+        hook: impl Fn(&str, Option<&str>, Arc<Vec<u8>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.body_hook = Some(Hook(Arc::new(hook)));
+        self
+    }
+
+    /// Only accept nested SVG images whose detached signature is accepted by `verifier`.
+    ///
+    /// The signature is fetched from the `href` of the SVG image with `.sig` appended, and
+    /// `verifier` is called with the `href`, the SVG body and the signature before the SVG is
+    /// parsed. SVG images are rejected if the signature can't be fetched or `verifier` returns
+    /// `false`. Raster images are not affected.
+    pub fn with_svg_verifier(
+        mut self,
+        verifier: impl Fn(&str, &[u8], &[u8]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.svg_verifier = Some(Hook(Arc::new(verifier)));
+        self
+    }
+
+    /// Treat responses for `href`s matching `filter` as `content_type`, whatever their
+    /// `Content-Type` header says, e.g. for origins known to mislabel their images. When several
+    /// overrides match an `href`, the first one added wins.
+    pub fn with_content_type_override(
+        mut self,
+        filter: TargetFilter,
+        content_type: impl Into<String>,
+    ) -> Self {
+        self.content_type_overrides
+            .push((filter, content_type.into()));
+        self
+    }
+
+    /// Run the request `future` for `href` on the runtime of this resolver, or on the caller's
+    /// runtime, blocking the current thread until it completes.
+    ///
+    /// The request may be sent on another thread, so it is accounted for here, once there is a
+    /// runtime to send it.
+    fn run_request<F>(&self, href: &str, future: F) -> Option<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.runtime {
+            Some(runtime) => {
+                crate::accounting::record_request();
+                runtime.run(future)
+            }
+            None => {
+                // Check if we're already in a tokio runtime
+                let Ok(handle) = tokio::runtime::Handle::try_current() else {
+                    crate::utils::log_warn!(
+                        "no tokio runtime found; cannot resolve '{}'",
+                        crate::redact::redact(href)
+                    );
+                    return None;
+                };
+                // We're in an async context, use block_in_place
+                crate::accounting::record_request();
+                Some(tokio::task::block_in_place(|| handle.block_on(future)))
+            }
+        }
+    }
+
+    /// Check the detached signature of the SVG image `body` with the verifier set with
+    /// [`with_svg_verifier`](`Self::with_svg_verifier`), if any.
+    fn verify_svg(&self, href: &str, body: &[u8]) -> bool {
+        let Some(verifier) = &self.svg_verifier else {
+            return true;
+        };
+        let resolver = self.clone();
+        let sig_href = format!("{href}.sig");
+        let signature = self
+            .run_request(
+                href,
+                async move { resolver.fetch_signature(&sig_href).await },
+            )
+            .flatten();
+        if let Some(signature) = &signature {
+            crate::accounting::record_bytes(signature.len());
+        }
+        crate::hooks::verify_svg(verifier, href, body, signature.as_deref())
+    }
 }
 
 impl From<reqwest::Client> for ReqwestResolver {
     fn from(client: reqwest::Client) -> Self {
-        Self { client }
+        Self::new(client)
     }
 }
 
 impl HrefStringResolver<'_> for ReqwestResolver {
     fn is_target(&self, href: &str) -> bool {
-        crate::utils::is_remote_url(href)
+        self.target.matches(href)
     }
     fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
-        let client = self.client.clone();
-        let href = href.to_string();
-        // Check if we're already in a tokio runtime
-        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        let cache_control = RequestCacheControl::current().header_value();
+        let resolver = self.clone();
+        let owned_href = href.to_string();
+        let (image_type, content_type, body, metadata) = self.run_request(href, async move {
+            resolver.fetch(&owned_href, cache_control).await
+        })??;
+        crate::accounting::record_bytes(body.len());
+        if matches!(image_type, ImageKindTypes::Svg) && !self.verify_svg(href, &body) {
+            return None;
+        }
+        let body = Arc::new(body);
+        if let Some(hook) = &self.body_hook {
+            hook(href, content_type.as_deref(), body.clone());
+        }
+
+        let kind = image_type.into_image_kind_from(body, href, options)?;
+        if let (Some(hook), Some(metadata)) = (&self.response_hook, metadata) {
+            hook(&metadata, &kind);
+        }
+        Some(kind)
+    }
+}
+
+/// The image type, content type, body and metadata of a fetched response.
+type FetchedBody = (
+    ImageKindTypes,
+    Option<String>,
+    Vec<u8>,
+    Option<ResponseMetadata>,
+);
+
+impl ReqwestResolver {
+    async fn fetch(&self, href: &str, cache_control: Option<String>) -> Option<FetchedBody> {
+        let _redirects = RedirectGuard::new(self.redirects.as_deref(), href);
+        let mut req = self.client.get(href);
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        if let Some(value) = cache_control {
+            req = req.header(reqwest::header::CACHE_CONTROL, value);
+        }
+        let resp = match req.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "failed to fetch '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
+                );
+                return None;
+            }
+        };
+        if !self.target.accepts_final_url(href, resp.url().as_str()) {
+            return None;
+        }
+        let redirects = self
+            .redirects
+            .as_ref()
+            .map(|log| log.take(href))
+            .unwrap_or_default();
+        let metadata = self.response_hook.as_ref().map(|_| ResponseMetadata {
+            redirects,
+            ..ResponseMetadata::from_reqwest(href, resp.url(), resp.status(), resp.headers())
+        });
+        if !resp.status().is_success() {
             crate::utils::log_warn!(
-                "no tokio runtime found; cannot resolve '{}'",
-                href
+                "failed to fetch '{}': status {}",
+                crate::redact::redact(href),
+                resp.status()
             );
             return None;
+        }
+        let content_type = crate::target::override_content_type(
+            &self.content_type_overrides,
+            href,
+            resp.headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok()),
+        )
+        .map(str::to_string);
+        let unsupported = || {
+            crate::utils::log_warn!(
+                "unsupported image type for '{}' (content-type: {:?})",
+                crate::redact::redact(href),
+                content_type
+            );
         };
-        // We're in an async context, use block_in_place
-        let (image_type, body) = tokio::task::block_in_place(|| {
-            handle.block_on(async {
-                let resp = match client.get(&href).send().await {
-                    Ok(resp) => resp,
-                    Err(e) => {
-                        crate::utils::log_warn!("failed to fetch '{}': {}", href, e);
-                        return None;
-                    }
-                };
-                let content_type = resp
-                    .headers()
-                    .get(reqwest::header::CONTENT_TYPE)
-                    .and_then(|v| v.to_str().ok());
-                let image_type = match crate::utils::ImageKindTypes::get_image_type(content_type, &href) {
-                    Some(t) => t,
-                    None => {
-                        crate::utils::log_warn!(
-                            "unsupported image type for '{}' (content-type: {:?})",
-                            href,
-                            content_type
-                        );
-                        return None;
-                    }
-                };
-                let body = match resp.bytes().await {
-                    Ok(b) => b.to_vec(),
-                    Err(e) => {
-                        crate::utils::log_warn!("failed to read response body for '{}': {}", href, e);
-                        return None;
-                    }
-                };
-                Some((image_type, body))
-            })
-        })?;
+        if ImageKindTypes::get_image_type(content_type.as_deref(), href).is_none()
+            && !ImageKindTypes::is_generic(content_type.as_deref())
+        {
+            unsupported();
+            return None;
+        }
+        let body = crate::utils::read_body(resp, href, self.max_body_size).await?;
+        if crate::utils::is_empty_body(href, &body) {
+            return None;
+        }
+        let Some(image_type) = ImageKindTypes::detect(content_type.as_deref(), href, &body) else {
+            unsupported();
+            return None;
+        };
+        Some((image_type, content_type, body, metadata))
+    }
 
-        image_type.into_image_kind(body.into(), options)
+    async fn fetch_signature(&self, sig_href: &str) -> Option<Vec<u8>> {
+        let _redirects = RedirectGuard::new(self.redirects.as_deref(), sig_href);
+        let mut req = self.client.get(sig_href);
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        let resp = match req.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "failed to fetch '{}': {}",
+                    crate::redact::redact(sig_href),
+                    crate::redact::redact_error(&e, sig_href)
+                );
+                return None;
+            }
+        };
+        if !self.target.accepts_final_url(sig_href, resp.url().as_str()) {
+            return None;
+        }
+        if !resp.status().is_success() {
+            crate::utils::log_warn!(
+                "failed to fetch '{}': status {}",
+                crate::redact::redact(sig_href),
+                resp.status()
+            );
+            return None;
+        }
+        crate::utils::read_body(resp, sig_href, self.max_body_size).await
     }
 }
 
@@ -88,6 +383,65 @@ mod tests {
     use super::*;
     use usvg::Options;
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn timeout_and_max_body_size() {
+        let body = include_bytes!("../test_data/gray.png");
+        let mut s = mockito::Server::new_async().await;
+        s.mock("GET", "/chunked.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| {
+                for chunk in body.chunks(100) {
+                    w.write_all(chunk)?;
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+        s.mock("GET", "/stalled.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_millis(500));
+                w.write_all(body)
+            })
+            .create_async()
+            .await;
+        let options = Options::default();
+        let chunked = format!("{}/chunked.png", s.url());
+
+        let resolver = ReqwestResolver::default().with_max_body_size(body.len());
+        assert!(resolver.get_image_kind(&chunked, &options).is_some());
+        let resolver = ReqwestResolver::default().with_max_body_size(body.len() - 1);
+        assert!(resolver.get_image_kind(&chunked, &options).is_none());
+
+        let stalled = format!("{}/stalled.png", s.url());
+        let resolver = ReqwestResolver::default().with_timeout(Duration::from_millis(100));
+        assert!(resolver.get_image_kind(&stalled, &options).is_none());
+        let resolver = ReqwestResolver::default().with_timeout(Duration::from_secs(5));
+        assert!(resolver.get_image_kind(&stalled, &options).is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn error_statuses() {
+        let mut s = mockito::Server::new_async().await;
+        for (path, status) in [("/missing.png", 404), ("/unavailable.png", 503)] {
+            s.mock("GET", path)
+                .with_status(status)
+                .with_header("content-type", "image/png")
+                .with_body(include_bytes!("../test_data/gray.png"))
+                .create_async()
+                .await;
+        }
+        let resolver = ReqwestResolver::default();
+        for path in ["/missing.png", "/unavailable.png"] {
+            let href = format!("{}{path}", s.url());
+            assert!(resolver
+                .get_image_kind(&href, &Options::default())
+                .is_none());
+        }
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn reqwest_resolver() {
         let resolver = ReqwestResolver::default();
@@ -136,6 +490,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn background_runtime() {
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(2)
+            .create();
+        let href = format!("{}/gray.png", s.url());
+        let resolver = ReqwestResolver::default()
+            .with_background_runtime()
+            .unwrap();
+
+        assert!(resolver
+            .get_image_kind(&href, &Options::default())
+            .is_some());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            assert!(resolver
+                .get_image_kind(&href, &Options::default())
+                .is_some());
+        });
+    }
+
+    #[test]
+    fn content_type_override() {
+        let mut s = mockito::Server::new();
+        for path in ["/legacy/gray", "/other/gray"] {
+            s.mock("GET", path)
+                .with_status(200)
+                .with_header("content-type", "text/plain")
+                .with_body(include_bytes!("../test_data/gray.png"))
+                .create();
+        }
+        let filter = TargetFilter::new().with_predicate(|href| href.contains("/legacy/"));
+        let resolver = ReqwestResolver::default()
+            .with_background_runtime()
+            .unwrap()
+            .with_content_type_override(filter, "image/png");
+        let options = Options::default();
+        assert!(matches!(
+            resolver.get_image_kind(&format!("{}/legacy/gray", s.url()), &options),
+            Some(usvg::ImageKind::PNG(_))
+        ));
+        assert!(resolver
+            .get_image_kind(&format!("{}/other/gray", s.url()), &options)
+            .is_none());
+    }
+
+    #[test]
+    fn svg_verifier() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+        let mut s = mockito::Server::new();
+        for name in ["signed", "forged", "unsigned"] {
+            s.mock("GET", format!("/{name}.svg").as_str())
+                .with_status(200)
+                .with_header("content-type", "image/svg+xml")
+                .with_body(svg)
+                .create();
+        }
+        s.mock("GET", "/signed.svg.sig").with_body("good").create();
+        s.mock("GET", "/forged.svg.sig").with_body("bad").create();
+        s.mock("GET", "/unsigned.svg.sig").with_status(404).create();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let resolver = ReqwestResolver::default()
+            .with_background_runtime()
+            .unwrap()
+            .with_svg_verifier(|_, body, signature| !body.is_empty() && signature == b"good");
+        let options = Options::default();
+        let resolve =
+            |name: &str| resolver.get_image_kind(&format!("{}/{name}", s.url()), &options);
+        assert!(matches!(
+            resolve("signed.svg"),
+            Some(usvg::ImageKind::SVG(_))
+        ));
+        assert!(resolve("forged.svg").is_none());
+        assert!(resolve("unsigned.svg").is_none());
+        assert!(matches!(resolve("gray.png"), Some(usvg::ImageKind::PNG(_))));
+    }
+
     #[tokio::test]
     #[should_panic]
     async fn reqwest_resolve_current() {
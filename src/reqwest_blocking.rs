@@ -1,4 +1,17 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
 use super::HrefStringResolver;
+use crate::cache_control::RequestCacheControl;
+use crate::client::{ClientConfig, RedirectGuard, RedirectLog};
+use crate::clock::{Clock, SystemClock};
+use crate::error::ResolveError;
+use crate::fetch_lock::FetchLock;
+use crate::hooks::{BodyHook, Hook, ResponseHook, ResponseMetadata, SvgVerifier};
+use crate::normalize::UrlNormalizer;
+use crate::target::TargetFilter;
 use crate::utils::ImageKindTypes;
 
 /// Blocking Reqwest resolver.
@@ -9,69 +22,1684 @@ use crate::utils::ImageKindTypes;
 #[derive(Debug, Default, Clone)]
 pub struct BlockingReqwestResolver {
     client: reqwest::blocking::Client,
+    target: TargetFilter,
+    response_hook: Option<ResponseHook>,
+    body_hook: Option<BodyHook>,
+    redirects: Option<Arc<RedirectLog>>,
+    max_body_size: Option<usize>,
+    timeout: Option<Duration>,
+    adaptive_timeout: Option<AdaptiveTimeout>,
+    svg_verifier: Option<SvgVerifier>,
+    content_type_overrides: Vec<(TargetFilter, String)>,
+    /// Keeps a client shared through [`ClientConfig`] alive while this resolver uses it.
+    _shared_client: Option<Arc<reqwest::blocking::Client>>,
+    #[cfg(feature = "http_auth")]
+    credentials: Option<Arc<crate::auth::CredentialStore>>,
 }
 
 impl BlockingReqwestResolver {
     /// Create a new `BlockingReqwestResolver` with the given [`Client`](`reqwest::blocking::Client`).
     pub fn new(client: reqwest::blocking::Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            target: TargetFilter::default(),
+            response_hook: None,
+            body_hook: None,
+            redirects: None,
+            max_body_size: None,
+            timeout: None,
+            adaptive_timeout: None,
+            svg_verifier: None,
+            content_type_overrides: Vec::new(),
+            _shared_client: None,
+            #[cfg(feature = "http_auth")]
+            credentials: None,
+        }
+    }
+
+    /// Create a new `BlockingReqwestResolver` with a client built from the given [`ClientConfig`].
+    ///
+    /// Resolvers created from equal configs share the same client and connection pool.
+    pub fn from_config(config: &ClientConfig) -> reqwest::Result<Self> {
+        let client = config.shared_blocking()?;
+        Ok(Self {
+            redirects: config.redirect_log(),
+            _shared_client: Some(client.clone()),
+            ..Self::new((*client).clone())
+        })
     }
 
     /// Get the underlying [`Client`](`reqwest::blocking::Client`) of this resolver.
     pub fn client(&self) -> &reqwest::blocking::Client {
         &self.client
     }
+
+    /// Set the [`TargetFilter`] deciding which `href`s this resolver fetches.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::{
+    ///     HrefStringResolver, reqwest_blocking::BlockingReqwestResolver, target::TargetFilter,
+    /// };
+    ///
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_target(TargetFilter::new().with_host_suffix("assets.example.com"));
+    /// assert!(resolver.is_target("https://assets.example.com/logo.png"));
+    /// assert!(!resolver.is_target("https://example.org/logo.png"));
+    /// ```
+    pub fn with_target(mut self, target: TargetFilter) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Only fetch the `href`s passing `predicate`, in addition to the checks of the current
+    /// [`TargetFilter`].
+    ///
+    /// This is a shorthand for setting [`TargetFilter::with_predicate`], and replaces a predicate
+    /// set before.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::{HrefStringResolver, reqwest_blocking::BlockingReqwestResolver};
+    ///
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_target_filter(|href| href.contains("/public/"));
+    /// assert!(resolver.is_target("https://example.com/public/logo.png"));
+    /// assert!(!resolver.is_target("https://example.com/private/logo.png"));
+    /// assert!(!resolver.is_target("ftp://example.com/public/logo.png"));
+    /// ```
+    pub fn with_target_filter(
+        self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            target: self.target.with_predicate(predicate),
+            ..self
+        }
+    }
+
+    /// Set a hook that is called with the [`ResponseMetadata`] of every successfully resolved image.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+    ///
+    /// let resolver = BlockingReqwestResolver::default().with_response_hook(|meta, _| {
+    ///     println!("{} -> {} (age: {:?})", meta.href, meta.final_url, meta.header("age"));
+    /// });
+    /// ```
+    pub fn with_response_hook(
+        mut self,
+        hook: impl Fn(&ResponseMetadata, &usvg::ImageKind) + Send + Sync + 'static,
+    ) -> Self {
+        self.response_hook = Some(Hook(Arc::new(hook)));
+        self
+    }
+
+    /// Set a hook that is called with the `href`, the content type and the raw bytes of every
+    /// successfully fetched body, e.g. to persist images to an application's own storage without
+    /// downloading them again.
+    ///
+    /// The content type is the one the image type was detected from, so it reflects
+    /// [`with_content_type_override`](`Self::with_content_type_override`). The hook is called
+    /// before the image is decoded, and not for images served from a cache.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+    ///
+    /// let resolver = BlockingReqwestResolver::default().with_body_hook(|href, content_type, body| {
+    ///     println!("fetched {} bytes of {content_type:?} from {href}", body.len());
+    /// });
+    /// ```
+    pub fn with_body_hook(
+        mut self,
+        hook: impl Fn(&str, Option<&str>, Arc<Vec<u8>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.body_hook = Some(Hook(Arc::new(hook)));
+        self
+    }
+
+    /// Fail responses whose body is larger than `max_body_size` bytes.
+    ///
+    /// A `Content-Length` above the limit is rejected before the body is read. Bodies without a
+    /// `Content-Length`, such as chunked responses, are read incrementally into a buffer that grows
+    /// geometrically, and reading stops as soon as the limit is exceeded, so an oversized body is
+    /// never buffered whole. By default, bodies of any size are accepted.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+    ///
+    /// let resolver = BlockingReqwestResolver::default().with_max_body_size(10 * 1024 * 1024);
+    /// ```
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// Fail requests that take longer than `timeout` in total, from sending the request to reading
+    /// the last byte of the body, overriding the timeout of the client.
+    ///
+    /// The time to connect is a setting of the client; see
+    /// [`ClientConfig::with_connect_timeout`](`crate::client::ClientConfig::with_connect_timeout`).
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+    ///
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_timeout(Duration::from_secs(10))
+    ///     .with_max_body_size(10 * 1024 * 1024);
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give bodies a deadline growing with their `Content-Length`, instead of a fixed timeout.
+    ///
+    /// See [`AdaptiveTimeout`] for how the deadline is computed.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use usvg_remote_resolvers::reqwest_blocking::{AdaptiveTimeout, BlockingReqwestResolver};
+    ///
+    /// let resolver = BlockingReqwestResolver::default().with_adaptive_timeout(AdaptiveTimeout::new(
+    ///     Duration::from_secs(2),
+    ///     Duration::from_secs(1),
+    ///     Duration::from_secs(60),
+    /// ));
+    /// ```
+    pub fn with_adaptive_timeout(mut self, timeout: AdaptiveTimeout) -> Self {
+        self.adaptive_timeout = Some(timeout);
+        self
+    }
+
+    /// Treat responses for `href`s matching `filter` as `content_type`, whatever their
+    /// `Content-Type` header says.
+    ///
+    /// Use it for origins known to mislabel their images, e.g. as `application/octet-stream`,
+    /// instead of detecting the type from the bytes of every response. When several overrides
+    /// match an `href`, the first one added wins.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+    /// use usvg_remote_resolvers::target::TargetFilter;
+    ///
+    /// let resolver = BlockingReqwestResolver::default().with_content_type_override(
+    ///     TargetFilter::new()
+    ///         .with_host_suffix("legacy-cdn.example.com")
+    ///         .with_predicate(|href| href.contains("/thumbs/")),
+    ///     "image/jpeg",
+    /// );
+    /// ```
+    pub fn with_content_type_override(
+        mut self,
+        filter: TargetFilter,
+        content_type: impl Into<String>,
+    ) -> Self {
+        self.content_type_overrides
+            .push((filter, content_type.into()));
+        self
+    }
+
+    /// Get the content type of the response for `href`, applying the overrides set with
+    /// [`with_content_type_override`](`Self::with_content_type_override`) to the `header`.
+    fn content_type<'a>(&'a self, href: &str, header: Option<&'a str>) -> Option<&'a str> {
+        crate::target::override_content_type(&self.content_type_overrides, href, header)
+    }
+
+    /// Only accept nested SVG images whose detached signature is accepted by `verifier`.
+    ///
+    /// The signature of an SVG image is fetched from its `href` with `.sig` appended, and
+    /// `verifier` is called with the `href`, the SVG body and the signature body before the SVG is
+    /// parsed. SVG images are rejected if the signature can't be fetched or `verifier` returns
+    /// `false`. Raster images are not affected.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+    ///
+    /// # fn verify_ed25519(_: &[u8], _: &[u8]) -> bool { true }
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_svg_verifier(|_href, svg, signature| verify_ed25519(svg, signature));
+    /// ```
+    pub fn with_svg_verifier(
+        mut self,
+        verifier: impl Fn(&str, &[u8], &[u8]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.svg_verifier = Some(Hook(Arc::new(verifier)));
+        self
+    }
+
+    /// Answer HTTP Basic and Digest challenges with credentials from the given store.
+    ///
+    /// See [`CredentialStore`](`crate::auth::CredentialStore`) for the supported schemes.
+    #[cfg(feature = "http_auth")]
+    pub fn with_credentials(mut self, credentials: crate::auth::CredentialStore) -> Self {
+        self.credentials = Some(Arc::new(credentials));
+        self
+    }
+
+    /// Send `req`, retrying once with an `Authorization` header if it is challenged.
+    fn send(
+        &self,
+        req: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::Result<reqwest::blocking::Response> {
+        let req = match self.timeout {
+            Some(timeout) => req.timeout(timeout),
+            None => req,
+        };
+        #[cfg(feature = "http_auth")]
+        if let Some(credentials) = &self.credentials {
+            let retry = req.try_clone();
+            crate::accounting::record_request();
+            let resp = req.send()?;
+            if resp.status() != reqwest::StatusCode::UNAUTHORIZED {
+                return Ok(resp);
+            }
+            let challenges = resp
+                .headers()
+                .get_all(reqwest::header::WWW_AUTHENTICATE)
+                .iter()
+                .filter_map(|v| v.to_str().ok());
+            let authorization = credentials.authorization(resp.url(), challenges);
+            return match (retry, authorization) {
+                (Some(retry), Some(authorization)) => {
+                    crate::accounting::record_request();
+                    retry
+                        .header(reqwest::header::AUTHORIZATION, authorization)
+                        .send()
+                }
+                _ => Ok(resp),
+            };
+        }
+        crate::accounting::record_request();
+        req.send()
+    }
+
+    /// Read the body of `resp`, enforcing the limit set with
+    /// [`with_max_body_size`](`Self::with_max_body_size`).
+    fn read_body(
+        &self,
+        resp: reqwest::blocking::Response,
+        href: &str,
+    ) -> Result<Vec<u8>, BodyError> {
+        let deadline = self
+            .adaptive_timeout
+            .map(|timeout| Instant::now() + timeout.timeout_for(resp.content_length()));
+        // Read incrementally even without limits, as the timeout of the client then applies to
+        // every read, while `bytes` would apply it to the whole body.
+        match read_limited(resp, self.max_body_size, deadline) {
+            Ok(body) => {
+                crate::accounting::record_bytes(body.len());
+                Ok(body)
+            }
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "failed to read response body for '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Check the detached signature of the SVG image `body` with the verifier set with
+    /// [`with_svg_verifier`](`Self::with_svg_verifier`), if any.
+    fn verify_svg(&self, href: &str, body: &[u8]) -> Result<(), ResolveError> {
+        let Some(verifier) = &self.svg_verifier else {
+            return Ok(());
+        };
+        let sig_href = format!("{href}.sig");
+        let _redirects = RedirectGuard::new(self.redirects.as_deref(), &sig_href);
+        let signature = match self.send(self.client.get(&sig_href)) {
+            Ok(resp) if resp.status().is_success() => self
+                .read_body(resp, &sig_href)
+                .map_err(|e| ResolveError::Network(crate::redact::redact_error(&e, href)))?,
+            Ok(resp) => {
+                crate::utils::log_warn!(
+                    "failed to fetch the signature of '{}': status {}",
+                    crate::redact::redact(href),
+                    resp.status()
+                );
+                return Err(ResolveError::Policy("missing SVG signature".to_string()));
+            }
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "failed to fetch the signature of '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
+                );
+                return Err(request_error(&e, href));
+            }
+        };
+        if verifier(href, body, &signature) {
+            Ok(())
+        } else {
+            crate::utils::log_warn!(
+                "rejected SVG with an invalid signature: '{}'",
+                crate::redact::redact(href)
+            );
+            Err(ResolveError::Policy("invalid SVG signature".to_string()))
+        }
+    }
+
+    fn response_metadata(
+        &self,
+        href: &str,
+        resp: &reqwest::blocking::Response,
+    ) -> Option<ResponseMetadata> {
+        let redirects = self
+            .redirects
+            .as_ref()
+            .map(|log| log.take(href))
+            .unwrap_or_default();
+        self.response_hook.as_ref()?;
+        Some(ResponseMetadata {
+            redirects,
+            ..ResponseMetadata::from_reqwest(href, resp.url(), resp.status(), resp.headers())
+        })
+    }
+
+    fn call_body_hook(&self, href: &str, content_type: Option<&str>, body: &Arc<Vec<u8>>) {
+        if let Some(hook) = &self.body_hook {
+            hook(href, content_type, body.clone());
+        }
+    }
+
+    pub(crate) fn call_response_hook(
+        &self,
+        metadata: Option<ResponseMetadata>,
+        kind: &usvg::ImageKind,
+    ) {
+        if let (Some(hook), Some(metadata)) = (&self.response_hook, metadata) {
+            hook(&metadata, kind);
+        }
+    }
 }
 
 impl From<reqwest::blocking::Client> for BlockingReqwestResolver {
     fn from(client: reqwest::blocking::Client) -> Self {
-        Self { client }
+        Self::new(client)
     }
 }
 
 impl HrefStringResolver<'_> for BlockingReqwestResolver {
     fn is_target(&self, href: &str) -> bool {
-        crate::utils::is_remote_url(href)
+        self.target.matches(href)
     }
     fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
-        let resp = match self.client.get(href).send() {
+        crate::error::TryHrefStringResolver::try_get_image_kind(self, href, options).ok()
+    }
+}
+
+impl crate::error::TryHrefStringResolver<'_> for BlockingReqwestResolver {
+    fn is_target(&self, href: &str) -> bool {
+        self.target.matches(href)
+    }
+    fn try_get_image_kind(
+        &self,
+        href: &str,
+        options: &usvg::Options,
+    ) -> Result<usvg::ImageKind, ResolveError> {
+        let fetched = self.fetch_body(href)?;
+        let kind = fetched
+            .image_type
+            .into_image_kind_from(fetched.body, href, options)
+            .ok_or_else(|| ResolveError::Decode("failed to parse SVG".to_string()))?;
+        self.call_response_hook(fetched.metadata, &kind);
+        Ok(kind)
+    }
+}
+
+/// A response body fetched by [`BlockingReqwestResolver::fetch_body`].
+pub(crate) struct FetchedBody {
+    pub(crate) image_type: ImageKindTypes,
+    pub(crate) body: Arc<Vec<u8>>,
+    pub(crate) metadata: Option<ResponseMetadata>,
+}
+
+impl BlockingReqwestResolver {
+    /// Fetch the body of `href` and detect its image type, without decoding it.
+    pub(crate) fn fetch_body(&self, href: &str) -> Result<FetchedBody, ResolveError> {
+        let _redirects = RedirectGuard::new(self.redirects.as_deref(), href);
+        let mut req = self.client.get(href);
+        if let Some(value) = RequestCacheControl::current().header_value() {
+            req = req.header(reqwest::header::CACHE_CONTROL, value);
+        }
+        let resp = match self.send(req) {
             Ok(resp) => resp,
             Err(e) => {
-                crate::utils::log_warn!("failed to fetch '{}': {}", href, e);
-                return None;
+                crate::utils::log_warn!(
+                    "failed to fetch '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
+                );
+                return Err(request_error(&e, href));
+            }
+        };
+        if !self.target.accepts_final_url(href, resp.url().as_str()) {
+            return Err(ResolveError::Policy(
+                "redirected to a URL rejected by the target".to_string(),
+            ));
+        }
+        let status = resp.status();
+        let metadata = self.response_metadata(href, &resp);
+        if !status.is_success() {
+            crate::utils::log_warn!(
+                "failed to fetch '{}': status {}",
+                crate::redact::redact(href),
+                status
+            );
+            return Err(ResolveError::Status(status.as_u16()));
+        }
+        let content_type = self
+            .content_type(
+                href,
+                resp.headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok()),
+            )
+            .map(str::to_string);
+        let unsupported = |content_type: Option<String>| {
+            crate::utils::log_warn!(
+                "unsupported image type for '{}' (content-type: {:?})",
+                crate::redact::redact(href),
+                content_type
+            );
+            ResolveError::UnsupportedType(content_type)
+        };
+        if ImageKindTypes::get_image_type(content_type.as_deref(), href).is_none()
+            && !ImageKindTypes::is_generic(content_type.as_deref())
+        {
+            return Err(unsupported(content_type));
+        }
+        let body = self.read_body(resp, href).map_err(|e| match e {
+            BodyError::TooLarge(max_size) => {
+                ResolveError::Policy(format!("response body exceeds {max_size} bytes"))
             }
+            BodyError::Io(e) if is_timeout(&e) => ResolveError::Timeout,
+            BodyError::Io(e) => ResolveError::Network(crate::redact::redact_error(&e, href)),
+        })?;
+        if crate::utils::is_empty_body(href, &body) {
+            return Err(ResolveError::EmptyBody);
+        }
+        let Some(image_type) = ImageKindTypes::detect(content_type.as_deref(), href, &body) else {
+            return Err(unsupported(content_type));
+        };
+        if matches!(image_type, ImageKindTypes::Svg) {
+            self.verify_svg(href, &body)?;
+        }
+        let body = Arc::new(body);
+        self.call_body_hook(href, content_type.as_deref(), &body);
+        Ok(FetchedBody {
+            image_type,
+            body,
+            metadata,
+        })
+    }
+}
+
+/// A deadline for reading a response body made of a base plus a time per megabyte of its
+/// `Content-Length`, bounded by a hard maximum.
+///
+/// A fixed timeout either kills legitimate large downloads or lets small dead requests linger.
+/// With an adaptive timeout, once the headers arrive, the body must be read within
+/// `base + per_megabyte * Content-Length`, or the maximum for bodies without a `Content-Length`.
+/// The deadline is checked as the body is read, so waiting for the headers and for each read is
+/// still bounded by the timeout of the client, e.g. a
+/// [first-byte timeout](`crate::client::ClientConfig::with_first_byte_timeout`).
+///
+/// ```
+/// use std::time::Duration;
+/// use usvg_remote_resolvers::reqwest_blocking::AdaptiveTimeout;
+///
+/// let timeout = AdaptiveTimeout::new(
+///     Duration::from_secs(2),
+///     Duration::from_secs(1),
+///     Duration::from_secs(60),
+/// );
+/// assert_eq!(timeout.timeout_for(Some(3 * 1024 * 1024)), Duration::from_secs(5));
+/// assert_eq!(timeout.timeout_for(None), Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveTimeout {
+    base: Duration,
+    per_megabyte: Duration,
+    max: Duration,
+}
+
+impl AdaptiveTimeout {
+    /// Create a new `AdaptiveTimeout` allowing `base` plus `per_megabyte` for every MiB of the
+    /// body, and at most `max`.
+    pub fn new(base: Duration, per_megabyte: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            per_megabyte,
+            max,
+        }
+    }
+
+    /// Get the time allowed for a body of `content_length` bytes, or the maximum if it is unknown.
+    pub fn timeout_for(&self, content_length: Option<u64>) -> Duration {
+        let Some(len) = content_length else {
+            return self.max;
         };
-        let content_type = resp
-            .headers()
-            .get(reqwest::header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok());
-        let image_type = match ImageKindTypes::get_image_type(content_type, href) {
-            Some(t) => t,
+        let megabytes = len as f64 / (1024.0 * 1024.0);
+        let timeout = self.base.as_secs_f64() + self.per_megabyte.as_secs_f64() * megabytes;
+        Duration::try_from_secs_f64(timeout).map_or(self.max, |timeout| timeout.min(self.max))
+    }
+}
+
+/// The initial capacity of body buffers for responses without a `Content-Length`.
+const INITIAL_BODY_CAPACITY: usize = 8 * 1024;
+
+/// An error while reading a response body.
+#[derive(Debug)]
+enum BodyError {
+    /// The body exceeds the limit, in bytes.
+    TooLarge(usize),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for BodyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(max_size) => write!(f, "body exceeds {max_size} bytes"),
+            Self::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Read the body of `resp`, failing once it exceeds `max_size` bytes or `deadline` has passed.
+///
+/// The buffer is preallocated from the `Content-Length` if there is one. Otherwise it starts small
+/// and grows geometrically, and at most one byte past the limit is read.
+fn read_limited(
+    mut resp: reqwest::blocking::Response,
+    max_size: Option<usize>,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, BodyError> {
+    use std::io::Read;
+
+    let capacity = match (resp.content_length(), max_size) {
+        (Some(len), Some(max_size)) if len > max_size as u64 => {
+            return Err(BodyError::TooLarge(max_size))
+        }
+        (Some(len), Some(_)) => len as usize,
+        // Without a limit, don't trust the length for more than the initial buffer.
+        (Some(len), None) => (len as usize).min(INITIAL_BODY_CAPACITY),
+        (None, max_size) => INITIAL_BODY_CAPACITY.min(max_size.unwrap_or(usize::MAX)),
+    };
+    let mut body = Vec::with_capacity(capacity);
+    let mut reader = (&mut resp).take(max_size.map_or(u64::MAX, |max_size| max_size as u64 + 1));
+    match deadline {
+        None => {
+            reader.read_to_end(&mut body).map_err(BodyError::Io)?;
+        }
+        Some(deadline) => {
+            let mut chunk = [0; INITIAL_BODY_CAPACITY];
+            loop {
+                let n = match reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(BodyError::Io(e)),
+                };
+                body.extend_from_slice(&chunk[..n]);
+                if Instant::now() > deadline {
+                    return Err(BodyError::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "body deadline exceeded",
+                    )));
+                }
+            }
+        }
+    }
+    if let Some(max_size) = max_size.filter(|max_size| body.len() > *max_size) {
+        return Err(BodyError::TooLarge(max_size));
+    }
+    Ok(body)
+}
+
+/// Whether a body read failed by running out of time, either past the deadline of
+/// [`read_limited`] or the timeout of the request.
+fn is_timeout(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::TimedOut
+        || e.get_ref()
+            .and_then(|e| e.downcast_ref::<reqwest::Error>())
+            .is_some_and(reqwest::Error::is_timeout)
+}
+
+/// Convert a reqwest error into a [`ResolveError`], redacting `href` from its message.
+fn request_error(e: &reqwest::Error, href: &str) -> ResolveError {
+    if e.is_timeout() {
+        ResolveError::Timeout
+    } else if let Some(status) = e.status() {
+        ResolveError::Status(status.as_u16())
+    } else {
+        ResolveError::Network(crate::redact::redact_error(e, href))
+    }
+}
+
+/// Cached response stored by [`HttpCacheStore`] implementations.
+#[derive(Debug, Clone)]
+pub struct HttpCacheEntry {
+    /// The `ETag` header of the response, used for `If-None-Match`.
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the response, used for `If-Modified-Since`.
+    pub last_modified: Option<String>,
+    /// The content type of the response (e.g. `"image/png"`).
+    pub content_type: Option<String>,
+    /// The raw body bytes of the response.
+    pub body: Arc<Vec<u8>>,
+    /// The entry can be used without revalidation until this time, from `Cache-Control: max-age`.
+    pub fresh_until: Option<SystemTime>,
+}
+
+impl HttpCacheEntry {
+    fn is_fresh(&self, cache_control: &RequestCacheControl, now: SystemTime) -> bool {
+        if cache_control.no_cache() {
+            return false;
+        }
+        let max_stale = cache_control.max_stale().unwrap_or_default();
+        self.fresh_until
+            .is_some_and(|fresh_until| now < fresh_until + max_stale)
+    }
+}
+
+/// Trait for storing and retrieving cached HTTP responses.
+///
+/// Implement this trait to provide a custom cache backend.
+///
+/// Entries are keyed by the cache key computed by [`CachedBlockingReqwestResolver`], which is the
+/// URL, followed by the request headers that were sent with it (if any).
+pub trait HttpCacheStore: Send + Sync {
+    /// Look up a cached entry by its cache key.
+    fn get(&self, key: &str) -> Option<HttpCacheEntry>;
+    /// Store a cache entry for the given cache key.
+    fn put(&self, key: &str, entry: HttpCacheEntry);
+}
+
+/// A shared cache store, so that several resolvers can use the same cache.
+impl<T: HttpCacheStore + ?Sized> HttpCacheStore for Arc<T> {
+    fn get(&self, key: &str) -> Option<HttpCacheEntry> {
+        (**self).get(key)
+    }
+    fn put(&self, key: &str, entry: HttpCacheEntry) {
+        (**self).put(key, entry)
+    }
+}
+
+/// An in-memory cache store backed by a [`HashMap`].
+#[derive(Debug, Default)]
+pub struct MemoryHttpCacheStore {
+    entries: Mutex<HashMap<String, HttpCacheEntry>>,
+}
+
+impl MemoryHttpCacheStore {
+    /// Create a new empty `MemoryHttpCacheStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl HttpCacheStore for MemoryHttpCacheStore {
+    fn get(&self, key: &str) -> Option<HttpCacheEntry> {
+        self.entries.lock().ok()?.get(key).cloned()
+    }
+    fn put(&self, key: &str, entry: HttpCacheEntry) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(key.to_string(), entry);
+        }
+    }
+}
+
+/// Encryption used by [`DiskHttpCacheStore`] to protect cached entries at rest.
+///
+/// This crate does not ship a cipher; implement this trait with the authenticated encryption
+/// scheme and key management required by your policy (e.g. AES-GCM with a key from a KMS).
+pub trait CacheCipher: Send + Sync {
+    /// Encrypt `plaintext`.
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8>;
+    /// Decrypt `ciphertext`, returning `None` if it is invalid or was not encrypted with this key.
+    fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// A cache store that keeps entries as files in a directory.
+///
+/// Each entry is stored as a `<hash>.body` file with the response body and a `<hash>.meta` file
+/// with the cache key and headers, where `<hash>` is derived from the cache key. The file names
+/// have a fixed length, so arbitrarily long URLs (e.g. signed URLs) don't run into path length
+/// limits, and the key stored in the `.meta` file is checked on read, so hash collisions are
+/// cache misses. The hash can be replaced with [`with_file_name`](`Self::with_file_name`).
+///
+/// With [`with_cipher`](`Self::with_cipher`), both files are encrypted, so neither the bodies nor
+/// the URLs and headers are readable on disk.
+#[derive(Clone)]
+pub struct DiskHttpCacheStore {
+    dir: PathBuf,
+    cipher: Option<Arc<dyn CacheCipher>>,
+    file_name: Option<FileNameFn>,
+}
+
+type FileNameFn = Hook<dyn Fn(&str) -> String + Send + Sync>;
+
+impl std::fmt::Debug for DiskHttpCacheStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskHttpCacheStore")
+            .field("dir", &self.dir)
+            .field("encrypted", &self.cipher.is_some())
+            .field("file_name", &self.file_name)
+            .finish()
+    }
+}
+
+impl DiskHttpCacheStore {
+    /// Create a new `DiskHttpCacheStore` storing entries in `dir`.
+    ///
+    /// The directory is created when the first entry is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            cipher: None,
+            file_name: None,
+        }
+    }
+
+    /// Derive the file names of the entries from the cache key with `f`, instead of the default
+    /// 64-bit FNV-1a hash.
+    ///
+    /// `f` must return a valid file name without an extension, and should return names of a
+    /// bounded length for any key. Use a stronger hash if many entries share the directory.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::reqwest_blocking::DiskHttpCacheStore;
+    ///
+    /// let store = DiskHttpCacheStore::new("cache").with_file_name(|key| {
+    ///     use std::hash::{DefaultHasher, Hash, Hasher};
+    ///     let mut hasher = DefaultHasher::new();
+    ///     key.hash(&mut hasher);
+    ///     format!("{:016x}", hasher.finish())
+    /// });
+    /// ```
+    pub fn with_file_name(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.file_name = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Encrypt the stored entries with the given cipher.
+    ///
+    /// Entries that fail to decrypt (e.g. written with another key) are treated as cache misses.
+    pub fn with_cipher(mut self, cipher: impl CacheCipher + 'static) -> Self {
+        self.cipher = Some(Arc::new(cipher));
+        self
+    }
+
+    fn read(&self, path: &std::path::Path) -> Option<Vec<u8>> {
+        let data = std::fs::read(path).ok()?;
+        match &self.cipher {
+            Some(cipher) => cipher.decrypt(&data),
+            None => Some(data),
+        }
+    }
+
+    fn write(&self, path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
+        match &self.cipher {
+            Some(cipher) => std::fs::write(path, cipher.encrypt(data)),
+            None => std::fs::write(path, data),
+        }
+    }
+
+    fn paths(&self, key: &str) -> (PathBuf, PathBuf) {
+        let name = match &self.file_name {
+            Some(f) => f(key),
             None => {
+                // FNV-1a, so that file names are stable across builds.
+                let hash = key.bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+                    (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+                });
+                format!("{hash:016x}")
+            }
+        };
+        (
+            self.dir.join(format!("{name}.meta")),
+            self.dir.join(format!("{name}.body")),
+        )
+    }
+}
+
+impl HttpCacheStore for DiskHttpCacheStore {
+    fn get(&self, key: &str) -> Option<HttpCacheEntry> {
+        let (meta_path, body_path) = self.paths(key);
+        let meta = String::from_utf8(self.read(&meta_path)?).ok()?;
+        let (stored_key, meta) = meta.split_once("\n\n")?;
+        if stored_key != key {
+            return None;
+        }
+        let fields: HashMap<&str, &str> = meta
+            .lines()
+            .filter_map(|line| line.split_once(": "))
+            .collect();
+        let body = self.read(&body_path)?;
+        Some(HttpCacheEntry {
+            etag: fields.get("etag").map(|s| s.to_string()),
+            last_modified: fields.get("last-modified").map(|s| s.to_string()),
+            content_type: fields.get("content-type").map(|s| s.to_string()),
+            body: Arc::new(body),
+            fresh_until: fields
+                .get("fresh-until")
+                .and_then(|s| s.parse().ok())
+                .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+        })
+    }
+    fn put(&self, key: &str, entry: HttpCacheEntry) {
+        let (meta_path, body_path) = self.paths(key);
+        let mut meta = format!("{key}\n\n");
+        let fields = [
+            ("etag", entry.etag),
+            ("last-modified", entry.last_modified),
+            ("content-type", entry.content_type),
+            (
+                "fresh-until",
+                entry
+                    .fresh_until
+                    .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs().to_string()),
+            ),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                meta.push_str(&format!("{name}: {value}\n"));
+            }
+        }
+        let result = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| self.write(&body_path, &entry.body))
+            .and_then(|_| self.write(&meta_path, meta.as_bytes()));
+        if let Err(e) = result {
+            crate::utils::log_warn!(
+                "failed to write cache entry for '{}': {}",
+                crate::redact::redact(key),
+                e
+            );
+        }
+    }
+}
+
+/// Parse the `max-age` of a `Cache-Control` header, or `None` if the response must not be reused
+/// without revalidation.
+fn max_age(cache_control: Option<&str>) -> Option<Duration> {
+    let mut max_age = None;
+    for directive in cache_control?.split(',').map(str::trim) {
+        match directive.split_once('=') {
+            Some((name, value)) if name.eq_ignore_ascii_case("max-age") => {
+                max_age = value
+                    .trim_matches('"')
+                    .parse()
+                    .ok()
+                    .map(Duration::from_secs);
+            }
+            _ if directive.eq_ignore_ascii_case("no-cache") => return None,
+            _ => {}
+        }
+    }
+    max_age
+}
+
+/// Whether the response varies on something that can't be part of the cache key.
+fn varies_on_everything(vary: Option<&str>) -> bool {
+    vary.is_some_and(|vary| vary.split(',').any(|name| name.trim() == "*"))
+}
+
+fn is_no_store(cache_control: Option<&str>) -> bool {
+    cache_control.is_some_and(|cc| {
+        cc.split(',')
+            .any(|directive| directive.trim().eq_ignore_ascii_case("no-store"))
+    })
+}
+
+/// Kind of a failed fetch, used to configure negative caching with
+/// [`CachedBlockingReqwestResolver::with_negative_ttl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// The server responded with 404 Not Found or 410 Gone.
+    NotFound,
+    /// The server responded with 429 Too Many Requests.
+    RateLimited,
+    /// The server responded with another unsuccessful status.
+    Status,
+    /// The request timed out.
+    Timeout,
+    /// The connection could not be established, including DNS resolution failures.
+    Connect,
+    /// The server responded without a body, e.g. with 204 No Content.
+    Empty,
+    /// Any other error, e.g. while reading the body.
+    Other,
+}
+
+impl FailureKind {
+    fn from_error(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_connect() {
+            Self::Connect
+        } else if let Some(status) = e.status() {
+            Self::from_status(status)
+        } else {
+            Self::Other
+        }
+    }
+
+    fn from_status(status: reqwest::StatusCode) -> Self {
+        match status {
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Self::NotFound,
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            _ => Self::Status,
+        }
+    }
+}
+
+/// How often a process waiting for another one to fetch an `href` checks the cache.
+const FETCH_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Check if the cache entry `latest` was stored after `seen` was read.
+fn is_updated(latest: &HttpCacheEntry, seen: Option<&HttpCacheEntry>) -> bool {
+    seen.is_none_or(|seen| {
+        latest.fresh_until != seen.fresh_until
+            || latest.etag != seen.etag
+            || latest.last_modified != seen.last_modified
+            || latest.body != seen.body
+    })
+}
+
+/// A failed fetch, with the delay requested by the server before retrying.
+#[derive(Debug)]
+struct Failure {
+    kind: FailureKind,
+    retry_after: Option<Duration>,
+}
+
+impl From<FailureKind> for Failure {
+    fn from(kind: FailureKind) -> Self {
+        Self {
+            kind,
+            retry_after: None,
+        }
+    }
+}
+
+/// Report of [`CachedBlockingReqwestResolver::warm`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarmReport {
+    /// The number of `href`s fetched or revalidated into the cache.
+    pub fetched: usize,
+    /// The number of `href`s skipped because their cached entry is still fresh.
+    pub fresh: usize,
+    /// The `href`s that could not be fetched, including the ones still rate limited after all
+    /// retries.
+    pub failed: Vec<String>,
+}
+
+/// How many times [`CachedBlockingReqwestResolver::warm`] retries a rate limited `href`.
+const WARM_MAX_RETRIES: u32 = 5;
+const WARM_MIN_BACKOFF: Duration = Duration::from_millis(100);
+const WARM_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A blocking resolver that caches responses according to HTTP caching headers.
+///
+/// Responses are stored in the provided [`HttpCacheStore`]. While an entry is fresh according to
+/// `Cache-Control: max-age`, it is used without a request. Once it is stale, a conditional request
+/// is made with `If-None-Match` / `If-Modified-Since`, and the cached body is reused if the server
+/// responds with 304 Not Modified. Responses with `Cache-Control: no-store` or `Vary: *` are never
+/// cached.
+///
+/// Request headers added with [`with_request_header`](`Self::with_request_header`) are part of the
+/// cache key, so resolvers negotiating different content (e.g. with different `Accept` headers)
+/// can share a cache store without serving each other's variants. The key can be customized
+/// entirely with [`with_cache_key`](`Self::with_cache_key`).
+///
+/// Unlike [`HttpCacheReqwestResolver`](`crate::reqwest_http_cache::HttpCacheReqwestResolver`), this
+/// does not need a [`tokio`] runtime.
+///
+/// ```
+/// use usvg_remote_resolvers::{
+///     HrefStringResolver,
+///     reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore},
+/// };
+///
+/// let resolver = BlockingReqwestResolver::default().with_cache(MemoryHttpCacheStore::new());
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CachedBlockingReqwestResolver<C> {
+    resolver: BlockingReqwestResolver,
+    cache: C,
+    request_headers: reqwest::header::HeaderMap,
+    cache_key: Option<CacheKeyFn>,
+    negative_ttls: HashMap<FailureKind, Duration>,
+    failures: Arc<Mutex<HashMap<String, (FailureKind, SystemTime)>>>,
+    clock: Arc<dyn Clock>,
+    normalizer: Option<Arc<dyn UrlNormalizer>>,
+    fetch_lock: Option<(Arc<dyn FetchLock>, Duration)>,
+}
+
+type CacheKeyFn = Hook<dyn Fn(&str, &reqwest::header::HeaderMap) -> String + Send + Sync>;
+
+impl BlockingReqwestResolver {
+    /// Add HTTP caching to this resolver using the given cache store.
+    pub fn with_cache<C: HttpCacheStore>(self, cache: C) -> CachedBlockingReqwestResolver<C> {
+        CachedBlockingReqwestResolver {
+            resolver: self,
+            cache,
+            request_headers: reqwest::header::HeaderMap::new(),
+            cache_key: None,
+            negative_ttls: HashMap::new(),
+            failures: Arc::default(),
+            clock: Arc::new(SystemClock),
+            normalizer: None,
+            fetch_lock: None,
+        }
+    }
+}
+
+impl<C: HttpCacheStore> CachedBlockingReqwestResolver<C> {
+    /// Get the underlying [`Client`](`reqwest::blocking::Client`) of this resolver.
+    pub fn client(&self) -> &reqwest::blocking::Client {
+        self.resolver.client()
+    }
+
+    /// Get a reference to the cache store.
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    /// Send the given header with every request, and make it part of the cache key.
+    ///
+    /// ```
+    /// use reqwest::header::{ACCEPT, HeaderValue};
+    /// use usvg_remote_resolvers::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+    ///
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_cache(MemoryHttpCacheStore::new())
+    ///     .with_request_header(ACCEPT, HeaderValue::from_static("image/png"));
+    /// assert_eq!(
+    ///     resolver.cache_key("https://example.com/logo"),
+    ///     "https://example.com/logo\naccept: image/png",
+    /// );
+    /// ```
+    pub fn with_request_header(
+        mut self,
+        name: reqwest::header::HeaderName,
+        value: reqwest::header::HeaderValue,
+    ) -> Self {
+        self.request_headers.append(name, value);
+        self
+    }
+
+    /// Compute the cache key with `f`, from the URL and the request headers of this resolver.
+    pub fn with_cache_key(
+        mut self,
+        f: impl Fn(&str, &reqwest::header::HeaderMap) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.cache_key = Some(Hook(Arc::new(f)));
+        self
+    }
+
+    /// Remember failures of the given kind for `ttl`, so that the `href` is not requested again
+    /// until it expires.
+    ///
+    /// By default, failures are not remembered. Negative entries are kept in memory and are shared
+    /// between clones of this resolver.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use usvg_remote_resolvers::reqwest_blocking::{
+    ///     BlockingReqwestResolver, FailureKind, MemoryHttpCacheStore,
+    /// };
+    ///
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_cache(MemoryHttpCacheStore::new())
+    ///     .with_negative_ttl(FailureKind::NotFound, Duration::from_secs(300))
+    ///     .with_negative_ttl(FailureKind::Timeout, Duration::from_secs(30));
+    /// ```
+    pub fn with_negative_ttl(mut self, kind: FailureKind, ttl: Duration) -> Self {
+        self.negative_ttls.insert(kind, ttl);
+        self
+    }
+
+    /// Revalidate the cached entries of `hrefs` before a batch render.
+    ///
+    /// Each cached entry is revalidated with `If-None-Match`/`If-Modified-Since`, even if it is
+    /// still fresh, so only changed bodies are downloaded. `hrefs` without a cached entry are
+    /// skipped, and entries that fail to revalidate are kept as they are.
+    ///
+    /// Returns the number of entries whose body changed. A `200 OK` with the same bytes as the
+    /// cached body doesn't count as a change. Decoded images of changed entries kept by a
+    /// [`CachedResolver`](crate::decoded_cache::CachedResolver) in front of this resolver are not
+    /// refreshed; remove them from its [`store`](crate::decoded_cache::CachedResolver::store).
+    pub fn revalidate<'h>(&self, hrefs: impl IntoIterator<Item = &'h str>) -> usize {
+        let mut changed = 0;
+        for href in hrefs {
+            let key = self.cache_key(href);
+            let Some(cached) = self.cache.get(&key) else {
+                continue;
+            };
+            let body = cached.body.clone();
+            if let Ok((entry, _)) = self.fetch(href, &key, Some(cached)) {
+                if entry.body != body {
+                    changed += 1;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Fetch `hrefs` into the cache ahead of a batch render.
+    ///
+    /// `hrefs` whose cached entry is still fresh are skipped. Requests are paced per host: when a
+    /// host responds with 429 Too Many Requests, the warmer waits for its `Retry-After` (or twice
+    /// the previous delay, at least 100ms) before the next request to that host and retries the
+    /// `href` up to five times. The delay decays again as requests to the host succeed. Waiting
+    /// uses the clock set with [`with_clock`](Self::with_clock).
+    ///
+    /// ```no_run
+    /// use usvg_remote_resolvers::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+    ///
+    /// let resolver = BlockingReqwestResolver::default().with_cache(MemoryHttpCacheStore::new());
+    /// let report = resolver.warm([
+    ///     "https://example.com/a.png",
+    ///     "https://example.com/b.png",
+    /// ]);
+    /// assert!(report.failed.is_empty());
+    /// ```
+    pub fn warm<'h>(&self, hrefs: impl IntoIterator<Item = &'h str>) -> WarmReport {
+        let mut report = WarmReport::default();
+        let mut delays: HashMap<String, Duration> = HashMap::new();
+        let cache_control = RequestCacheControl::current();
+        for href in hrefs {
+            let key = self.cache_key(href);
+            let cached = self.cache.get(&key);
+            if cached
+                .as_ref()
+                .is_some_and(|cached| cached.is_fresh(&cache_control, self.clock.now()))
+            {
+                report.fresh += 1;
+                continue;
+            }
+            let host = url::Url::parse(href)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+                .unwrap_or_default();
+            let mut retries = 0;
+            loop {
+                let delay = delays.get(&host).copied().unwrap_or_default();
+                if !delay.is_zero() {
+                    self.clock.sleep(delay);
+                }
+                match self.fetch(href, &key, cached.clone()) {
+                    Ok(_) => {
+                        let delay = delay * 3 / 4;
+                        if delay < WARM_MIN_BACKOFF {
+                            delays.remove(&host);
+                        } else {
+                            delays.insert(host, delay);
+                        }
+                        report.fetched += 1;
+                        break;
+                    }
+                    Err(failure)
+                        if failure.kind == FailureKind::RateLimited
+                            && retries < WARM_MAX_RETRIES =>
+                    {
+                        retries += 1;
+                        let delay = (delay * 2)
+                            .max(WARM_MIN_BACKOFF)
+                            .max(failure.retry_after.unwrap_or_default())
+                            .min(WARM_MAX_BACKOFF);
+                        delays.insert(host.clone(), delay);
+                    }
+                    Err(failure) => {
+                        self.remember_failure(&key, failure.kind);
+                        report.failed.push(href.to_string());
+                        break;
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Use `clock` for freshness and negative caching, instead of the system clock.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use usvg_remote_resolvers::clock::MockClock;
+    /// use usvg_remote_resolvers::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+    ///
+    /// let clock = MockClock::default();
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_cache(MemoryHttpCacheStore::new())
+    ///     .with_clock(clock.clone());
+    /// // Entries cached with `max-age=60` are stale after this.
+    /// clock.advance(Duration::from_secs(61));
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Normalize `href`s with `normalizer` before computing their cache key, so `href`s that are
+    /// equal after normalization share a cache entry.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::normalize::DefaultNormalizer;
+    /// use usvg_remote_resolvers::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+    ///
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_cache(MemoryHttpCacheStore::new())
+    ///     .with_normalizer(DefaultNormalizer::new());
+    /// assert_eq!(
+    ///     resolver.cache_key("https://example.com/logo.png?utm_source=mail"),
+    ///     "https://example.com/logo.png",
+    /// );
+    /// ```
+    pub fn with_normalizer(mut self, normalizer: impl UrlNormalizer + 'static) -> Self {
+        self.normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Take a lock of `lock` before fetching an `href`, so among the processes sharing the cache
+    /// store, one fetches it while the others wait for it to be stored in the cache.
+    ///
+    /// A waiting process uses the entry of the `href` once it is stored or updated, or fetches it
+    /// itself once it gets the lock, e.g. if the response couldn't be cached. Locks expire after
+    /// `timeout`, and processes stop waiting after it.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use usvg_remote_resolvers::fetch_lock::MemoryFetchLock;
+    /// use usvg_remote_resolvers::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+    ///
+    /// let resolver = BlockingReqwestResolver::default()
+    ///     .with_cache(MemoryHttpCacheStore::new())
+    ///     .with_fetch_lock(MemoryFetchLock::new(), Duration::from_secs(30));
+    /// ```
+    pub fn with_fetch_lock(mut self, lock: impl FetchLock + 'static, timeout: Duration) -> Self {
+        self.fetch_lock = Some((Arc::new(lock), timeout));
+        self
+    }
+
+    /// Get the key used to store the response for `href` in the cache.
+    pub fn cache_key(&self, href: &str) -> String {
+        let href = crate::normalize::normalize(self.normalizer.as_ref(), href);
+        if let Some(f) = &self.cache_key {
+            return f(&href, &self.request_headers);
+        }
+        let mut headers: Vec<_> = self
+            .request_headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes())))
+            .collect();
+        headers.sort();
+        std::iter::once(href.to_string())
+            .chain(headers)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn fetch(
+        &self,
+        href: &str,
+        key: &str,
+        cached: Option<HttpCacheEntry>,
+    ) -> Result<(HttpCacheEntry, Option<ResponseMetadata>), Failure> {
+        let _redirects = RedirectGuard::new(self.resolver.redirects.as_deref(), href);
+        let mut req = self
+            .resolver
+            .client
+            .get(href)
+            .headers(self.request_headers.clone());
+        if let Some(value) = RequestCacheControl::current().header_value() {
+            req = req.header(reqwest::header::CACHE_CONTROL, value);
+        }
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                req = req.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = match self.resolver.send(req) {
+            Ok(resp) => resp,
+            Err(e) => {
                 crate::utils::log_warn!(
-                    "unsupported image type for '{}' (content-type: {:?})",
-                    href,
-                    content_type
+                    "failed to fetch '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
                 );
-                return None;
+                return Err(FailureKind::from_error(&e).into());
             }
         };
-        let body = match resp.bytes() {
-            Ok(b) => b.to_vec(),
-            Err(e) => {
-                crate::utils::log_warn!("failed to read response body for '{}': {}", href, e);
-                return None;
+        if !self
+            .resolver
+            .target
+            .accepts_final_url(href, resp.url().as_str())
+        {
+            return Err(FailureKind::Other.into());
+        }
+        let metadata = self.resolver.response_metadata(href, &resp);
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        };
+        let cache_control = header(reqwest::header::CACHE_CONTROL);
+        let vary = header(reqwest::header::VARY);
+        let fresh_until =
+            max_age(cache_control.as_deref()).map(|max_age| self.clock.now() + max_age);
+
+        if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+            let Some(cached) = cached else {
+                crate::utils::log_warn!(
+                    "unexpected 304 response for '{}'",
+                    crate::redact::redact(href)
+                );
+                return Err(FailureKind::Status.into());
+            };
+            let entry = HttpCacheEntry {
+                fresh_until,
+                ..cached
+            };
+            self.cache.put(key, entry.clone());
+            crate::events::record_cache_hit();
+            return Ok((entry, metadata));
+        }
+        if !resp.status().is_success() {
+            crate::utils::log_warn!(
+                "failed to fetch '{}': status {}",
+                crate::redact::redact(href),
+                resp.status()
+            );
+            return Err(Failure {
+                kind: FailureKind::from_status(resp.status()),
+                retry_after: header(reqwest::header::RETRY_AFTER)
+                    .and_then(|s| s.trim().parse().ok())
+                    .map(Duration::from_secs),
+            });
+        }
+
+        let entry = HttpCacheEntry {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+            content_type: self
+                .resolver
+                .content_type(href, header(reqwest::header::CONTENT_TYPE).as_deref())
+                .map(str::to_string),
+            fresh_until,
+            body: match self.resolver.read_body(resp, href) {
+                Ok(body) => Arc::new(body),
+                Err(BodyError::Io(e)) if is_timeout(&e) => return Err(FailureKind::Timeout.into()),
+                Err(_) => return Err(FailureKind::Other.into()),
+            },
+        };
+        if crate::utils::is_empty_body(href, &entry.body) {
+            return Err(FailureKind::Empty.into());
+        }
+        // Detect the type as decoding does, so SVGs served with a generic type are verified too.
+        let is_svg = matches!(
+            ImageKindTypes::detect(entry.content_type.as_deref(), href, &entry.body),
+            Some(ImageKindTypes::Svg)
+        );
+        if is_svg && self.resolver.verify_svg(href, &entry.body).is_err() {
+            return Err(FailureKind::Other.into());
+        }
+        self.resolver
+            .call_body_hook(href, entry.content_type.as_deref(), &entry.body);
+        let reusable =
+            entry.etag.is_some() || entry.last_modified.is_some() || fresh_until.is_some();
+        if reusable
+            && !is_no_store(cache_control.as_deref())
+            && !varies_on_everything(vary.as_deref())
+        {
+            self.cache.put(key, entry.clone());
+        }
+        Ok((entry, metadata))
+    }
+
+    /// Fetch `href` like [`fetch`](Self::fetch), or wait for another process holding its lock to
+    /// store it in the cache, if a [`FetchLock`] is set.
+    fn fetch_locked(
+        &self,
+        href: &str,
+        key: &str,
+        cached: Option<HttpCacheEntry>,
+    ) -> Result<(HttpCacheEntry, Option<ResponseMetadata>), Failure> {
+        let Some((lock, timeout)) = &self.fetch_lock else {
+            return self.fetch(href, key, cached);
+        };
+        let deadline = self.clock.now() + *timeout;
+        loop {
+            let locked = lock.try_lock(key, *timeout);
+            let latest = self.cache.get(key).filter(|e| !e.body.is_empty());
+            if let Some(latest) = latest.filter(|latest| is_updated(latest, cached.as_ref())) {
+                if locked {
+                    lock.unlock(key);
+                }
+                crate::events::record_cache_hit();
+                return Ok((latest, None));
+            }
+            if locked {
+                let result = self.fetch(href, key, cached);
+                lock.unlock(key);
+                return result;
+            }
+            if self.clock.now() >= deadline {
+                crate::utils::log_warn!(
+                    "timed out waiting for another fetch of '{}'",
+                    crate::redact::redact(href)
+                );
+                return self.fetch(href, key, cached);
+            }
+            self.clock.sleep(FETCH_LOCK_POLL_INTERVAL);
+        }
+    }
+
+    /// Check if `key` failed recently and is still negatively cached.
+    fn is_negatively_cached(&self, key: &str) -> bool {
+        let Ok(mut failures) = self.failures.lock() else {
+            return false;
+        };
+        match failures.get(key) {
+            Some((_, until)) if self.clock.now() < *until => true,
+            Some(_) => {
+                failures.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn remember_failure(&self, key: &str, kind: FailureKind) {
+        let Some(ttl) = self.negative_ttls.get(&kind) else {
+            return;
+        };
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.insert(key.to_string(), (kind, self.clock.now() + *ttl));
+        }
+    }
+}
+
+impl<C: HttpCacheStore> HrefStringResolver<'_> for CachedBlockingReqwestResolver<C> {
+    fn is_target(&self, href: &str) -> bool {
+        self.resolver.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        let outcome = if !self.is_target(trace.href()) {
+            crate::explain::Outcome::NotTarget
+        } else if !RequestCacheControl::current().no_cache()
+            && self.is_negatively_cached(&self.cache_key(trace.href()))
+        {
+            crate::explain::Outcome::Denied("failed recently".to_string())
+        } else {
+            crate::explain::Outcome::Matched
+        };
+        trace.push::<Self>(outcome);
+    }
+    fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
+        let key = self.cache_key(href);
+        let cache_control = RequestCacheControl::current();
+        if !cache_control.no_cache() && self.is_negatively_cached(&key) {
+            crate::utils::log_warn!("skipping recently failed '{}'", crate::redact::redact(href));
+            return None;
+        }
+        // Empty entries stored by older versions are never images, so they are fetched again.
+        let (entry, metadata) = match self.cache.get(&key).filter(|c| !c.body.is_empty()) {
+            Some(cached) if cached.is_fresh(&cache_control, self.clock.now()) => {
+                crate::events::record_cache_hit();
+                (cached, None)
             }
+            cached => match self.fetch_locked(href, &key, cached) {
+                Ok(fetched) => fetched,
+                Err(failure) => {
+                    self.remember_failure(&key, failure.kind);
+                    return None;
+                }
+            },
         };
-        image_type.into_image_kind(body.into(), options)
+        let image_type =
+            match ImageKindTypes::detect(entry.content_type.as_deref(), href, &entry.body) {
+                Some(t) => t,
+                None => {
+                    crate::utils::log_warn!(
+                        "unsupported image type for '{}' (content-type: {:?})",
+                        crate::redact::redact(href),
+                        entry.content_type
+                    );
+                    return None;
+                }
+            };
+        let kind = image_type.into_image_kind_from(entry.body, href, options)?;
+        self.resolver.call_response_hook(metadata, &kind);
+        Some(kind)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::AddressFamily;
     use usvg::Options;
 
+    #[test]
+    fn adaptive_timeout() {
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+        let url = format!("{}/gray.png", s.url());
+
+        let resolve = |base, per_megabyte| {
+            let resolver = BlockingReqwestResolver::default().with_adaptive_timeout(
+                AdaptiveTimeout::new(base, per_megabyte, Duration::from_secs(10)),
+            );
+            crate::error::TryHrefStringResolver::try_get_image_kind(
+                &resolver,
+                &url,
+                &Options::default(),
+            )
+        };
+        assert!(resolve(Duration::from_secs(1), Duration::from_secs(1)).is_ok());
+        assert!(matches!(
+            resolve(Duration::ZERO, Duration::ZERO),
+            Err(ResolveError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn content_type_override() {
+        let mut s = mockito::Server::new();
+        for path in ["/legacy/gray", "/other/gray"] {
+            s.mock("GET", path)
+                .with_status(200)
+                .with_header("content-type", "text/plain")
+                .with_body(include_bytes!("../test_data/gray.png"))
+                .create();
+        }
+
+        let resolver = BlockingReqwestResolver::default().with_content_type_override(
+            TargetFilter::new().with_predicate(|href| href.contains("/legacy/")),
+            "image/png",
+        );
+        let options = Options::default();
+        assert!(matches!(
+            resolver.get_image_kind(&format!("{}/legacy/gray", s.url()), &options),
+            Some(usvg::ImageKind::PNG(_))
+        ));
+        assert!(resolver
+            .get_image_kind(&format!("{}/other/gray", s.url()), &options)
+            .is_none());
+    }
+
+    #[test]
+    fn svg_verifier() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+        let mut s = mockito::Server::new();
+        for name in ["signed", "forged", "unsigned"] {
+            s.mock("GET", format!("/{name}.svg").as_str())
+                .with_status(200)
+                .with_header("content-type", "image/svg+xml")
+                .with_body(svg)
+                .create();
+        }
+        s.mock("GET", "/signed.svg.sig").with_body("good").create();
+        s.mock("GET", "/forged.svg.sig").with_body("bad").create();
+        s.mock("GET", "/unsigned.svg.sig").with_status(404).create();
+        s.mock("GET", "/generic")
+            .with_status(200)
+            .with_header("content-type", "application/octet-stream")
+            .with_body(svg)
+            .create();
+        s.mock("GET", "/generic.sig").with_status(404).create();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let resolver = BlockingReqwestResolver::default()
+            .with_svg_verifier(|_, body, signature| !body.is_empty() && signature == b"good");
+        let resolve = |name: &str| {
+            crate::error::TryHrefStringResolver::try_get_image_kind(
+                &resolver,
+                &format!("{}/{name}", s.url()),
+                &Options::default(),
+            )
+        };
+        assert!(matches!(resolve("signed.svg"), Ok(usvg::ImageKind::SVG(_))));
+        assert!(matches!(
+            resolve("forged.svg"),
+            Err(ResolveError::Policy(_))
+        ));
+        assert!(matches!(
+            resolve("unsigned.svg"),
+            Err(ResolveError::Policy(_))
+        ));
+        assert!(matches!(resolve("gray.png"), Ok(usvg::ImageKind::PNG(_))));
+        assert!(matches!(resolve("generic"), Err(ResolveError::Policy(_))));
+
+        let resolver = resolver.with_cache(MemoryHttpCacheStore::new());
+        let options = Options::default();
+        assert!(resolver
+            .get_image_kind(&format!("{}/generic", s.url()), &options)
+            .is_none());
+        assert!(resolver
+            .get_image_kind(&format!("{}/signed.svg", s.url()), &options)
+            .is_some());
+    }
+
     #[test]
     fn reqwest_resolver() {
         let resolver = BlockingReqwestResolver::default();
@@ -119,4 +1747,814 @@ mod tests {
             resvg::tiny_skia::PremultipliedColorU8::from_rgba(0, 127, 255, 255).unwrap()
         );
     }
+
+    fn gray_svg(url: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <image href="{url}/gray.png" />
+            </svg>"#
+        )
+    }
+
+    #[test]
+    fn cached_resolver_fresh() {
+        let resolver = BlockingReqwestResolver::default().with_cache(MemoryHttpCacheStore::new());
+        let mut options = Options::default();
+        options.image_href_resolver.resolve_string = resolver.into_fn();
+
+        let mut s = mockito::Server::new();
+        let mock = s
+            .mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+
+        for _ in 0..2 {
+            let tree = usvg::Tree::from_str(&gray_svg(&s.url()), &options).unwrap();
+            assert!(tree.root().has_children());
+        }
+        mock.assert();
+    }
+
+    #[test]
+    fn cached_resolver_request_cache_control() {
+        let resolver = BlockingReqwestResolver::default().with_cache(MemoryHttpCacheStore::new());
+        let mut options = Options::default();
+        options.image_href_resolver.resolve_string = resolver.into_fn();
+
+        let mut s = mockito::Server::new();
+        let plain = s
+            .mock("GET", "/gray.png")
+            .match_header("cache-control", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=0")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+        let forced = s
+            .mock("GET", "/gray.png")
+            .match_header("cache-control", "no-cache")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=0")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+
+        usvg::Tree::from_str(&gray_svg(&s.url()), &options).unwrap();
+        RequestCacheControl::new()
+            .with_max_stale(Duration::from_secs(3600))
+            .scope(|| usvg::Tree::from_str(&gray_svg(&s.url()), &options).unwrap());
+        RequestCacheControl::new()
+            .with_no_cache()
+            .scope(|| usvg::Tree::from_str(&gray_svg(&s.url()), &options).unwrap());
+        plain.assert();
+        forced.assert();
+    }
+
+    #[test]
+    fn cached_resolver_revalidate_pass() {
+        let resolver = BlockingReqwestResolver::default().with_cache(MemoryHttpCacheStore::new());
+        let mut s = mockito::Server::new();
+        let url = format!("{}/gray.png", s.url());
+        let full = s
+            .mock("GET", "/gray.png")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=3600")
+            .with_header("etag", "\"v1\"")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+        let not_modified = s
+            .mock("GET", "/gray.png")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(304)
+            .expect(1)
+            .create();
+
+        assert_eq!(resolver.revalidate([url.as_str()]), 0);
+        assert!(resolver.get_image_kind(&url, &Options::default()).is_some());
+        assert_eq!(resolver.revalidate([url.as_str()]), 0);
+        full.assert();
+        not_modified.assert();
+    }
+
+    #[test]
+    fn revalidate_ignores_identical_bodies() {
+        let resolver = BlockingReqwestResolver::default().with_cache(MemoryHttpCacheStore::new());
+        let mut s = mockito::Server::new();
+        let url = format!("{}/gray.png", s.url());
+        let gray = include_bytes!("../test_data/gray.png");
+        let full = s
+            .mock("GET", "/gray.png")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("etag", "\"v1\"")
+            .with_body(gray)
+            .expect(1)
+            .create();
+        let same = s
+            .mock("GET", "/gray.png")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("etag", "\"v2\"")
+            .with_body(gray)
+            .expect(1)
+            .create();
+
+        assert!(resolver.get_image_kind(&url, &Options::default()).is_some());
+        assert_eq!(resolver.revalidate([url.as_str()]), 0);
+        full.assert();
+        same.assert();
+    }
+
+    #[test]
+    fn cached_resolver_revalidate() {
+        let dir = std::env::temp_dir().join(format!(
+            "usvg-remote-resolvers-http-cache-{}",
+            std::process::id()
+        ));
+        let resolver = BlockingReqwestResolver::default().with_cache(DiskHttpCacheStore::new(&dir));
+        let mut options = Options::default();
+        options.image_href_resolver.resolve_string = resolver.into_fn();
+
+        let mut s = mockito::Server::new();
+        let not_modified = s
+            .mock("GET", "/gray.png")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(304)
+            .expect(1)
+            .create();
+        let full = s
+            .mock("GET", "/gray.png")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("etag", "\"v1\"")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+
+        for _ in 0..2 {
+            let tree = usvg::Tree::from_str(&gray_svg(&s.url()), &options).unwrap();
+            assert!(tree.root().has_children());
+        }
+        full.assert();
+        not_modified.assert();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn disk_cache_long_keys() {
+        let dir = std::env::temp_dir().join(format!(
+            "usvg-remote-resolvers-long-keys-{}",
+            std::process::id()
+        ));
+        let key = format!("https://example.com/a.png?signature={}", "x".repeat(8192));
+        let entry = HttpCacheEntry {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            content_type: Some("image/png".to_string()),
+            fresh_until: None,
+            body: Arc::new(vec![1, 2, 3]),
+        };
+
+        let store = DiskHttpCacheStore::new(&dir);
+        store.put(&key, entry.clone());
+        assert_eq!(store.get(&key).unwrap().body, entry.body);
+
+        let store = DiskHttpCacheStore::new(&dir).with_file_name(|key| key.len().to_string());
+        store.put(&key, entry.clone());
+        assert!(dir.join(format!("{}.body", key.len())).exists());
+        assert_eq!(store.get(&key).unwrap().body, entry.body);
+        assert!(store.get(&format!("{key}y")).is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn cached_resolver_request_headers_in_key() {
+        use reqwest::header::{HeaderValue, ACCEPT};
+
+        let cache = Arc::new(MemoryHttpCacheStore::new());
+        let mut s = mockito::Server::new();
+        let png = s
+            .mock("GET", "/logo")
+            .match_header("accept", "image/png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=3600")
+            .with_header("vary", "accept")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(2)
+            .create();
+
+        let href = format!("{}/logo", s.url());
+        for _ in 0..2 {
+            let png_resolver = BlockingReqwestResolver::default()
+                .with_cache(cache.clone())
+                .with_request_header(ACCEPT, HeaderValue::from_static("image/png"));
+            let other_resolver = BlockingReqwestResolver::default()
+                .with_cache(cache.clone())
+                .with_request_header(ACCEPT, HeaderValue::from_static("image/png"))
+                .with_request_header(
+                    reqwest::header::ACCEPT_LANGUAGE,
+                    HeaderValue::from_static("ja"),
+                );
+            assert!(png_resolver
+                .get_image_kind(&href, &Options::default())
+                .is_some());
+            assert!(other_resolver
+                .get_image_kind(&href, &Options::default())
+                .is_some());
+        }
+        png.assert();
+    }
+
+    #[test]
+    fn cached_resolver_negative_cache() {
+        let clock = crate::clock::MockClock::default();
+        let resolver = BlockingReqwestResolver::default()
+            .with_cache(MemoryHttpCacheStore::new())
+            .with_negative_ttl(FailureKind::NotFound, Duration::from_secs(60))
+            .with_clock(clock.clone());
+
+        let mut s = mockito::Server::new();
+        let missing = s
+            .mock("GET", "/missing.png")
+            .with_status(404)
+            .expect(2)
+            .create();
+        let error = s
+            .mock("GET", "/error.png")
+            .with_status(500)
+            .expect(2)
+            .create();
+
+        for _ in 0..2 {
+            let options = Options::default();
+            let missing_href = format!("{}/missing.png", s.url());
+            let error_href = format!("{}/error.png", s.url());
+            assert!(resolver.get_image_kind(&missing_href, &options).is_none());
+            assert!(resolver.get_image_kind(&error_href, &options).is_none());
+        }
+        clock.advance(Duration::from_secs(61));
+        let missing_href = format!("{}/missing.png", s.url());
+        assert!(resolver
+            .get_image_kind(&missing_href, &Options::default())
+            .is_none());
+        missing.assert();
+        error.assert();
+    }
+
+    #[test]
+    fn disk_cache_store_cipher() {
+        struct XorCipher(u8);
+
+        impl CacheCipher for XorCipher {
+            fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+                plaintext.iter().map(|b| b ^ self.0).collect()
+            }
+            fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+                Some(self.encrypt(ciphertext))
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "usvg-remote-resolvers-http-cache-cipher-{}",
+            std::process::id()
+        ));
+        let key = "https://example.com/secret.png";
+        let store = DiskHttpCacheStore::new(&dir).with_cipher(XorCipher(0x5a));
+        store.put(
+            key,
+            HttpCacheEntry {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+                content_type: Some("image/png".to_string()),
+                body: Arc::new(b"body".to_vec()),
+                fresh_until: None,
+            },
+        );
+
+        let entry = store.get(key).unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(entry.body.as_slice(), b"body");
+        for file in std::fs::read_dir(&dir).unwrap() {
+            let data = std::fs::read(file.unwrap().path()).unwrap();
+            assert!(!String::from_utf8_lossy(&data).contains("example.com"));
+            assert_ne!(data, b"body");
+        }
+        assert!(DiskHttpCacheStore::new(&dir)
+            .with_cipher(XorCipher(0x33))
+            .get(key)
+            .is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn from_config() {
+        let config = ClientConfig::new()
+            .with_pool_max_idle_per_host(0)
+            .with_pool_idle_timeout(None)
+            .with_tcp_keepalive(Duration::from_secs(30))
+            .with_address_family(AddressFamily::PreferIpv4);
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let resolver = BlockingReqwestResolver::from_config(&config).unwrap();
+        let url = s.url().replace("127.0.0.1", "localhost");
+        assert!(resolver
+            .get_image_kind(&format!("{url}/gray.png"), &Options::default())
+            .is_some());
+    }
+
+    #[test]
+    fn dns_pins() {
+        let pins = crate::client::DnsPins::new();
+        let config = ClientConfig::new()
+            .with_address_family(AddressFamily::PreferIpv4)
+            .with_dns_pins(pins.clone());
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(2)
+            .create();
+
+        let resolver = BlockingReqwestResolver::from_config(&config).unwrap();
+        let url = s.url().replace("127.0.0.1", "localhost");
+        for _ in 0..2 {
+            assert!(resolver
+                .get_image_kind(&format!("{url}/gray.png"), &Options::default())
+                .is_some());
+        }
+        assert_eq!(pins.get("localhost"), Some([127, 0, 0, 1].into()));
+        pins.clear();
+        assert_eq!(pins.get("localhost"), None);
+    }
+
+    #[test]
+    fn try_resolver_errors() {
+        use crate::error::TryHrefStringResolver;
+
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/page")
+            .with_status(200)
+            .with_header("content-type", "text/html")
+            .create();
+
+        let resolver = BlockingReqwestResolver::default();
+        assert_eq!(
+            resolver
+                .try_get_image_kind(&format!("{}/page", s.url()), &Options::default())
+                .unwrap_err(),
+            ResolveError::UnsupportedType(Some("text/html".to_string()))
+        );
+
+        let gray = include_bytes!("../test_data/gray.png");
+        s.mock("GET", "/missing.png")
+            .with_status(404)
+            .with_header("content-type", "image/png")
+            .with_body(gray)
+            .create();
+        s.mock("GET", "/unavailable.png")
+            .with_status(503)
+            .with_header("content-type", "application/octet-stream")
+            .with_body(gray)
+            .create();
+        for (path, status) in [("missing.png", 404), ("unavailable.png", 503)] {
+            let error = resolver
+                .try_get_image_kind(&format!("{}/{path}", s.url()), &Options::default())
+                .unwrap_err();
+            assert_eq!(error, ResolveError::Status(status));
+            assert_eq!(
+                crate::retry::RetryPolicy::is_retryable(&error),
+                status == 503
+            );
+        }
+    }
+
+    #[test]
+    fn max_body_size() {
+        use crate::error::TryHrefStringResolver;
+
+        let body = include_bytes!("../test_data/gray.png");
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/chunked.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| {
+                for chunk in body.chunks(100) {
+                    w.write_all(chunk)?;
+                }
+                Ok(())
+            })
+            .create();
+        s.mock("GET", "/sized.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(body)
+            .create();
+
+        let options = Options::default();
+        let fits = BlockingReqwestResolver::default().with_max_body_size(body.len());
+        let too_small = BlockingReqwestResolver::default().with_max_body_size(body.len() - 1);
+        for path in ["chunked.png", "sized.png"] {
+            let href = format!("{}/{path}", s.url());
+            assert!(fits.get_image_kind(&href, &options).is_some());
+            assert!(matches!(
+                too_small.try_get_image_kind(&href, &options),
+                Err(ResolveError::Policy(_))
+            ));
+        }
+
+        let cached = BlockingReqwestResolver::default()
+            .with_max_body_size(body.len() - 1)
+            .with_cache(MemoryHttpCacheStore::new());
+        assert!(cached
+            .get_image_kind(&format!("{}/chunked.png", s.url()), &options)
+            .is_none());
+    }
+
+    #[test]
+    fn request_timeout() {
+        use crate::error::TryHrefStringResolver;
+
+        let body = include_bytes!("../test_data/gray.png");
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/trickle.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_chunked_body(|w| {
+                for chunk in body.chunks(body.len().div_ceil(6)) {
+                    std::thread::sleep(Duration::from_millis(100));
+                    w.write_all(chunk)?;
+                    w.flush()?;
+                }
+                Ok(())
+            })
+            .create();
+        let href = format!("{}/trickle.png", s.url());
+        let options = Options::default();
+
+        // Every read is faster than the timeout, but the whole body is not.
+        let resolver = BlockingReqwestResolver::default().with_timeout(Duration::from_millis(300));
+        assert!(matches!(
+            resolver.try_get_image_kind(&href, &options),
+            Err(ResolveError::Timeout)
+        ));
+        let resolver = BlockingReqwestResolver::default().with_timeout(Duration::from_secs(5));
+        assert!(resolver.get_image_kind(&href, &options).is_some());
+    }
+
+    #[test]
+    fn warm_backs_off() {
+        let mut s = mockito::Server::new();
+        let limited = s
+            .mock("GET", "/gray.png")
+            .with_status(429)
+            .with_header("retry-after", "2")
+            .expect(1)
+            .create();
+        let ok = s
+            .mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=600")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+
+        let clock = crate::clock::MockClock::new(SystemTime::UNIX_EPOCH);
+        let resolver = BlockingReqwestResolver::default()
+            .with_cache(MemoryHttpCacheStore::new())
+            .with_clock(clock.clone());
+        let href = format!("{}/gray.png", s.url());
+        let report = resolver.warm([href.as_str(), href.as_str()]);
+        assert_eq!(report.fetched, 1);
+        assert_eq!(report.fresh, 1);
+        assert!(report.failed.is_empty());
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(2));
+        limited.assert();
+        ok.assert();
+    }
+
+    #[cfg(feature = "http_auth")]
+    #[test]
+    fn digest_auth() {
+        let mut s = mockito::Server::new();
+        let challenge = s
+            .mock("GET", "/gray.png")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .with_status(401)
+            .with_header(
+                "www-authenticate",
+                r#"Digest realm="assets", qop="auth", nonce="abc", opaque="xyz""#,
+            )
+            .expect(1)
+            .create();
+        let authorized = s
+            .mock("GET", "/gray.png")
+            .match_header(
+                "authorization",
+                mockito::Matcher::Regex(r#"^Digest username="user", realm="assets""#.to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+
+        let resolver = BlockingReqwestResolver::default().with_credentials(
+            crate::auth::CredentialStore::new().with_host("127.0.0.1", "user", "password"),
+        );
+        assert!(resolver
+            .get_image_kind(&format!("{}/gray.png", s.url()), &Options::default())
+            .is_some());
+        challenge.assert();
+        authorized.assert();
+    }
+
+    #[test]
+    fn redirect_chain() {
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/a.png")
+            .with_status(302)
+            .with_header("location", "/b.png")
+            .create();
+        s.mock("GET", "/b.png")
+            .with_status(301)
+            .with_header("location", "/gray.png")
+            .create();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        let config = ClientConfig::new().with_max_redirects(2);
+        let resolver = BlockingReqwestResolver::from_config(&config)
+            .unwrap()
+            .with_response_hook(move |meta, _| recorded.lock().unwrap().push(meta.clone()));
+        let href = format!("{}/a.png", s.url());
+        assert!(resolver
+            .get_image_kind(&href, &Options::default())
+            .is_some());
+        let seen = seen.lock().unwrap();
+        assert_eq!(
+            seen[0].redirects,
+            [href.clone(), format!("{}/b.png", s.url())]
+        );
+        assert_eq!(seen[0].redirect_hosts(), ["127.0.0.1"]);
+
+        let config = ClientConfig::new().with_max_redirects(1);
+        let resolver = BlockingReqwestResolver::from_config(&config).unwrap();
+        assert!(resolver
+            .get_image_kind(&href, &Options::default())
+            .is_none());
+    }
+
+    #[test]
+    fn cache_control_max_age() {
+        assert_eq!(
+            max_age(Some("public, max-age=60")),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(max_age(Some("no-cache, max-age=60")), None);
+        assert_eq!(max_age(None), None);
+        assert!(is_no_store(Some("private, no-store")));
+        assert!(varies_on_everything(Some("accept, *")));
+        assert!(!varies_on_everything(Some("accept")));
+    }
+
+    #[test]
+    fn body_hook() {
+        let mut s = mockito::Server::new();
+        let mock = s
+            .mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=60")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = bodies.clone();
+        let resolver = BlockingReqwestResolver::default()
+            .with_body_hook(move |href, content_type, body| {
+                recorded.lock().unwrap().push((
+                    href.to_string(),
+                    content_type.map(str::to_string),
+                    body,
+                ));
+            })
+            .with_cache(MemoryHttpCacheStore::new());
+        let href = format!("{}/gray.png", s.url());
+        let options = Options::default();
+        for _ in 0..2 {
+            assert!(resolver.get_image_kind(&href, &options).is_some());
+        }
+        mock.assert();
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0].0, href);
+        assert_eq!(bodies[0].1.as_deref(), Some("image/png"));
+        assert_eq!(
+            bodies[0].2.as_slice(),
+            include_bytes!("../test_data/gray.png")
+        );
+    }
+
+    #[test]
+    fn sniffs_generic_content_types() {
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/octet")
+            .with_header("content-type", "application/octet-stream")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+        s.mock("GET", "/untyped")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+        let html = s
+            .mock("GET", "/page")
+            .with_header("content-type", "text/html")
+            .with_body("<html></html>")
+            .expect(1)
+            .create();
+        let resolver = BlockingReqwestResolver::default();
+        let options = Options::default();
+
+        for path in ["octet", "untyped"] {
+            let kind = resolver.get_image_kind(&format!("{}/{path}", s.url()), &options);
+            assert!(matches!(kind, Some(usvg::ImageKind::PNG(_))), "{path}");
+        }
+        let error = crate::error::TryHrefStringResolver::try_get_image_kind(
+            &resolver,
+            &format!("{}/page", s.url()),
+            &options,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            ResolveError::UnsupportedType(Some("text/html".to_string()))
+        );
+        html.assert();
+    }
+
+    #[test]
+    fn nested_svg_relative_href() {
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/icons/outer.svg")
+            .with_header("content-type", "image/svg+xml")
+            .with_body(r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="gray.png"/></svg>"#)
+            .create();
+        let gray = s
+            .mock("GET", "/icons/gray.png")
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+        let resolver = BlockingReqwestResolver::default();
+        let mut options = Options::default();
+        resolver.clone().set_into_options(&mut options);
+
+        let kind = resolver
+            .get_image_kind(&format!("{}/icons/outer.svg", s.url()), &options)
+            .unwrap();
+        let usvg::ImageKind::SVG(tree) = kind else {
+            panic!("expected an SVG image");
+        };
+        assert!(tree.root().has_children());
+        gray.assert();
+    }
+
+    #[test]
+    fn response_hook() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        let resolver = BlockingReqwestResolver::default().with_response_hook(move |meta, _| {
+            recorded.lock().unwrap().push(meta.clone());
+        });
+        let mut options = Options::default();
+        options.image_href_resolver.resolve_string = resolver.into_fn();
+
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/old.png")
+            .with_status(301)
+            .with_header("location", "/gray.png")
+            .create();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("x-cache", "HIT")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let href = format!("{}/old.png", s.url());
+        usvg::Tree::from_str(
+            &format!(r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="{href}"/></svg>"#),
+            &options,
+        )
+        .unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].href, href);
+        assert_eq!(seen[0].final_url, format!("{}/gray.png", s.url()));
+        assert_eq!(seen[0].status, 200);
+        assert_eq!(seen[0].header("X-Cache"), Some("HIT"));
+    }
+
+    #[test]
+    fn empty_bodies_are_not_cached() {
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(204)
+            .with_header("content-type", "image/png")
+            .with_header("etag", "\"empty\"")
+            .with_header("cache-control", "max-age=3600")
+            .create();
+        let href = format!("{}/gray.png", s.url());
+        let options = Options::default();
+
+        let error = crate::error::TryHrefStringResolver::try_get_image_kind(
+            &BlockingReqwestResolver::default(),
+            &href,
+            &options,
+        )
+        .unwrap_err();
+        assert_eq!(error, ResolveError::EmptyBody);
+
+        let store = Arc::new(MemoryHttpCacheStore::new());
+        let resolver = BlockingReqwestResolver::default().with_cache(store.clone());
+        assert!(resolver.get_image_kind(&href, &options).is_none());
+        assert!(store.get(&resolver.cache_key(&href)).is_none());
+    }
+
+    #[test]
+    fn fetch_lock_waits_for_other_fetchers() {
+        use crate::fetch_lock::{FetchLock, MemoryFetchLock};
+
+        let mut s = mockito::Server::new();
+        let mock = s
+            .mock("GET", "/gray.png")
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .expect(1)
+            .create();
+        let href = format!("{}/gray.png", s.url());
+        let store = Arc::new(MemoryHttpCacheStore::new());
+        let lock = MemoryFetchLock::new();
+        let resolver = BlockingReqwestResolver::default()
+            .with_cache(store.clone())
+            .with_fetch_lock(lock.clone(), Duration::from_millis(500));
+        let key = resolver.cache_key(&href);
+        let options = Options::default();
+
+        // Another worker holds the lock, and stores the image while this one waits.
+        assert!(lock.try_lock(&key, Duration::from_secs(60)));
+        let other = std::thread::spawn({
+            let (store, lock, key) = (store.clone(), lock.clone(), key.clone());
+            move || {
+                std::thread::sleep(Duration::from_millis(100));
+                store.put(
+                    &key,
+                    HttpCacheEntry {
+                        etag: Some("\"a\"".to_string()),
+                        last_modified: None,
+                        content_type: Some("image/png".to_string()),
+                        body: Arc::new(include_bytes!("../test_data/gray.png").to_vec()),
+                        fresh_until: None,
+                    },
+                );
+                lock.unlock(&key);
+            }
+        });
+        assert!(resolver.get_image_kind(&href, &options).is_some());
+        other.join().unwrap();
+
+        // The lock of a crashed worker is never released, so this one fetches after the timeout.
+        let lock = MemoryFetchLock::new();
+        let resolver = BlockingReqwestResolver::default()
+            .with_cache(MemoryHttpCacheStore::new())
+            .with_fetch_lock(lock.clone(), Duration::from_millis(100));
+        assert!(lock.try_lock(&key, Duration::from_secs(60)));
+        assert!(resolver.get_image_kind(&href, &options).is_some());
+        mock.assert();
+    }
 }
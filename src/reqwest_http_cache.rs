@@ -1,23 +1,86 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bridge::BackgroundRuntime;
+use crate::cache_control::RequestCacheControl;
+use crate::hooks::{BodyHook, Hook, ResponseHook, ResponseMetadata, SvgVerifier};
+use crate::target::TargetFilter;
+use crate::utils::ImageKindTypes;
 use crate::HrefStringResolver;
 
+/// Whether a response was served from the HTTP cache.
+///
+/// This is read from the `x-cache` header added by [`http_cache_reqwest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The response was served from the cache.
+    Hit,
+    /// The response was fetched from the origin.
+    Miss,
+    /// The cache middleware did not report a status.
+    Unknown,
+}
+
+impl CacheStatus {
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        match headers.get("x-cache").and_then(|v| v.to_str().ok()) {
+            Some("HIT") => Self::Hit,
+            Some("MISS") => Self::Miss,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+type CacheStatusHook = Hook<dyn Fn(&str, CacheStatus) + Send + Sync>;
+
+/// The image type, body, metadata and cache status of a fetched response.
+type FetchedBody = (
+    ImageKindTypes,
+    Arc<Vec<u8>>,
+    Option<ResponseMetadata>,
+    CacheStatus,
+);
+
 /// A resolver that uses reqwest with HTTP cache middleware to fetch images.
 ///
 /// This resolver wraps a [`reqwest_middleware::ClientWithMiddleware`] configured
 /// with [`http_cache_reqwest`] to cache HTTP responses according to standard
 /// HTTP caching semantics.
 ///
+/// The `Vary` header of cached responses is honored by the middleware. To make request
+/// headers part of the cache key, set [`cache_key`](`http_cache_reqwest::HttpCacheOptions::cache_key`)
+/// in the [`HttpCacheOptions`](`http_cache_reqwest::HttpCacheOptions`).
+///
 /// Like [`ReqwestResolver`](`crate::reqwest::ReqwestResolver`), this resolver
 /// can be used inside a [`tokio`] runtime but will block the current thread
 /// when resolving images. It *panics* if used with a current_thread runtime.
 #[derive(Debug, Clone)]
 pub struct HttpCacheReqwestResolver {
     client: reqwest_middleware::ClientWithMiddleware,
+    target: TargetFilter,
+    cache_status_hook: Option<CacheStatusHook>,
+    response_hook: Option<ResponseHook>,
+    body_hook: Option<BodyHook>,
+    timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    svg_verifier: Option<SvgVerifier>,
+    content_type_overrides: Vec<(TargetFilter, String)>,
 }
 
 impl HttpCacheReqwestResolver {
     /// Create a new `HttpCacheReqwestResolver` with the given middleware client.
     pub fn new(client: reqwest_middleware::ClientWithMiddleware) -> Self {
-        Self { client }
+        Self {
+            client,
+            target: TargetFilter::default(),
+            cache_status_hook: None,
+            response_hook: None,
+            body_hook: None,
+            timeout: None,
+            max_body_size: None,
+            svg_verifier: None,
+            content_type_overrides: Vec::new(),
+        }
     }
 
     /// Create a new `HttpCacheReqwestResolver` from cache configuration.
@@ -35,6 +98,8 @@ impl HttpCacheReqwestResolver {
     /// Create a new `HttpCacheReqwestResolver` from a custom [`reqwest::Client`] and cache configuration.
     ///
     /// Use this if you need to customize the reqwest client (e.g., set timeouts, headers, TLS settings).
+    /// A client with tuned connection pool settings can be built with
+    /// [`ClientConfig::build`](`crate::client::ClientConfig::build`).
     pub fn from_cache_options_with_client(
         client: reqwest::Client,
         manager: impl http_cache_reqwest::CacheManager + 'static,
@@ -48,80 +113,356 @@ impl HttpCacheReqwestResolver {
                 options,
             }))
             .build();
-        Self { client }
+        Self::new(client)
     }
 
     /// Get the underlying [`ClientWithMiddleware`](`reqwest_middleware::ClientWithMiddleware`) of this resolver.
     pub fn client(&self) -> &reqwest_middleware::ClientWithMiddleware {
         &self.client
     }
+
+    /// Set the [`TargetFilter`] deciding which `href`s this resolver fetches.
+    pub fn with_target(mut self, target: TargetFilter) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Only fetch the `href`s passing `predicate`, in addition to the checks of the current
+    /// [`TargetFilter`].
+    ///
+    /// This is a shorthand for setting [`TargetFilter::with_predicate`], and replaces a predicate
+    /// set before.
+    pub fn with_target_filter(
+        self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            target: self.target.with_predicate(predicate),
+            ..self
+        }
+    }
+
+    /// Fail requests that take longer than `timeout` in total, from sending the request to reading
+    /// the last byte of the body, overriding the timeout of the client.
+    ///
+    /// The time to connect is a setting of the client; see
+    /// [`ClientConfig::with_connect_timeout`](`crate::client::ClientConfig::with_connect_timeout`).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail responses whose body is larger than `max_body_size` bytes.
+    ///
+    /// A `Content-Length` above the limit is rejected before the body is read, and other bodies are
+    /// read chunk by chunk until they exceed the limit, so an oversized body is never buffered
+    /// whole. Responses served from the cache are checked as well. By default, bodies of any size
+    /// are accepted.
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// Set a hook that is called with the [`ResponseMetadata`] of every successfully resolved image.
+    pub fn with_response_hook(
+        mut self,
+        hook: impl Fn(&ResponseMetadata, &usvg::ImageKind) + Send + Sync + 'static,
+    ) -> Self {
+        self.response_hook = Some(Hook(Arc::new(hook)));
+        self
+    }
+
+    /// Set a hook that is called with the `href`, the content type and the raw bytes of every
+    /// successfully fetched body, e.g. to persist images to an application's own storage without
+    /// downloading them again.
+    ///
+    /// The hook is called before the image is decoded, and not for responses served from the cache.
+    pub fn with_body_hook(
+        mut self,
+        hook: impl Fn(&str, Option<&str>, Arc<Vec<u8>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.body_hook = Some(Hook(Arc::new(hook)));
+        self
+    }
+
+    /// Set a hook that is called with the [`CacheStatus`] of every fetched `href`.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::reqwest_http_cache::{CacheStatus, HttpCacheReqwestResolver};
+    ///
+    /// let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+    /// let resolver = HttpCacheReqwestResolver::new(client).with_cache_status_hook(|href, status| {
+    ///     if status == CacheStatus::Miss {
+    ///         println!("fetched '{href}' from origin");
+    ///     }
+    /// });
+    /// ```
+    pub fn with_cache_status_hook(
+        mut self,
+        hook: impl Fn(&str, CacheStatus) + Send + Sync + 'static,
+    ) -> Self {
+        self.cache_status_hook = Some(Hook(Arc::new(hook)));
+        self
+    }
+
+    /// Only accept nested SVG images whose detached signature is accepted by `verifier`.
+    ///
+    /// The signature is fetched through the cache from the `href` of the SVG image with `.sig`
+    /// appended, and `verifier` is called with the `href`, the SVG body and the signature before
+    /// the SVG is parsed. SVG images are rejected if the signature can't be fetched or `verifier`
+    /// returns `false`. Raster images are not affected.
+    pub fn with_svg_verifier(
+        mut self,
+        verifier: impl Fn(&str, &[u8], &[u8]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.svg_verifier = Some(Hook(Arc::new(verifier)));
+        self
+    }
+
+    /// Treat responses for `href`s matching `filter` as `content_type`, whatever their
+    /// `Content-Type` header says, e.g. for origins known to mislabel their images. When several
+    /// overrides match an `href`, the first one added wins.
+    pub fn with_content_type_override(
+        mut self,
+        filter: TargetFilter,
+        content_type: impl Into<String>,
+    ) -> Self {
+        self.content_type_overrides
+            .push((filter, content_type.into()));
+        self
+    }
 }
 
 impl From<reqwest_middleware::ClientWithMiddleware> for HttpCacheReqwestResolver {
     fn from(client: reqwest_middleware::ClientWithMiddleware) -> Self {
-        Self { client }
+        Self::new(client)
     }
 }
 
 impl HrefStringResolver<'_> for HttpCacheReqwestResolver {
     fn is_target(&self, href: &str) -> bool {
-        crate::utils::is_remote_url(href)
+        self.target.matches(href)
     }
     fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
-        let client = self.client.clone();
-        let href = href.to_string();
+        let cache_control = RequestCacheControl::current().header_value();
         let Ok(handle) = tokio::runtime::Handle::try_current() else {
             crate::utils::log_warn!(
                 "no tokio runtime found; cannot resolve '{}'",
-                href
+                crate::redact::redact(href)
             );
             return None;
         };
-        let (image_type, body) = tokio::task::block_in_place(|| {
-            handle.block_on(async {
-                let resp = match client.get(&href).send().await {
-                    Ok(resp) => resp,
-                    Err(e) => {
-                        crate::utils::log_warn!("failed to fetch '{}': {}", href, e);
-                        return None;
-                    }
-                };
-                let content_type = resp
-                    .headers()
-                    .get(reqwest::header::CONTENT_TYPE)
-                    .and_then(|v| v.to_str().ok());
-                let image_type = match crate::utils::ImageKindTypes::get_image_type(content_type, &href) {
-                    Some(t) => t,
-                    None => {
-                        crate::utils::log_warn!(
-                            "unsupported image type for '{}' (content-type: {:?})",
-                            href,
-                            content_type
-                        );
-                        return None;
-                    }
-                };
-                let body = match resp.bytes().await {
-                    Ok(b) => b.to_vec(),
-                    Err(e) => {
-                        crate::utils::log_warn!("failed to read response body for '{}': {}", href, e);
-                        return None;
-                    }
-                };
-                Some((image_type, body))
-            })
-        })?;
-
-        image_type.into_image_kind(body.into(), options)
+        let (image_type, body, metadata, cache_status) =
+            tokio::task::block_in_place(|| handle.block_on(self.fetch(href, cache_control)))?;
+        self.decode(image_type, body, href, metadata, cache_status, options)
+    }
+}
+
+impl HttpCacheReqwestResolver {
+    async fn fetch(&self, href: &str, cache_control: Option<String>) -> Option<FetchedBody> {
+        let mut req = self.client.get(href);
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        if let Some(value) = cache_control {
+            req = req.header(reqwest::header::CACHE_CONTROL, value);
+        }
+        let resp = match req.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "failed to fetch '{}': {}",
+                    crate::redact::redact(href),
+                    crate::redact::redact_error(&e, href)
+                );
+                return None;
+            }
+        };
+        if !self.target.accepts_final_url(href, resp.url().as_str()) {
+            return None;
+        }
+        let metadata = self.response_hook.as_ref().map(|_| {
+            ResponseMetadata::from_reqwest(href, resp.url(), resp.status(), resp.headers())
+        });
+        let cache_status = CacheStatus::from_headers(resp.headers());
+        if let Some(hook) = &self.cache_status_hook {
+            hook(href, cache_status);
+        }
+        if !resp.status().is_success() {
+            crate::utils::log_warn!(
+                "failed to fetch '{}': status {}",
+                crate::redact::redact(href),
+                resp.status()
+            );
+            return None;
+        }
+        let content_type = crate::target::override_content_type(
+            &self.content_type_overrides,
+            href,
+            resp.headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok()),
+        )
+        .map(str::to_string);
+        let unsupported = || {
+            crate::utils::log_warn!(
+                "unsupported image type for '{}' (content-type: {:?})",
+                crate::redact::redact(href),
+                content_type
+            );
+        };
+        if ImageKindTypes::get_image_type(content_type.as_deref(), href).is_none()
+            && !ImageKindTypes::is_generic(content_type.as_deref())
+        {
+            unsupported();
+            return None;
+        }
+        let body = crate::utils::read_body(resp, href, self.max_body_size).await?;
+        if crate::utils::is_empty_body(href, &body) {
+            return None;
+        }
+        let Some(image_type) = ImageKindTypes::detect(content_type.as_deref(), href, &body) else {
+            unsupported();
+            return None;
+        };
+        if matches!(image_type, ImageKindTypes::Svg) && !self.verify_svg(href, &body).await {
+            return None;
+        }
+        let body = Arc::new(body);
+        if let Some(hook) = self
+            .body_hook
+            .as_ref()
+            .filter(|_| cache_status != CacheStatus::Hit)
+        {
+            hook(href, content_type.as_deref(), body.clone());
+        }
+        Some((image_type, body, metadata, cache_status))
+    }
+
+    /// Check the detached signature of the SVG image `body` with the verifier set with
+    /// [`with_svg_verifier`](`Self::with_svg_verifier`), if any.
+    async fn verify_svg(&self, href: &str, body: &[u8]) -> bool {
+        let Some(verifier) = &self.svg_verifier else {
+            return true;
+        };
+        let signature = self.fetch_signature(&format!("{href}.sig")).await;
+        crate::hooks::verify_svg(verifier, href, body, signature.as_deref())
+    }
+
+    async fn fetch_signature(&self, sig_href: &str) -> Option<Vec<u8>> {
+        let mut req = self.client.get(sig_href);
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        let resp = match req.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                crate::utils::log_warn!(
+                    "failed to fetch '{}': {}",
+                    crate::redact::redact(sig_href),
+                    crate::redact::redact_error(&e, sig_href)
+                );
+                return None;
+            }
+        };
+        if !self.target.accepts_final_url(sig_href, resp.url().as_str()) {
+            return None;
+        }
+        if !resp.status().is_success() {
+            crate::utils::log_warn!(
+                "failed to fetch '{}': status {}",
+                crate::redact::redact(sig_href),
+                resp.status()
+            );
+            return None;
+        }
+        crate::utils::read_body(resp, sig_href, self.max_body_size).await
+    }
+
+    fn decode(
+        &self,
+        image_type: ImageKindTypes,
+        body: Arc<Vec<u8>>,
+        href: &str,
+        metadata: Option<ResponseMetadata>,
+        cache_status: CacheStatus,
+        options: &usvg::Options,
+    ) -> Option<usvg::ImageKind> {
+        // Recorded here rather than in `fetch`, which may run on the thread of another runtime.
+        if cache_status == CacheStatus::Hit {
+            crate::events::record_cache_hit();
+        }
+        let kind = image_type.into_image_kind_from(body, href, options)?;
+        if let (Some(hook), Some(metadata)) = (&self.response_hook, metadata) {
+            hook(&metadata, &kind);
+        }
+        Some(kind)
+    }
+}
+
+/// A blocking equivalent of [`HttpCacheReqwestResolver`] that does not need a tokio runtime.
+///
+/// The middleware client is driven on a runtime owned by this resolver, with its own worker
+/// thread, so synchronous callers get the cache (and any other middleware) without managing
+/// tokio. It can also be used from inside a runtime of any flavor, although it blocks the
+/// calling thread while fetching. Clones share the same runtime.
+///
+/// ```
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::reqwest_http_cache::BlockingHttpCacheReqwestResolver;
+///
+/// let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+/// let resolver = BlockingHttpCacheReqwestResolver::new(client.into()).unwrap();
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BlockingHttpCacheReqwestResolver {
+    resolver: HttpCacheReqwestResolver,
+    runtime: Arc<BackgroundRuntime>,
+}
+
+impl BlockingHttpCacheReqwestResolver {
+    /// Create a new `BlockingHttpCacheReqwestResolver` driving `resolver` on its own runtime.
+    ///
+    /// Fails if the runtime cannot be started.
+    pub fn new(resolver: HttpCacheReqwestResolver) -> std::io::Result<Self> {
+        Ok(Self {
+            resolver,
+            runtime: Arc::new(BackgroundRuntime::new()?),
+        })
+    }
+
+    /// Get the wrapped [`HttpCacheReqwestResolver`].
+    pub fn resolver(&self) -> &HttpCacheReqwestResolver {
+        &self.resolver
+    }
+}
+
+impl HrefStringResolver<'_> for BlockingHttpCacheReqwestResolver {
+    fn is_target(&self, href: &str) -> bool {
+        self.resolver.is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
+        let cache_control = RequestCacheControl::current().header_value();
+        let resolver = self.resolver.clone();
+        let owned_href = href.to_string();
+        let (image_type, body, metadata, cache_status) = self
+            .runtime
+            .run(async move { resolver.fetch(&owned_href, cache_control).await })??;
+        self.resolver
+            .decode(image_type, body, href, metadata, cache_status, options)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "reqwest_http_cache_manager_moka")]
     use http_cache_reqwest::{Cache, CacheMode, HttpCache, HttpCacheOptions};
     use usvg::Options;
 
+    #[cfg(feature = "reqwest_http_cache_manager_moka")]
     fn build_cached_client(
         cache: impl http_cache_reqwest::CacheManager + 'static,
     ) -> reqwest_middleware::ClientWithMiddleware {
@@ -187,4 +528,176 @@ mod tests {
 
         mock.assert();
     }
+
+    #[cfg(feature = "reqwest_http_cache_manager_moka")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn http_cache_status_hook() {
+        let statuses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = statuses.clone();
+        let client = build_cached_client(http_cache_reqwest::MokaManager::default());
+        let resolver =
+            HttpCacheReqwestResolver::new(client).with_cache_status_hook(move |_, status| {
+                recorded.lock().unwrap().push(status);
+            });
+        let mut options = Options::default();
+        options.image_href_resolver.resolve_string = resolver.into_fn();
+
+        let mut s = mockito::Server::new_async().await;
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <image href="{}/gray.png" />
+            </svg>"#,
+            s.url()
+        );
+        let (_, hit) =
+            crate::events::observe_cache_hit(|| usvg::Tree::from_str(&svg, &options).unwrap());
+        assert!(!hit);
+        let (_, hit) =
+            crate::events::observe_cache_hit(|| usvg::Tree::from_str(&svg, &options).unwrap());
+        assert!(hit);
+
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            vec![CacheStatus::Miss, CacheStatus::Hit]
+        );
+    }
+
+    #[cfg(feature = "reqwest_http_cache_manager_moka")]
+    #[test]
+    fn blocking_http_cache_resolver() {
+        let statuses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = statuses.clone();
+        let client = build_cached_client(http_cache_reqwest::MokaManager::default());
+        let resolver = BlockingHttpCacheReqwestResolver::new(
+            HttpCacheReqwestResolver::new(client).with_cache_status_hook(move |_, status| {
+                recorded.lock().unwrap().push(status);
+            }),
+        )
+        .unwrap();
+        let mut options = Options::default();
+        options.image_href_resolver.resolve_string = resolver.into_fn();
+
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("cache-control", "max-age=3600")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <image href="{}/gray.png" />
+            </svg>"#,
+            s.url()
+        );
+        assert!(usvg::Tree::from_str(&svg, &options)
+            .unwrap()
+            .root()
+            .has_children());
+        let (_, hit) =
+            crate::events::observe_cache_hit(|| usvg::Tree::from_str(&svg, &options).unwrap());
+        assert!(hit);
+
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            vec![CacheStatus::Miss, CacheStatus::Hit]
+        );
+    }
+
+    #[test]
+    fn content_type_override() {
+        let mut s = mockito::Server::new();
+        for path in ["/legacy/gray", "/other/gray"] {
+            s.mock("GET", path)
+                .with_status(200)
+                .with_header("content-type", "text/plain")
+                .with_body(include_bytes!("../test_data/gray.png"))
+                .create();
+        }
+        let filter = TargetFilter::new().with_predicate(|href| href.contains("/legacy/"));
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        let resolver = BlockingHttpCacheReqwestResolver::new(
+            HttpCacheReqwestResolver::new(client).with_content_type_override(filter, "image/png"),
+        )
+        .unwrap();
+        let options = Options::default();
+        assert!(matches!(
+            resolver.get_image_kind(&format!("{}/legacy/gray", s.url()), &options),
+            Some(usvg::ImageKind::PNG(_))
+        ));
+        assert!(resolver
+            .get_image_kind(&format!("{}/other/gray", s.url()), &options)
+            .is_none());
+    }
+
+    #[test]
+    fn svg_verifier() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+        let mut s = mockito::Server::new();
+        for name in ["signed", "forged", "unsigned"] {
+            s.mock("GET", format!("/{name}.svg").as_str())
+                .with_status(200)
+                .with_header("content-type", "image/svg+xml")
+                .with_body(svg)
+                .create();
+        }
+        s.mock("GET", "/signed.svg.sig").with_body("good").create();
+        s.mock("GET", "/forged.svg.sig").with_body("bad").create();
+        s.mock("GET", "/unsigned.svg.sig").with_status(404).create();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(include_bytes!("../test_data/gray.png"))
+            .create();
+
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        let resolver = BlockingHttpCacheReqwestResolver::new(
+            HttpCacheReqwestResolver::new(client)
+                .with_svg_verifier(|_, body, signature| !body.is_empty() && signature == b"good"),
+        )
+        .unwrap();
+        let options = Options::default();
+        let resolve =
+            |name: &str| resolver.get_image_kind(&format!("{}/{name}", s.url()), &options);
+        assert!(matches!(
+            resolve("signed.svg"),
+            Some(usvg::ImageKind::SVG(_))
+        ));
+        assert!(resolve("forged.svg").is_none());
+        assert!(resolve("unsigned.svg").is_none());
+        assert!(matches!(resolve("gray.png"), Some(usvg::ImageKind::PNG(_))));
+    }
+
+    #[test]
+    fn max_body_size() {
+        let body = include_bytes!("../test_data/gray.png");
+        let mut s = mockito::Server::new();
+        s.mock("GET", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(body)
+            .create();
+        let href = format!("{}/gray.png", s.url());
+        let options = Options::default();
+
+        let resolve = |max_body_size| {
+            let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+            let resolver = HttpCacheReqwestResolver::new(client)
+                .with_timeout(Duration::from_secs(5))
+                .with_max_body_size(max_body_size);
+            BlockingHttpCacheReqwestResolver::new(resolver)
+                .unwrap()
+                .get_image_kind(&href, &options)
+        };
+        assert!(resolve(body.len()).is_some());
+        assert!(resolve(body.len() - 1).is_none());
+    }
 }
@@ -0,0 +1,299 @@
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use usvg::{ImageKind, Options};
+
+use crate::budget::RetryBudget;
+use crate::clock::{Clock, SystemClock};
+use crate::error::ResolveError;
+use crate::HrefStringResolver;
+
+/// How many times, and how long apart, [`RetryResolver`] attempts to resolve an `href`.
+///
+/// Failed attempts are retried after an exponential backoff, doubling from the initial backoff up
+/// to the maximum. With jitter, which is on by default, each delay is picked at random between
+/// half of the backoff and the full backoff, so clients that failed together don't retry together.
+///
+/// ```
+/// use std::time::Duration;
+/// use usvg_remote_resolvers::retry::RetryPolicy;
+///
+/// let policy = RetryPolicy::new(4)
+///     .with_backoff(Duration::from_millis(200), Duration::from_secs(1))
+///     .without_jitter();
+/// assert_eq!(policy.backoff(1), Duration::from_millis(200));
+/// assert_eq!(policy.backoff(3), Duration::from_millis(800));
+/// assert_eq!(policy.backoff(4), Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, backing off from 100ms up to 5s, with jitter.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a new `RetryPolicy` making at most `max_attempts` attempts, including the first one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is `0`.
+    pub fn new(max_attempts: u32) -> Self {
+        assert!(max_attempts > 0, "max_attempts must be at least 1");
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Back off for `initial` before the first retry, doubling for every further retry up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Wait exactly the backoff between attempts.
+    pub fn without_jitter(mut self) -> Self {
+        self.jitter = false;
+        self
+    }
+
+    /// Get the maximum number of attempts, including the first one.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Get the backoff before the `retry`th retry, starting at `1`, before jitter is applied.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Returns `true` if an attempt failing with `error` may succeed when retried: network errors,
+    /// timeouts and `5xx` statuses.
+    pub fn is_retryable(error: &ResolveError) -> bool {
+        match error {
+            ResolveError::Network(_) | ResolveError::Timeout => true,
+            ResolveError::Status(status) => (500..600).contains(status),
+            ResolveError::EmptyBody
+            | ResolveError::UnsupportedType(_)
+            | ResolveError::Decode(_)
+            | ResolveError::Policy(_) => false,
+        }
+    }
+
+    /// Get the delay before the `retry`th retry, with jitter if enabled.
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        if !self.jitter {
+            return backoff;
+        }
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        backoff / 2 + backoff.mul_f64((random as f64 / u64::MAX as f64) / 2.0)
+    }
+}
+
+/// A resolver retrying the transient failures of the inner resolver, following a
+/// [`RetryPolicy`].
+///
+/// The inner resolver must report why it failed, so only the failures that may be transient are
+/// retried; see [`RetryPolicy::is_retryable`]. Waiting between attempts blocks the calling
+/// thread. If a [`RetryBudget`] is set, every retry spends it, and no retries are made once it is
+/// exhausted, so a document full of broken images doesn't retry every one of them.
+///
+/// ```
+/// # #[cfg(feature = "reqwest_blocking")]
+/// # {
+/// use usvg_remote_resolvers::HrefStringResolver;
+/// use usvg_remote_resolvers::reqwest_blocking::BlockingReqwestResolver;
+/// use usvg_remote_resolvers::retry::{RetryPolicy, RetryResolver};
+///
+/// let resolver = RetryResolver::new(BlockingReqwestResolver::default(), RetryPolicy::default());
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryResolver<T> {
+    inner: T,
+    policy: RetryPolicy,
+    budget: Option<RetryBudget>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<T> RetryResolver<T> {
+    /// Create a new `RetryResolver` retrying `inner` following `policy`.
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            budget: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the policy of this resolver.
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Spend `budget` on every retry.
+    pub fn with_budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Use `clock` to wait between attempts, instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl<'a, T: crate::error::TryHrefStringResolver<'a>> crate::error::TryHrefStringResolver<'a>
+    for RetryResolver<T>
+{
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn try_get_image_kind(&self, href: &str, options: &Options) -> Result<ImageKind, ResolveError> {
+        let mut attempt = 1;
+        loop {
+            let error = match self.inner.try_get_image_kind(href, options) {
+                Ok(kind) => return Ok(kind),
+                Err(error) => error,
+            };
+            if attempt >= self.policy.max_attempts
+                || !RetryPolicy::is_retryable(&error)
+                || self
+                    .budget
+                    .as_ref()
+                    .is_some_and(|budget| !budget.try_spend())
+            {
+                return Err(error);
+            }
+            let delay = self.policy.delay(attempt);
+            crate::utils::log_warn!(
+                "retrying '{}' in {:?} after attempt {}: {}",
+                crate::redact::redact(href),
+                delay,
+                attempt,
+                error
+            );
+            self.clock.sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+impl<'a, T: crate::error::TryHrefStringResolver<'a>> HrefStringResolver<'a> for RetryResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        crate::error::TryHrefStringResolver::try_get_image_kind(self, href, options).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    /// Fails with the given error until the given number of attempts were made.
+    struct Flaky(AtomicUsize, usize, ResolveError);
+
+    impl crate::error::TryHrefStringResolver<'_> for Flaky {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn try_get_image_kind(
+            &self,
+            href: &str,
+            options: &Options,
+        ) -> Result<ImageKind, ResolveError> {
+            if self.0.fetch_add(1, Ordering::SeqCst) < self.1 {
+                return Err(self.2.clone());
+            }
+            crate::DefaultResolver
+                .get_image_kind(href, options)
+                .ok_or(ResolveError::Status(404))
+        }
+    }
+
+    #[test]
+    fn retries_transient_failures() {
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH);
+        let policy = RetryPolicy::new(3)
+            .with_backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .without_jitter();
+        let retrying = |failures, error| {
+            RetryResolver::new(Flaky(AtomicUsize::new(0), failures, error), policy)
+                .with_clock(clock.clone())
+        };
+        let options = Options::default();
+
+        let resolver = retrying(2, ResolveError::Status(503));
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_some());
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(300)
+        );
+
+        let resolver = retrying(3, ResolveError::Timeout);
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_none());
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 3);
+
+        let resolver = retrying(1, ResolveError::Status(404));
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_none());
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 1);
+
+        let resolver = retrying(2, ResolveError::Status(503)).with_budget(RetryBudget::new(1));
+        assert!(resolver
+            .get_image_kind("./test_data/gray.png", &options)
+            .is_none());
+        assert_eq!(resolver.inner().0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn jitter_stays_within_backoff() {
+        let policy = RetryPolicy::default();
+        for retry in 1..8 {
+            let delay = policy.delay(retry);
+            assert!(delay >= policy.backoff(retry) / 2 && delay <= policy.backoff(retry));
+        }
+    }
+}
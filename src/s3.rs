@@ -124,7 +124,10 @@ impl S3Resolver {
 
     /// Add caching to this resolver using the given cache store.
     pub fn with_cache<C: S3CacheStore>(self, cache: C) -> CachedS3Resolver<C> {
-        CachedS3Resolver { client: self.client, cache }
+        CachedS3Resolver {
+            client: self.client,
+            cache,
+        }
     }
 }
 
@@ -157,7 +160,7 @@ impl<C: S3CacheStore> HrefStringResolver<'_> for CachedS3Resolver<C> {
     }
     fn get_image_kind(&self, href: &str, options: &usvg::Options) -> Option<usvg::ImageKind> {
         let Some((bucket, key)) = parse_s3_url(href) else {
-            crate::utils::log_warn!("invalid S3 URL: '{}'", href);
+            crate::utils::log_warn!("invalid S3 URL: '{}'", crate::redact::redact(href));
             return None;
         };
         let client = self.client.clone();
@@ -168,7 +171,7 @@ impl<C: S3CacheStore> HrefStringResolver<'_> for CachedS3Resolver<C> {
         let Ok(handle) = tokio::runtime::Handle::try_current() else {
             crate::utils::log_warn!(
                 "no tokio runtime found; cannot resolve '{}'",
-                href
+                crate::redact::redact(href)
             );
             return None;
         };
@@ -184,31 +187,45 @@ impl<C: S3CacheStore> HrefStringResolver<'_> for CachedS3Resolver<C> {
                     Ok(resp) => {
                         let etag = resp.e_tag().unwrap_or_default().to_string();
                         let content_type = resp.content_type().map(|s| s.to_string());
-                        let image_type = match crate::utils::ImageKindTypes::get_image_type(
+                        let unsupported = || {
+                            crate::utils::log_warn!(
+                                "unsupported image type for '{}' (content-type: {:?})",
+                                crate::redact::redact(href),
+                                content_type
+                            );
+                        };
+                        if crate::utils::ImageKindTypes::get_image_type(
                             content_type.as_deref(),
                             &key,
-                        ) {
-                            Some(t) => t,
-                            None => {
-                                crate::utils::log_warn!(
-                                    "unsupported image type for '{}' (content-type: {:?})",
-                                    href,
-                                    content_type
-                                );
-                                return None;
-                            }
-                        };
+                        )
+                        .is_none()
+                            && !crate::utils::ImageKindTypes::is_generic(content_type.as_deref())
+                        {
+                            unsupported();
+                            return None;
+                        }
                         let body: Arc<Vec<u8>> = match resp.body.collect().await {
                             Ok(b) => Arc::new(b.to_vec()),
                             Err(e) => {
                                 crate::utils::log_warn!(
                                     "failed to read S3 response body for '{}': {}",
-                                    href,
-                                    e
+                                    crate::redact::redact(href),
+                                    crate::redact::redact_error(&e, href)
                                 );
                                 return None;
                             }
                         };
+                        if crate::utils::is_empty_body(href, &body) {
+                            return None;
+                        }
+                        let Some(image_type) = crate::utils::ImageKindTypes::detect(
+                            content_type.as_deref(),
+                            &key,
+                            &body,
+                        ) else {
+                            unsupported();
+                            return None;
+                        };
 
                         if !etag.is_empty() {
                             self.cache.put(
@@ -228,16 +245,17 @@ impl<C: S3CacheStore> HrefStringResolver<'_> for CachedS3Resolver<C> {
                         let raw = err.raw_response();
                         if raw.as_ref().is_some_and(|r| r.status().as_u16() == 304) {
                             let cached = cached?;
-                            let image_type = crate::utils::ImageKindTypes::get_image_type(
+                            let image_type = crate::utils::ImageKindTypes::detect(
                                 cached.content_type.as_deref(),
                                 &key,
+                                &cached.body,
                             )?;
                             Some((image_type, cached.body))
                         } else {
                             crate::utils::log_warn!(
                                 "S3 request failed for '{}': {}",
-                                href,
-                                err
+                                crate::redact::redact(href),
+                                crate::redact::redact_error(&err, href)
                             );
                             None
                         }
@@ -246,7 +264,7 @@ impl<C: S3CacheStore> HrefStringResolver<'_> for CachedS3Resolver<C> {
             })
         })?;
 
-        image_type.into_image_kind(body, options)
+        image_type.into_image_kind_from(body, href, options)
     }
 }
 
@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::target::TargetFilter;
+
+/// The namespace of `xlink:href`.
+const XLINK_NS: &str = "http://www.w3.org/1999/xlink";
+
+/// Elements whose `href` is resolved as an image.
+const IMAGE_ELEMENTS: &[&str] = &["image", "feImage"];
+
+/// Elements whose content is only rendered where it is referenced.
+const REFERENCED_ELEMENTS: &[&str] = &[
+    "defs", "symbol", "clipPath", "mask", "pattern", "marker", "filter",
+];
+
+/// Extract the `href`s of the images rendered by an SVG document, without duplicates, in
+/// rendering order.
+///
+/// Both `href` and `xlink:href` are read, and `data:` URLs are skipped since they are not
+/// resolved by the string resolvers. The `href`s are returned as written, so relative paths are
+/// included as well.
+///
+/// Images inside `<defs>`, `<symbol>` and other elements that are only rendered where they are
+/// referenced are included only if they are referenced, with `<use href="#id">` or `url(#id)`
+/// in an attribute such as `fill` or `filter`, so assets that are never painted are not
+/// prefetched.
+///
+/// ```
+/// let hrefs = usvg_remote_resolvers::scan::image_hrefs(
+///     r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+///         <defs>
+///             <symbol id="used"><image href="https://example.com/c.png"/></symbol>
+///             <symbol id="unused"><image href="https://example.com/d.png"/></symbol>
+///         </defs>
+///         <image href="https://example.com/a.png"/>
+///         <image xlink:href="./b.png"/>
+///         <image href="https://example.com/a.png"/>
+///         <use href="#used"/>
+///     </svg>"##,
+/// )
+/// .unwrap();
+/// assert_eq!(hrefs, ["https://example.com/a.png", "./b.png", "https://example.com/c.png"]);
+/// ```
+pub fn image_hrefs(svg: &str) -> Result<Vec<String>, usvg::Error> {
+    let document = parse(svg)?;
+    let mut scanner = Scanner {
+        ids: document
+            .descendants()
+            .filter_map(|node| Some((node.attribute("id")?, node)))
+            .collect(),
+        followed: HashSet::new(),
+        hrefs: Vec::new(),
+    };
+    scanner.scan(document.root_element(), false);
+    Ok(scanner.hrefs)
+}
+
+struct Scanner<'d, 'i> {
+    ids: HashMap<&'d str, roxmltree::Node<'d, 'i>>,
+    followed: HashSet<roxmltree::NodeId>,
+    hrefs: Vec<String>,
+}
+
+impl<'d, 'i> Scanner<'d, 'i> {
+    /// Scan `node` and its descendants. Elements that are only rendered where they are
+    /// referenced are skipped, unless `node` is `referenced`.
+    fn scan(&mut self, node: roxmltree::Node<'d, 'i>, referenced: bool) {
+        let name = node.tag_name().name();
+        if !referenced && REFERENCED_ELEMENTS.contains(&name) {
+            return;
+        }
+        let href = node
+            .attribute("href")
+            .or_else(|| node.attribute((XLINK_NS, "href")))
+            .map(str::trim)
+            .unwrap_or_default();
+        if IMAGE_ELEMENTS.contains(&name) || name == "use" {
+            if let Some(id) = href.strip_prefix('#') {
+                self.follow(id);
+            } else if name != "use"
+                && !href.is_empty()
+                && !href.starts_with("data:")
+                && !self.hrefs.iter().any(|h| h == href)
+            {
+                self.hrefs.push(href.to_string());
+            }
+        }
+        for attribute in node.attributes() {
+            for id in url_references(attribute.value()) {
+                self.follow(id);
+            }
+        }
+        for child in node.children().filter(|n| n.is_element()) {
+            self.scan(child, false);
+        }
+    }
+
+    /// Scan the element with the given `id`, once.
+    fn follow(&mut self, id: &str) {
+        let Some(&node) = self.ids.get(id) else {
+            return;
+        };
+        if self.followed.insert(node.id()) {
+            self.scan(node, true);
+        }
+    }
+}
+
+/// The ids referenced with `url(#id)` in an attribute value.
+fn url_references(value: &str) -> impl Iterator<Item = &str> {
+    value.split("url(").skip(1).filter_map(|rest| {
+        let (inner, _) = rest.split_once(')')?;
+        inner.trim().trim_matches(['"', '\'']).strip_prefix('#')
+    })
+}
+
+fn parse(svg: &str) -> Result<roxmltree::Document<'_>, usvg::Error> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    roxmltree::Document::parse_with_options(svg, options).map_err(usvg::Error::ParsingFailed)
+}
+
+/// A remote asset listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestAsset {
+    /// The URL of the asset.
+    pub url: String,
+    /// The `Content-Type` of the asset, if known.
+    pub content_type: Option<String>,
+    /// The size of the asset in bytes, if known.
+    pub size: Option<u64>,
+}
+
+/// The deduplicated list of remote assets referenced by a set of SVG documents.
+///
+/// This can be used to push the assets to edge caches before the documents go live. The sizes
+/// and types are only known after [`fetch_metadata`](`Self::fetch_metadata`).
+///
+/// ```
+/// use usvg_remote_resolvers::scan::Manifest;
+///
+/// let manifest = Manifest::scan([
+///     r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="https://example.com/a.png"/></svg>"#,
+///     r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="./local.png"/></svg>"#,
+/// ])
+/// .unwrap();
+/// assert_eq!(
+///     manifest.to_json(),
+///     r#"{"assets":[{"url":"https://example.com/a.png","content_type":null,"size":null}]}"#,
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// The assets, in the order they were first referenced.
+    pub assets: Vec<ManifestAsset>,
+}
+
+impl Manifest {
+    /// Scan `documents` for `http://` and `https://` image URLs.
+    pub fn scan<'d>(documents: impl IntoIterator<Item = &'d str>) -> Result<Self, usvg::Error> {
+        Self::scan_with_target(documents, &TargetFilter::default())
+    }
+
+    /// Scan `documents` for image URLs accepted by `target`.
+    pub fn scan_with_target<'d>(
+        documents: impl IntoIterator<Item = &'d str>,
+        target: &TargetFilter,
+    ) -> Result<Self, usvg::Error> {
+        let mut manifest = Self::default();
+        for document in documents {
+            for url in image_hrefs(document)? {
+                if target.matches(&url) && !manifest.assets.iter().any(|a| a.url == url) {
+                    manifest.assets.push(ManifestAsset {
+                        url,
+                        content_type: None,
+                        size: None,
+                    });
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// The URLs of the assets.
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.assets.iter().map(|a| a.url.as_str())
+    }
+
+    /// Fill in the types and sizes of the assets with `HEAD` requests.
+    ///
+    /// Assets whose request fails keep their previous values.
+    #[cfg(feature = "reqwest_blocking")]
+    pub fn fetch_metadata(&mut self, client: &reqwest::blocking::Client) {
+        for asset in &mut self.assets {
+            let resp = match client.head(&asset.url).send() {
+                Ok(resp) if resp.status().is_success() => resp,
+                Ok(resp) => {
+                    crate::utils::log_warn!(
+                        "HEAD '{}' failed: status {}",
+                        crate::redact::redact(&asset.url),
+                        resp.status()
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    crate::utils::log_warn!(
+                        "HEAD '{}' failed: {}",
+                        crate::redact::redact(&asset.url),
+                        crate::redact::redact_error(&e, &asset.url)
+                    );
+                    continue;
+                }
+            };
+            let header = |name| resp.headers().get(name).and_then(|v| v.to_str().ok());
+            asset.content_type = header(reqwest::header::CONTENT_TYPE).map(str::to_string);
+            asset.size = header(reqwest::header::CONTENT_LENGTH).and_then(|v| v.parse().ok());
+        }
+    }
+
+    /// Serialize the manifest as JSON, as `{"assets":[{"url":..,"content_type":..,"size":..}]}`.
+    pub fn to_json(&self) -> String {
+        let assets: Vec<_> = self
+            .assets
+            .iter()
+            .map(|asset| {
+                format!(
+                    r#"{{"url":{},"content_type":{},"size":{}}}"#,
+                    crate::utils::json_string(&asset.url),
+                    asset
+                        .content_type
+                        .as_deref()
+                        .map_or("null".to_string(), crate::utils::json_string),
+                    asset.size.map_or("null".to_string(), |s| s.to_string()),
+                )
+            })
+            .collect();
+        format!(r#"{{"assets":[{}]}}"#, assets.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_data_and_non_image_hrefs() {
+        let hrefs = image_hrefs(
+            r##"<svg xmlns="http://www.w3.org/2000/svg">
+                <a href="https://example.com/page.html"><image href="data:image/png;base64,AAAA"/></a>
+                <filter id="f"><feImage href="https://example.com/f.png"/></filter>
+                <use href="#f"/>
+            </svg>"##,
+        )
+        .unwrap();
+        assert_eq!(hrefs, ["https://example.com/f.png"]);
+        assert!(image_hrefs("<svg").is_err());
+    }
+
+    #[test]
+    fn follows_references() {
+        let hrefs = image_hrefs(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+                <defs>
+                    <pattern id="p"><use xlink:href="#nested"/></pattern>
+                    <g id="nested"><image href="https://example.com/nested.png"/></g>
+                    <mask id="m"><image href="https://example.com/mask.png"/></mask>
+                    <filter id="f"><feImage href="https://example.com/unused.png"/></filter>
+                    <symbol id="loop"><use href="#loop"/></symbol>
+                </defs>
+                <rect fill="url(#p)" style="mask: url('#m')"/>
+                <use href="#loop"/>
+            </svg>"##,
+        )
+        .unwrap();
+        assert_eq!(
+            hrefs,
+            [
+                "https://example.com/nested.png",
+                "https://example.com/mask.png"
+            ]
+        );
+    }
+
+    #[cfg(feature = "reqwest_blocking")]
+    #[test]
+    fn fetch_metadata() {
+        let mut s = mockito::Server::new();
+        s.mock("HEAD", "/gray.png")
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_header("content-length", "1234")
+            .create();
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="{}/gray.png"/></svg>"#,
+            s.url()
+        );
+
+        let mut manifest = Manifest::scan([svg.as_str(), svg.as_str()]).unwrap();
+        manifest.fetch_metadata(&reqwest::blocking::Client::new());
+        assert_eq!(manifest.assets.len(), 1);
+        assert_eq!(
+            manifest.assets[0].content_type.as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(manifest.assets[0].size, Some(1234));
+    }
+}
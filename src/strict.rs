@@ -0,0 +1,227 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// Error listing the `href`s that could not be resolved in strict mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedImages {
+    /// The `href`s that resolved to nothing, in the order they were encountered.
+    pub hrefs: Vec<String>,
+}
+
+impl fmt::Display for UnresolvedImages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to resolve {} image(s): ", self.hrefs.len())?;
+        for (i, href) in self.hrefs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "'{}'", crate::redact::redact(href))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnresolvedImages {}
+
+/// Error returned by [`parse_str_strict`].
+#[derive(Debug)]
+pub enum StrictError {
+    /// The SVG itself failed to parse.
+    Parse(usvg::Error),
+    /// The SVG parsed, but some images could not be resolved.
+    Unresolved(UnresolvedImages),
+}
+
+impl fmt::Display for StrictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "failed to parse SVG: {}", e),
+            Self::Unresolved(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StrictError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(e) => Some(e),
+            Self::Unresolved(e) => Some(e),
+        }
+    }
+}
+
+impl From<usvg::Error> for StrictError {
+    fn from(e: usvg::Error) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<UnresolvedImages> for StrictError {
+    fn from(e: UnresolvedImages) -> Self {
+        Self::Unresolved(e)
+    }
+}
+
+/// Handle to the `href`s that a [`StrictResolver`] failed to resolve.
+///
+/// It stays valid after the resolver is moved into the [`Options`].
+#[derive(Debug, Clone, Default)]
+pub struct UnresolvedSink {
+    hrefs: Arc<Mutex<Vec<String>>>,
+}
+
+impl UnresolvedSink {
+    fn push(&self, href: &str) {
+        if let Ok(mut hrefs) = self.hrefs.lock() {
+            hrefs.push(href.to_string());
+        }
+    }
+
+    /// Take the recorded failures, returning an error if there were any.
+    ///
+    /// The sink is emptied, so it can be reused for the next parse.
+    pub fn finish(&self) -> Result<(), UnresolvedImages> {
+        let hrefs = self
+            .hrefs
+            .lock()
+            .map(|mut h| std::mem::take(&mut *h))
+            .unwrap_or_default();
+        if hrefs.is_empty() {
+            Ok(())
+        } else {
+            Err(UnresolvedImages { hrefs })
+        }
+    }
+}
+
+/// A resolver that records every `href` the inner resolver could not resolve.
+///
+/// Without it, a failed image simply renders as nothing. Call
+/// [`UnresolvedSink::finish`] after parsing to turn those failures into an error.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver, strict::StrictResolver};
+///
+/// let resolver = StrictResolver::new(DefaultResolver);
+/// let sink = resolver.sink();
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+///
+/// let _tree = usvg::Tree::from_str(
+///     r#"<svg xmlns="http://www.w3.org/2000/svg">
+///         <image href="./missing.png" />
+///     </svg>"#,
+///     &options,
+/// ).unwrap();
+/// assert_eq!(sink.finish().unwrap_err().hrefs, vec!["./missing.png"]);
+/// ```
+#[derive(Debug)]
+pub struct StrictResolver<T> {
+    inner: T,
+    sink: UnresolvedSink,
+}
+
+impl<T> StrictResolver<T> {
+    /// Create a new `StrictResolver` wrapping the given resolver.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            sink: UnresolvedSink::default(),
+        }
+    }
+
+    /// Get a handle to the failures recorded by this resolver.
+    pub fn sink(&self) -> UnresolvedSink {
+        self.sink.clone()
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for StrictResolver<T> {
+    /// Every `href` is a target, so that the ones rejected by the inner resolver are recorded too.
+    fn is_target(&self, _: &str) -> bool {
+        true
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.push::<Self>(crate::explain::Outcome::Matched);
+        trace.nested(|trace| self.inner.explain_into(trace));
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let result = self
+            .inner
+            .is_target(href)
+            .then(|| self.inner.get_image_kind(href, options))
+            .flatten();
+        if result.is_none() {
+            self.sink.push(href);
+        }
+        result
+    }
+}
+
+/// Parse `text` with `resolver`, failing if any image could not be resolved.
+///
+/// The resolver is wrapped in a [`StrictResolver`] and installed into `options`.
+pub fn parse_str_strict<'a, R>(
+    text: &str,
+    resolver: R,
+    options: &mut Options<'a>,
+) -> Result<usvg::Tree, StrictError>
+where
+    R: HrefStringResolver<'a> + 'a,
+{
+    let resolver = StrictResolver::new(resolver);
+    let sink = resolver.sink();
+    resolver.set_into_options(options);
+    let tree = usvg::Tree::from_str(text, options)?;
+    sink.finish()?;
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DefaultResolver, FileResolver};
+
+    #[test]
+    fn strict_ok() {
+        let mut options = Options::default();
+        let tree = parse_str_strict(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <image href="./test_data/gray.png" />
+            </svg>"#,
+            DefaultResolver,
+            &mut options,
+        );
+        assert!(tree.is_ok());
+    }
+
+    #[test]
+    fn strict_reports_unresolved() {
+        let mut options = Options::default();
+        let err = parse_str_strict(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <image href="./test_data/gray.png" />
+                <image href="https://example.com/a.png" />
+            </svg>"#,
+            FileResolver::new(),
+            &mut options,
+        )
+        .unwrap_err();
+        let StrictError::Unresolved(unresolved) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(
+            unresolved.hrefs,
+            vec!["./test_data/gray.png", "https://example.com/a.png"]
+        );
+    }
+}
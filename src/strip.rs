@@ -0,0 +1,297 @@
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+const JPEG_ICC_PROFILE: &[u8] = b"ICC_PROFILE\0";
+const JPEG_METADATA: &[&[u8]] = &[
+    b"Exif\0\0",
+    b"http://ns.adobe.com/xap/1.0/\0",
+    b"http://ns.adobe.com/xmp/extension/\0",
+    b"Photoshop 3.0\0",
+];
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_METADATA: &[&[u8]] = &[b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+/// Which parts of raster images are removed by [`StripResolver`] and [`StripStore`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StripOptions {
+    icc_profile: bool,
+    metadata: bool,
+}
+
+impl StripOptions {
+    /// Create new `StripOptions` that keep everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove embedded ICC profiles (JPEG `APP2` segments and the PNG `iCCP` chunk), so the images
+    /// are rendered as sRGB like untagged images are.
+    pub fn with_icc_profile(mut self) -> Self {
+        self.icc_profile = true;
+        self
+    }
+
+    /// Remove EXIF, XMP and IPTC metadata (JPEG `APP1` and `APP13` segments, and the PNG `eXIf`,
+    /// `tEXt`, `zTXt`, `iTXt` and `tIME` chunks), such as camera details and GPS positions.
+    ///
+    /// This also removes the EXIF orientation of JPEG images.
+    pub fn with_metadata(mut self) -> Self {
+        self.metadata = true;
+        self
+    }
+
+    fn strip_jpeg_segment(&self, marker: u8, payload: &[u8]) -> bool {
+        match marker {
+            0xe2 => self.icc_profile && payload.starts_with(JPEG_ICC_PROFILE),
+            0xe1 | 0xed => self.metadata && JPEG_METADATA.iter().any(|id| payload.starts_with(id)),
+            _ => false,
+        }
+    }
+
+    fn strip_png_chunk(&self, chunk_type: &[u8]) -> bool {
+        match chunk_type {
+            b"iCCP" => self.icc_profile,
+            _ => self.metadata && PNG_METADATA.contains(&chunk_type),
+        }
+    }
+}
+
+/// Remove the parts selected by `options` from a JPEG or PNG image, detected by its signature.
+///
+/// Returns `None` if `data` is neither a well-formed JPEG nor PNG.
+pub fn strip(data: &[u8], options: &StripOptions) -> Option<Vec<u8>> {
+    if data.starts_with(PNG_SIGNATURE) {
+        strip_png(data, options)
+    } else {
+        strip_jpeg(data, options)
+    }
+}
+
+/// Remove the parts selected by `options` from a JPEG image.
+///
+/// Returns `None` if `data` is not a well-formed JPEG.
+pub fn strip_jpeg(data: &[u8], options: &StripOptions) -> Option<Vec<u8>> {
+    let mut rest = data.strip_prefix(b"\xff\xd8")?;
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(b"\xff\xd8");
+    loop {
+        let (&[0xff, marker], after) = rest.split_first_chunk::<2>()? else {
+            return None;
+        };
+        match marker {
+            // Fill byte before a marker.
+            0xff => {
+                rest = &rest[1..];
+                continue;
+            }
+            // Markers without a payload.
+            0x01 | 0xd0..=0xd7 => {
+                stripped.extend_from_slice(&rest[..2]);
+                rest = after;
+                continue;
+            }
+            // End of image.
+            0xd9 => {
+                stripped.extend_from_slice(rest);
+                return Some(stripped);
+            }
+            _ => {}
+        }
+        let length = usize::from(u16::from_be_bytes(*after.first_chunk::<2>()?));
+        let segment = rest.get(..2 + length)?;
+        if marker == 0xda {
+            // Start of scan: the entropy coded data follows, which is copied as is.
+            stripped.extend_from_slice(rest);
+            return Some(stripped);
+        }
+        if !options.strip_jpeg_segment(marker, segment.get(4..)?) {
+            stripped.extend_from_slice(segment);
+        }
+        rest = &rest[2 + length..];
+    }
+}
+
+/// Remove the parts selected by `options` from a PNG image.
+///
+/// Returns `None` if `data` is not a well-formed PNG.
+pub fn strip_png(data: &[u8], options: &StripOptions) -> Option<Vec<u8>> {
+    let mut rest = data.strip_prefix(PNG_SIGNATURE)?;
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(PNG_SIGNATURE);
+    while !rest.is_empty() {
+        let length = usize::try_from(u32::from_be_bytes(*rest.first_chunk::<4>()?)).ok()?;
+        let chunk = rest.get(..12 + length)?;
+        if !options.strip_png_chunk(&chunk[4..8]) {
+            stripped.extend_from_slice(chunk);
+        }
+        rest = &rest[12 + length..];
+    }
+    Some(stripped)
+}
+
+/// A resolver that removes parts of the JPEG and PNG images resolved by the inner resolver, such
+/// as ICC profiles, before they are rendered.
+///
+/// Images that can't be parsed are passed through unchanged. Bodies stored by a caching inner
+/// resolver are not changed; wrap its store in a [`StripStore`] to strip them before caching.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+/// use usvg_remote_resolvers::strip::{StripOptions, StripResolver};
+///
+/// let resolver = StripResolver::new(DefaultResolver, StripOptions::new().with_icc_profile());
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StripResolver<T> {
+    inner: T,
+    options: StripOptions,
+}
+
+impl<T> StripResolver<T> {
+    /// Create a new `StripResolver` removing the parts selected by `options`.
+    pub fn new(inner: T, options: StripOptions) -> Self {
+        Self { inner, options }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for StripResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        Some(match self.inner.get_image_kind(href, options)? {
+            ImageKind::JPEG(data) => ImageKind::JPEG(
+                strip_jpeg(&data, &self.options)
+                    .map(std::sync::Arc::new)
+                    .unwrap_or(data),
+            ),
+            ImageKind::PNG(data) => ImageKind::PNG(
+                strip_png(&data, &self.options)
+                    .map(std::sync::Arc::new)
+                    .unwrap_or(data),
+            ),
+            kind => kind,
+        })
+    }
+}
+
+/// A [`HttpCacheStore`](crate::reqwest_blocking::HttpCacheStore) that removes the parts selected by
+/// [`StripOptions`] from JPEG and PNG bodies before they are stored, so the removed metadata is
+/// never kept in the cache.
+///
+/// ```
+/// use usvg_remote_resolvers::reqwest_blocking::{BlockingReqwestResolver, MemoryHttpCacheStore};
+/// use usvg_remote_resolvers::strip::{StripOptions, StripStore};
+///
+/// let store = StripStore::new(MemoryHttpCacheStore::new(), StripOptions::new().with_metadata());
+/// let resolver = BlockingReqwestResolver::default().with_cache(store);
+/// ```
+#[cfg(feature = "reqwest_blocking")]
+#[derive(Debug)]
+pub struct StripStore<S> {
+    inner: S,
+    options: StripOptions,
+}
+
+#[cfg(feature = "reqwest_blocking")]
+impl<S> StripStore<S> {
+    /// Create a new `StripStore` removing the parts selected by `options`.
+    pub fn new(inner: S, options: StripOptions) -> Self {
+        Self { inner, options }
+    }
+
+    /// Get the inner store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "reqwest_blocking")]
+impl<S: crate::reqwest_blocking::HttpCacheStore> crate::reqwest_blocking::HttpCacheStore
+    for StripStore<S>
+{
+    fn get(&self, key: &str) -> Option<crate::reqwest_blocking::HttpCacheEntry> {
+        self.inner.get(key)
+    }
+    fn put(&self, key: &str, mut entry: crate::reqwest_blocking::HttpCacheEntry) {
+        if let Some(body) = strip(&entry.body, &self.options) {
+            entry.body = std::sync::Arc::new(body);
+        }
+        self.inner.put(key, entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        // The CRC is not checked when stripping.
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    #[test]
+    fn strips_jpeg_icc_profile() {
+        let mut jpeg = b"\xff\xd8\xff\xe0\x00\x04JF".to_vec();
+        jpeg.extend_from_slice(b"\xff\xe2\x00\x0fICC_PROFILE\0\x01");
+        jpeg.extend_from_slice(b"\xff\xda\x00\x02\x12\x34\xff\xd9");
+
+        let options = StripOptions::new().with_icc_profile();
+        assert_eq!(
+            strip_jpeg(&jpeg, &options).unwrap(),
+            b"\xff\xd8\xff\xe0\x00\x04JF\xff\xda\x00\x02\x12\x34\xff\xd9"
+        );
+        assert_eq!(strip_jpeg(&jpeg, &StripOptions::new()).unwrap(), jpeg);
+        assert_eq!(strip_jpeg(&jpeg[..10], &options), None);
+    }
+
+    #[test]
+    fn strips_png_icc_profile() {
+        let png = std::fs::read("./test_data/gray.png").unwrap();
+        let mut tagged = png[..8].to_vec();
+        let ihdr_end = 8 + 12 + 13;
+        tagged.extend_from_slice(&png[8..ihdr_end]);
+        tagged.extend(png_chunk(b"iCCP", b"profile\0\0data"));
+        tagged.extend_from_slice(&png[ihdr_end..]);
+
+        let stripped = strip_png(&tagged, &StripOptions::new().with_icc_profile()).unwrap();
+        assert_eq!(stripped, png);
+    }
+
+    #[test]
+    fn strips_metadata() {
+        let mut jpeg = b"\xff\xd8\xff\xe1\x00\x08Exif\0\0".to_vec();
+        jpeg.extend_from_slice(b"\xff\xe2\x00\x0fICC_PROFILE\0\x01");
+        jpeg.extend_from_slice(b"\xff\xda\x00\x02\xff\xd9");
+        assert_eq!(
+            strip(&jpeg, &StripOptions::new().with_metadata()).unwrap(),
+            b"\xff\xd8\xff\xe2\x00\x0fICC_PROFILE\0\x01\xff\xda\x00\x02\xff\xd9"
+        );
+
+        let png = std::fs::read("./test_data/gray.png").unwrap();
+        let mut tagged = png[..8].to_vec();
+        tagged.extend(png_chunk(b"eXIf", b"MM\0*"));
+        tagged.extend(png_chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x/>"));
+        tagged.extend_from_slice(&png[8..]);
+        assert_eq!(
+            strip(&tagged, &StripOptions::new().with_metadata()).unwrap(),
+            png
+        );
+        assert_eq!(strip(&tagged, &StripOptions::new()).unwrap(), tagged);
+    }
+}
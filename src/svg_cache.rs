@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use usvg::{ImageKind, Options};
+
+use crate::decoded_cache::{ImageStore, LruImageStore};
+use crate::normalize::UrlNormalizer;
+use crate::HrefStringResolver;
+
+/// The number of trees kept by an [`SvgTreeCacheResolver`] unless set otherwise.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// A fingerprint of the [`Options`] fields that change the result of parsing a nested SVG.
+///
+/// Two [`Options`] with equal fingerprints produce the same [`Tree`](`usvg::Tree`) for the same
+/// data, so this can be used as part of the key of any cache of parsed trees.
+///
+/// The font database, font resolver and image resolvers are not part of the fingerprint; use
+/// separate caches when rendering with different fonts or resolvers.
+///
+/// ```
+/// use usvg_remote_resolvers::svg_cache::OptionsFingerprint;
+///
+/// let low = usvg::Options::default();
+/// let high = usvg::Options { dpi: 300.0, ..usvg::Options::default() };
+/// assert_ne!(OptionsFingerprint::new(&low), OptionsFingerprint::new(&high));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OptionsFingerprint {
+    resources_dir: Option<std::path::PathBuf>,
+    dpi: u32,
+    font_family: String,
+    font_size: u32,
+    languages: Vec<String>,
+    shape_rendering: u8,
+    text_rendering: u8,
+    image_rendering: u8,
+    default_size: (u32, u32),
+    style_sheet: Option<String>,
+}
+
+impl OptionsFingerprint {
+    /// Compute the fingerprint of the given [`Options`].
+    pub fn new(options: &Options) -> Self {
+        Self {
+            resources_dir: options.resources_dir.clone(),
+            dpi: options.dpi.to_bits(),
+            font_family: options.font_family.clone(),
+            font_size: options.font_size.to_bits(),
+            languages: options.languages.clone(),
+            shape_rendering: options.shape_rendering as u8,
+            text_rendering: options.text_rendering as u8,
+            image_rendering: options.image_rendering as u8,
+            default_size: (
+                options.default_size.width().to_bits(),
+                options.default_size.height().to_bits(),
+            ),
+            style_sheet: options.style_sheet.clone(),
+        }
+    }
+}
+
+impl From<&Options<'_>> for OptionsFingerprint {
+    fn from(options: &Options) -> Self {
+        Self::new(options)
+    }
+}
+
+/// A resolver that caches the parsed [`Tree`](`usvg::Tree`) of nested SVG images.
+///
+/// For [`ImageKind::SVG`], the expensive part is parsing the fetched data, which HTTP caches do
+/// not help with. This wrapper keeps the parsed tree keyed by the `href` and the [`OptionsFingerprint`]
+/// of the [`Options`], so the same remote SVG is parsed only once across renders. At most 256
+/// trees are kept by default, evicting the least recently used ones; see
+/// [`with_capacity`](Self::with_capacity).
+///
+/// Raster images are passed through from the inner resolver without caching.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver, svg_cache::SvgTreeCacheResolver};
+///
+/// let resolver = SvgTreeCacheResolver::new(DefaultResolver);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug)]
+pub struct SvgTreeCacheResolver<T> {
+    inner: T,
+    cache: LruImageStore,
+    normalizer: Option<Arc<dyn UrlNormalizer>>,
+}
+
+impl<T> SvgTreeCacheResolver<T> {
+    /// Create a new `SvgTreeCacheResolver` wrapping the given resolver.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            cache: LruImageStore::new(DEFAULT_CAPACITY),
+            normalizer: None,
+        }
+    }
+
+    /// Keep at most `capacity` trees, evicting the least recently used ones. This drops the trees
+    /// cached so far.
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        Self {
+            cache: LruImageStore::new(capacity),
+            ..self
+        }
+    }
+
+    /// Key the cached trees by the `href` normalized with `normalizer`.
+    pub fn with_normalizer(mut self, normalizer: impl UrlNormalizer + 'static) -> Self {
+        self.normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Number of cached trees.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if no trees are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all cached trees.
+    pub fn clear(&self) {
+        self.cache.clear();
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for SvgTreeCacheResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let key = crate::normalize::normalize(self.normalizer.as_ref(), href);
+        let fingerprint = OptionsFingerprint::new(options);
+        if let Some(kind) = self.cache.get(&key, &fingerprint) {
+            crate::events::record_cache_hit();
+            return Some(kind);
+        }
+        let kind = self.inner.get_image_kind(href, options)?;
+        if matches!(kind, ImageKind::SVG(_)) {
+            self.cache.put(&key, &fingerprint, kind.clone());
+        }
+        Some(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountingSvgResolver(Arc<AtomicUsize>);
+
+    impl HrefStringResolver<'_> for CountingSvgResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, _: &str, options: &Options) -> Option<ImageKind> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let tree = usvg::Tree::from_str(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#,
+                options,
+            )
+            .ok()?;
+            Some(ImageKind::SVG(tree))
+        }
+    }
+
+    #[test]
+    fn caches_parsed_tree() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let resolver = SvgTreeCacheResolver::new(CountingSvgResolver(count.clone()));
+        let options = Options::default();
+
+        assert!(resolver
+            .get_image_kind("https://example.com/a.svg", &options)
+            .is_some());
+        assert!(resolver
+            .get_image_kind("https://example.com/a.svg", &options)
+            .is_some());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(resolver.len(), 1);
+
+        assert!(resolver
+            .get_image_kind("https://example.com/b.svg", &options)
+            .is_some());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        resolver.clear();
+        assert!(resolver.is_empty());
+    }
+
+    #[test]
+    fn options_are_part_of_key() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let resolver = SvgTreeCacheResolver::new(CountingSvgResolver(count.clone()));
+        let options = Options::default();
+        let high_dpi = Options {
+            dpi: 300.0,
+            ..Options::default()
+        };
+
+        resolver.get_image_kind("https://example.com/a.svg", &options);
+        resolver.get_image_kind("https://example.com/a.svg", &high_dpi);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_trees() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let resolver =
+            SvgTreeCacheResolver::new(CountingSvgResolver(count.clone())).with_capacity(2);
+        let options = Options::default();
+
+        for href in ["a.svg", "b.svg", "a.svg", "c.svg", "a.svg"] {
+            resolver.get_image_kind(href, &options);
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(resolver.len(), 2);
+        resolver.get_image_kind("b.svg", &options);
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+}
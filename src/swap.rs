@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::sync::{Arc, RwLock};
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+type SharedResolver = Arc<dyn HrefStringResolver<'static>>;
+
+thread_local! {
+    /// The resolvers overriding a [`SwappableResolver`] on this thread, keyed by the address of
+    /// its shared slot, innermost last.
+    static OVERRIDES: RefCell<Vec<(usize, SharedResolver)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A resolver whose inner resolver can be replaced at runtime, e.g. to rotate credentials or
+/// switch CDNs in a long-lived service without rebuilding its [`Options`].
+///
+/// Clones share the same inner resolver, so install one clone into the options and keep another
+/// to [`replace`](Self::replace) it. Each resolution uses the resolver that was current when it
+/// started, so the ones in progress finish with the old resolver while new ones use the new one.
+///
+/// The inner resolver can also be overridden for a single parse on the current thread with
+/// [`scope`](Self::scope), e.g. to apply per-job credentials on top of shared options.
+///
+/// ```
+/// use usvg_remote_resolvers::memory::MemoryResolver;
+/// use usvg_remote_resolvers::swap::SwappableResolver;
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let resolver = SwappableResolver::new(DefaultResolver);
+/// let mut options = usvg::Options::default();
+/// resolver.clone().set_into_options(&mut options);
+///
+/// // Later, while `options` is in use:
+/// resolver.replace(MemoryResolver::new());
+/// ```
+#[derive(Clone)]
+pub struct SwappableResolver {
+    current: Arc<RwLock<SharedResolver>>,
+}
+
+impl std::fmt::Debug for SwappableResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SwappableResolver").finish_non_exhaustive()
+    }
+}
+
+impl SwappableResolver {
+    /// Create a new `SwappableResolver` starting with `resolver`.
+    pub fn new(resolver: impl HrefStringResolver<'static> + 'static) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(resolver))),
+        }
+    }
+
+    /// Replace the inner resolver of this resolver and all its clones, returning the previous one.
+    pub fn replace(&self, resolver: impl HrefStringResolver<'static> + 'static) -> SharedResolver {
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, Arc::new(resolver))
+    }
+
+    /// Get the current inner resolver, which is the innermost override on this thread if there is
+    /// one.
+    pub fn current(&self) -> SharedResolver {
+        let overridden = OVERRIDES.with(|overrides| {
+            let overrides = overrides.borrow();
+            overrides
+                .iter()
+                .rev()
+                .find(|(key, _)| *key == self.key())
+                .map(|(_, resolver)| resolver.clone())
+        });
+        overridden.unwrap_or_else(|| {
+            self.current
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone()
+        })
+    }
+
+    /// Run `f` with `resolver` in place of the inner resolver of this resolver and all its clones,
+    /// on the current thread only.
+    ///
+    /// Other threads, and [`replace`](Self::replace), are not affected. The override is removed
+    /// afterwards, even if `f` panics.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::memory::MemoryResolver;
+    /// use usvg_remote_resolvers::swap::SwappableResolver;
+    /// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+    ///
+    /// let resolver = SwappableResolver::new(DefaultResolver);
+    /// let mut options = usvg::Options::default();
+    /// resolver.clone().set_into_options(&mut options);
+    ///
+    /// let job = MemoryResolver::new().insert_png("logo.png", Vec::new());
+    /// resolver.scope(job, || {
+    ///     assert!(resolver.is_target("logo.png"));
+    ///     // let tree = usvg::Tree::from_str(svg, &options);
+    /// });
+    /// ```
+    pub fn scope<R>(
+        &self,
+        resolver: impl HrefStringResolver<'static> + 'static,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        struct Restore;
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                OVERRIDES.with(|overrides| overrides.borrow_mut().pop());
+            }
+        }
+
+        OVERRIDES.with(|overrides| {
+            overrides
+                .borrow_mut()
+                .push((self.key(), Arc::new(resolver)))
+        });
+        let _restore = Restore;
+        f()
+    }
+
+    /// The key of the slot shared by this resolver and its clones.
+    fn key(&self) -> usize {
+        Arc::as_ptr(&self.current) as *const () as usize
+    }
+}
+
+impl<'a> HrefStringResolver<'a> for SwappableResolver {
+    fn is_target(&self, href: &str) -> bool {
+        self.current().is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&*self.current());
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        self.current().get_image_kind(href, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryResolver;
+
+    #[test]
+    fn replaces_installed_resolver() {
+        let resolver = SwappableResolver::new(MemoryResolver::new());
+        let mut options = Options::default();
+        resolver.clone().set_into_options(&mut options);
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><image href="gray.png"/></svg>"#;
+        let has_image = |options: &Options| {
+            usvg::Tree::from_str(svg, options)
+                .unwrap()
+                .root()
+                .has_children()
+        };
+
+        assert!(!has_image(&options));
+        resolver.replace(
+            MemoryResolver::new().insert_png("gray.png", include_bytes!("../test_data/gray.png")),
+        );
+        assert!(has_image(&options));
+        assert!(resolver.current().is_target("gray.png"));
+
+        resolver.scope(MemoryResolver::new(), || {
+            assert!(!has_image(&options));
+            std::thread::scope(|s| {
+                s.spawn(|| assert!(has_image(&options)));
+            });
+        });
+        assert!(has_image(&options));
+    }
+}
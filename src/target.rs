@@ -0,0 +1,569 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+
+use usvg::{ImageKind, Options};
+
+use crate::normalize::UrlNormalizer;
+use crate::HrefStringResolver;
+
+type TargetPredicate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Decides which `href`s an HTTP resolver handles.
+///
+/// By default, any `http://` or `https://` URL is accepted. The accepted schemes can be changed,
+/// the host can be required to match one of a set of suffixes or to match none of another, ports
+/// can be allowed or denied, file extensions can be denied, hosts can be blocked at runtime with a
+/// [`HostKillSwitch`], and an arbitrary predicate can be added. An `href` is a target only if it passes all of the
+/// configured checks, so a rejected `href` is never connected to. With a
+/// [`UrlNormalizer`], the checks see the normalized `href`.
+///
+/// The HTTP resolvers also reject a response if it was redirected to a URL their filter does not
+/// accept. To not follow such redirects at all, set the filter into their clients with
+/// `ClientConfig::with_redirect_target`.
+///
+/// ```
+/// use usvg_remote_resolvers::target::TargetFilter;
+///
+/// let filter = TargetFilter::new()
+///     .with_schemes(["https"])
+///     .with_host_suffix("assets.example.com")
+///     .with_ports([443])
+///     .with_predicate(|href| href.ends_with(".png"));
+///
+/// assert!(filter.matches("https://assets.example.com/logo.png"));
+/// assert!(filter.matches("https://eu.assets.example.com/logo.png"));
+/// assert!(!filter.matches("http://assets.example.com/logo.png"));
+/// assert!(!filter.matches("https://example.com/logo.png"));
+/// assert!(!filter.matches("https://assets.example.com:8443/logo.png"));
+/// assert!(!filter.matches("https://assets.example.com/logo.svg"));
+/// ```
+#[derive(Clone)]
+pub struct TargetFilter {
+    schemes: Vec<String>,
+    host_suffixes: Vec<String>,
+    denied_host_suffixes: Vec<String>,
+    allowed_ports: Vec<u16>,
+    denied_ports: Vec<u16>,
+    denied_extensions: Vec<String>,
+    predicate: Option<TargetPredicate>,
+    normalizer: Option<Arc<dyn UrlNormalizer>>,
+    kill_switch: Option<HostKillSwitch>,
+}
+
+impl Default for TargetFilter {
+    fn default() -> Self {
+        Self {
+            schemes: vec!["http".to_string(), "https".to_string()],
+            host_suffixes: Vec::new(),
+            denied_host_suffixes: Vec::new(),
+            allowed_ports: Vec::new(),
+            denied_ports: Vec::new(),
+            denied_extensions: Vec::new(),
+            predicate: None,
+            normalizer: None,
+            kill_switch: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for TargetFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TargetFilter")
+            .field("schemes", &self.schemes)
+            .field("host_suffixes", &self.host_suffixes)
+            .field("denied_host_suffixes", &self.denied_host_suffixes)
+            .field("allowed_ports", &self.allowed_ports)
+            .field("denied_ports", &self.denied_ports)
+            .field("denied_extensions", &self.denied_extensions)
+            .field("predicate", &self.predicate.is_some())
+            .field("normalizer", &self.normalizer)
+            .field("kill_switch", &self.kill_switch)
+            .finish()
+    }
+}
+
+impl TargetFilter {
+    /// Extensions of files that are clearly not images: executables, scripts, documents and
+    /// archives, for use with [`with_denied_extensions`](`Self::with_denied_extensions`).
+    pub const NON_IMAGE_EXTENSIONS: &'static [&'static str] = &[
+        "exe", "dll", "so", "dylib", "msi", "bat", "cmd", "sh", "ps1", "js", "mjs", "css", "html",
+        "htm", "php", "asp", "aspx", "jsp", "json", "pdf", "doc", "docx", "xls", "xlsx", "zip",
+        "tar", "gz", "7z", "rar", "jar", "apk", "iso", "dmg", "mp3", "mp4", "wasm",
+    ];
+
+    /// Create a new `TargetFilter` accepting any `http://` or `https://` URL.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the accepted schemes (e.g. `["https"]`). Schemes are compared case-insensitively.
+    pub fn with_schemes<I, S>(mut self, schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.schemes = schemes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Require the host to be `suffix` or a subdomain of it.
+    ///
+    /// Internationalized hosts match whether written in Unicode or punycode (`xn--`), in `suffix`
+    /// as in the URL. Can be called multiple times; the host has to match any one of the suffixes.
+    pub fn with_host_suffix(mut self, suffix: impl Into<String>) -> Self {
+        let suffix = suffix.into();
+        self.host_suffixes.push(ascii_host(&suffix));
+        self
+    }
+
+    /// Reject URLs whose host is `suffix` or a subdomain of it, even if it is allowed by
+    /// [`with_host_suffix`](`Self::with_host_suffix`).
+    ///
+    /// Can be called multiple times; the host is rejected if it matches any one of the suffixes.
+    pub fn with_denied_host_suffix(mut self, suffix: impl Into<String>) -> Self {
+        let suffix = suffix.into();
+        self.denied_host_suffixes.push(ascii_host(&suffix));
+        self
+    }
+
+    /// Only accept URLs whose port is one of `ports` (e.g. `[443]`).
+    ///
+    /// The default port of the scheme is used if the URL has no explicit port.
+    pub fn with_ports(mut self, ports: impl IntoIterator<Item = u16>) -> Self {
+        self.allowed_ports = ports.into_iter().collect();
+        self
+    }
+
+    /// Reject URLs whose port is one of `ports`, even if it is allowed by
+    /// [`with_ports`](`Self::with_ports`).
+    ///
+    /// Redirects to a denied port, e.g. of an internal service, are rejected as described for
+    /// [`TargetFilter`].
+    pub fn with_denied_ports(mut self, ports: impl IntoIterator<Item = u16>) -> Self {
+        self.denied_ports.extend(ports);
+        self
+    }
+
+    /// Reject URLs whose path ends in one of the file extensions `extensions` (e.g.
+    /// `["exe", "js"]`), so they are refused without being fetched.
+    ///
+    /// Extensions are given without the leading dot and compared case-insensitively. The query
+    /// and fragment of the URL are not part of its path, so `/download?file=a.exe` is not
+    /// rejected. Can be called multiple times to add more extensions.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::target::TargetFilter;
+    ///
+    /// let filter =
+    ///     TargetFilter::new().with_denied_extensions(TargetFilter::NON_IMAGE_EXTENSIONS.iter().copied());
+    /// assert!(!filter.matches("https://example.com/setup.EXE"));
+    /// assert!(!filter.matches("https://example.com/app.js?v=2"));
+    /// assert!(filter.matches("https://example.com/logo.png"));
+    /// ```
+    pub fn with_denied_extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.denied_extensions.extend(
+            extensions
+                .into_iter()
+                .map(|ext| ext.into().trim_start_matches('.').to_ascii_lowercase()),
+        );
+        self
+    }
+
+    /// Require the `href` to pass the given predicate, in addition to the other checks.
+    pub fn with_predicate(
+        mut self,
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Normalize `href`s with `normalizer` before checking them.
+    pub fn with_normalizer(mut self, normalizer: impl UrlNormalizer + 'static) -> Self {
+        self.normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Reject URLs whose host is blocked by `kill_switch` at the time they are checked.
+    pub fn with_kill_switch(mut self, kill_switch: HostKillSwitch) -> Self {
+        self.kill_switch = Some(kill_switch);
+        self
+    }
+
+    /// Check if the `href` is accepted by this filter.
+    pub fn matches(&self, href: &str) -> bool {
+        let href = crate::normalize::normalize(self.normalizer.as_ref(), href);
+        let href = &*href;
+        let Some((scheme, _)) = href.split_once("://") else {
+            return false;
+        };
+        if !self.schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)) {
+            return false;
+        }
+        if !self.denied_extensions.is_empty() && self.has_denied_extension(href) {
+            return false;
+        }
+        if !self.host_suffixes.is_empty()
+            || !self.denied_host_suffixes.is_empty()
+            || !self.allowed_ports.is_empty()
+            || !self.denied_ports.is_empty()
+            || self.kill_switch.as_ref().is_some_and(|s| !s.is_empty())
+        {
+            let Ok(url) = url::Url::parse(href) else {
+                return false;
+            };
+            if let (Some(kill_switch), Some(host)) = (&self.kill_switch, url.host_str()) {
+                if kill_switch.is_blocked(host) {
+                    return false;
+                }
+            }
+            if (!self.host_suffixes.is_empty() || !self.denied_host_suffixes.is_empty())
+                && !self.matches_host(&url)
+            {
+                return false;
+            }
+            if !self.matches_port(&url) {
+                return false;
+            }
+        }
+        self.predicate.as_ref().is_none_or(|p| p(href))
+    }
+
+    /// Check that the response to `href`, served from `final_url` after any redirects, comes
+    /// from a URL accepted by this filter, logging a warning if it does not.
+    #[cfg(any(
+        feature = "reqwest",
+        feature = "reqwest_blocking",
+        feature = "reqwest_http_cache"
+    ))]
+    pub(crate) fn accepts_final_url(&self, href: &str, final_url: &str) -> bool {
+        if final_url == href || self.matches(final_url) {
+            return true;
+        }
+        crate::utils::log_warn!(
+            "rejected '{}', redirected to '{}'",
+            crate::redact::redact(href),
+            crate::redact::redact(final_url)
+        );
+        false
+    }
+
+    fn has_denied_extension(&self, href: &str) -> bool {
+        crate::utils::href_extension(href).is_some_and(|ext| self.denied_extensions.contains(&ext))
+    }
+
+    fn matches_port(&self, url: &url::Url) -> bool {
+        let port = url.port_or_known_default();
+        if self.denied_ports.iter().any(|p| Some(*p) == port) {
+            return false;
+        }
+        self.allowed_ports.is_empty() || self.allowed_ports.iter().any(|p| Some(*p) == port)
+    }
+
+    fn matches_host(&self, url: &url::Url) -> bool {
+        let Some(host) = url.host_str().map(str::to_ascii_lowercase) else {
+            return false;
+        };
+        if self
+            .denied_host_suffixes
+            .iter()
+            .any(|suffix| host_has_suffix(&host, suffix))
+        {
+            return false;
+        }
+        self.host_suffixes.is_empty()
+            || self
+                .host_suffixes
+                .iter()
+                .any(|suffix| host_has_suffix(&host, suffix))
+    }
+}
+
+/// A set of blocked hosts shared by the [`TargetFilter`]s it is set into, to stop fetching from a
+/// misbehaving host at runtime.
+///
+/// Clones share the same set, and changes take effect for the next `href` checked by any of the
+/// filters, without rebuilding the resolvers. Blocking a host also blocks its subdomains.
+///
+/// ```
+/// use usvg_remote_resolvers::target::{HostKillSwitch, TargetFilter};
+///
+/// let kill_switch = HostKillSwitch::new();
+/// let filter = TargetFilter::new().with_kill_switch(kill_switch.clone());
+/// assert!(filter.matches("https://cdn.example.com/logo.png"));
+///
+/// kill_switch.block("example.com");
+/// assert!(!filter.matches("https://cdn.example.com/logo.png"));
+/// assert_eq!(kill_switch.blocked(), ["example.com"]);
+///
+/// kill_switch.unblock("example.com");
+/// assert!(filter.matches("https://cdn.example.com/logo.png"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HostKillSwitch(Arc<RwLock<BTreeSet<String>>>);
+
+impl HostKillSwitch {
+    /// Create a new `HostKillSwitch` blocking no hosts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Block `host` and its subdomains. Returns `false` if it was already blocked.
+    pub fn block(&self, host: &str) -> bool {
+        self.write().insert(ascii_host(host))
+    }
+
+    /// Unblock `host`, blocked before with [`block`](Self::block). Returns `false` if it was not
+    /// blocked.
+    pub fn unblock(&self, host: &str) -> bool {
+        self.write().remove(&ascii_host(host))
+    }
+
+    /// Get the blocked hosts, sorted.
+    pub fn blocked(&self) -> Vec<String> {
+        self.read().iter().cloned().collect()
+    }
+
+    /// Check if `host` is blocked, either itself or as a subdomain of a blocked host.
+    pub fn is_blocked(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.read()
+            .iter()
+            .any(|blocked| host_has_suffix(&host, blocked))
+    }
+
+    /// Returns `true` if no host is blocked.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeSet<String>> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeSet<String>> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A resolver that rejects the `href`s not accepted by a [`TargetFilter`] before the inner
+/// resolver sees them.
+///
+/// Use it to restrict the outbound requests of server-side renders of untrusted SVG documents.
+/// Unlike the target filter of an HTTP resolver, which only makes it skip an `href` so the next
+/// resolver of a [`with_fallback`](`HrefStringResolver::with_fallback`) chain may handle it, this
+/// claims every `href` the inner resolver would handle and resolves the rejected ones to nothing.
+/// Wrap the whole chain to make sure no layer fetches a rejected `href`.
+///
+/// The policy only sees the `href`s, not where the HTTP resolvers are redirected to. Build their
+/// clients with the policy as `ClientConfig::with_redirect_target` to apply it to redirects too.
+///
+/// ```
+/// use usvg_remote_resolvers::target::{PolicyResolver, TargetFilter};
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+///
+/// let policy = TargetFilter::new()
+///     .with_schemes(["https"])
+///     .with_host_suffix("example.com")
+///     .with_denied_host_suffix("internal.example.com");
+/// let resolver = PolicyResolver::new(DefaultResolver, policy);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PolicyResolver<T> {
+    inner: T,
+    policy: TargetFilter,
+}
+
+impl<T> PolicyResolver<T> {
+    /// Create a new `PolicyResolver` passing the `href`s accepted by `policy` to `inner`.
+    pub fn new(inner: T, policy: TargetFilter) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get the policy of this resolver.
+    pub fn policy(&self) -> &TargetFilter {
+        &self.policy
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for PolicyResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        if self.inner.is_target(trace.href()) && !self.policy.matches(trace.href()) {
+            trace.push::<Self>(crate::explain::Outcome::Denied(
+                "rejected by policy".to_string(),
+            ));
+        } else {
+            trace.wrap::<Self>(&self.inner);
+        }
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        if !self.policy.matches(href) {
+            crate::utils::log_warn!("rejected by policy: '{}'", crate::redact::redact(href));
+            return None;
+        }
+        self.inner.get_image_kind(href, options)
+    }
+}
+
+/// Get the content type of the first of `overrides` whose filter accepts `href`, or `header` if
+/// none does.
+#[cfg(any(
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "reqwest_http_cache",
+    feature = "hyper"
+))]
+pub(crate) fn override_content_type<'a>(
+    overrides: &'a [(TargetFilter, String)],
+    href: &str,
+    header: Option<&'a str>,
+) -> Option<&'a str> {
+    overrides
+        .iter()
+        .find(|(filter, _)| filter.matches(href))
+        .map(|(_, content_type)| content_type.as_str())
+        .or(header)
+}
+
+/// Convert a host (suffix) like `.Bücher.example` to the lowercase ASCII form of the hosts of
+/// parsed URLs, `xn--bcher-kva.example`.
+fn ascii_host(suffix: &str) -> String {
+    let suffix = suffix.trim_start_matches('.');
+    match url::Host::parse(suffix) {
+        Ok(url::Host::Domain(domain)) => domain,
+        _ => suffix.to_ascii_lowercase(),
+    }
+}
+
+/// Whether the lowercase `host` is `suffix` or a subdomain of it.
+pub(crate) fn host_has_suffix(host: &str, suffix: &str) -> bool {
+    host == suffix
+        || host
+            .strip_suffix(suffix)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_matches_http() {
+        let filter = TargetFilter::default();
+        assert!(filter.matches("https://example.com/a.png"));
+        assert!(filter.matches("http://example.com/a.png"));
+        assert!(filter.matches("HTTPS://example.com/a.png"));
+        assert!(!filter.matches("file:///a.png"));
+        assert!(!filter.matches("./a.png"));
+    }
+
+    #[test]
+    fn host_suffix() {
+        let filter = TargetFilter::new().with_host_suffix(".example.com");
+        assert!(filter.matches("https://example.com/a.png"));
+        assert!(filter.matches("https://cdn.Example.com/a.png"));
+        assert!(!filter.matches("https://badexample.com/a.png"));
+        assert!(!filter.matches("https://example.com.evil.org/a.png"));
+
+        let filter = TargetFilter::new().with_host_suffix("Bücher.example");
+        assert!(filter.matches("https://cdn.bücher.example/a.png"));
+        assert!(filter.matches("https://xn--bcher-kva.example/a.png"));
+        assert!(!filter.matches("https://bucher.example/a.png"));
+    }
+
+    #[test]
+    fn ports() {
+        let filter = TargetFilter::new().with_ports([443, 8443]);
+        assert!(filter.matches("https://example.com/a.png"));
+        assert!(filter.matches("https://example.com:8443/a.png"));
+        assert!(!filter.matches("http://example.com/a.png"));
+        assert!(!filter.matches("https://example.com:9000/a.png"));
+
+        let filter = TargetFilter::new().with_denied_ports([22, 6379]);
+        assert!(filter.matches("http://example.com:8080/a.png"));
+        assert!(!filter.matches("http://example.com:6379/a.png"));
+    }
+
+    #[test]
+    fn denied_extensions() {
+        let filter = TargetFilter::new().with_denied_extensions([".exe", "JS", "html"]);
+        assert!(filter.matches("https://example.com/a.png"));
+        assert!(filter.matches("https://example.exe"));
+        assert!(filter.matches("https://example.com/a.exe.d/logo"));
+        assert!(filter.matches("https://example.com/download?file=a.exe"));
+        assert!(!filter.matches("https://example.com/setup.exe"));
+        assert!(!filter.matches("https://example.com/app.Js#main"));
+        assert!(!filter.matches("https://example.com/index.HTML?a=b"));
+    }
+
+    #[test]
+    fn policy_rejects_denied_hosts() {
+        struct AnyResolver;
+
+        impl HrefStringResolver<'_> for AnyResolver {
+            fn is_target(&self, _: &str) -> bool {
+                true
+            }
+            fn get_image_kind(&self, _: &str, _: &Options) -> Option<ImageKind> {
+                Some(ImageKind::PNG(Arc::new(Vec::new())))
+            }
+        }
+
+        let policy = TargetFilter::new()
+            .with_schemes(["https"])
+            .with_denied_host_suffix("169.254.169.254")
+            .with_denied_host_suffix("internal.example.com");
+        assert!(policy.matches("https://cdn.example.com/a.png"));
+        assert!(!policy.matches("https://db.Internal.example.com/a.png"));
+
+        let resolver = PolicyResolver::new(AnyResolver, policy);
+        let options = Options::default();
+        assert!(resolver.is_target("http://169.254.169.254/latest/meta-data"));
+        for href in [
+            "http://example.com/a.png",
+            "https://169.254.169.254/latest/meta-data",
+            "https://internal.example.com/a.png",
+        ] {
+            assert!(resolver.get_image_kind(href, &options).is_none(), "{href}");
+        }
+        assert!(resolver
+            .get_image_kind("https://cdn.example.com/a.png", &options)
+            .is_some());
+    }
+
+    #[test]
+    fn kill_switch_blocks_at_runtime() {
+        let kill_switch = HostKillSwitch::new();
+        let inner = crate::memory::MemoryResolver::new()
+            .insert_png("https://cdn.example.com/a.png", Vec::new())
+            .insert_png("https://example.org/a.png", Vec::new());
+        let policy = TargetFilter::new().with_kill_switch(kill_switch.clone());
+        let resolver = PolicyResolver::new(inner, policy);
+        let options = Options::default();
+        let resolves = |href| resolver.get_image_kind(href, &options).is_some();
+
+        assert!(resolves("https://cdn.example.com/a.png"));
+        assert!(kill_switch.block(".Example.COM"));
+        assert!(!kill_switch.block("example.com"));
+        assert!(!resolves("https://cdn.example.com/a.png"));
+        assert!(resolves("https://example.org/a.png"));
+
+        assert!(kill_switch.unblock("example.com"));
+        assert!(kill_switch.blocked().is_empty());
+        assert!(resolves("https://cdn.example.com/a.png"));
+    }
+}
@@ -0,0 +1,299 @@
+use std::io::Cursor;
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// Decoded 8-bit RGBA pixels.
+struct Pixels {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+/// Downscale a raster image so that neither side exceeds `max_size` pixels.
+///
+/// The downscaled image is wrapped in an SVG image with the size of the original, so it is laid
+/// out exactly like the original even where the `<image>` has no `width` or `height`. Only the
+/// first frame of animated images is kept.
+///
+/// Returns `None` if `kind` is an SVG image, already fits in `max_size`, can't be decoded, or has
+/// more than 64 megapixels.
+pub fn thumbnail(kind: &ImageKind, max_size: u32) -> Option<ImageKind> {
+    thumbnail_within(kind, max_size, crate::utils::DEFAULT_MAX_PIXELS)
+}
+
+fn thumbnail_within(kind: &ImageKind, max_size: u32, max_pixels: u64) -> Option<ImageKind> {
+    let pixels = match kind {
+        ImageKind::JPEG(data) => decode_jpeg(data, max_pixels),
+        ImageKind::PNG(data) => decode_png(data, max_pixels),
+        ImageKind::GIF(data) => decode_gif(data, max_pixels),
+        ImageKind::WEBP(data) => decode_webp(data, max_pixels),
+        ImageKind::SVG(_) => None,
+    }?;
+    let (width, height) = (pixels.width, pixels.height);
+    if width.max(height) <= max_size.max(1) {
+        return None;
+    }
+    let png = encode_png(&downscale(&pixels, max_size.max(1)))?;
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}"><image width="{width}" height="{height}" preserveAspectRatio="none" href="data:image/png;base64,{}"/></svg>"#,
+        crate::utils::base64(&png)
+    );
+    let tree = usvg::Tree::from_str(&svg, &Options::default()).ok()?;
+    Some(ImageKind::SVG(tree))
+}
+
+fn decode_jpeg(data: &[u8], max_pixels: u64) -> Option<Pixels> {
+    use zune_jpeg::zune_core::{colorspace::ColorSpace, options::DecoderOptions};
+
+    let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA);
+    let mut decoder = zune_jpeg::JpegDecoder::new_with_options(Cursor::new(data), options);
+    decoder.decode_headers().ok()?;
+    let (width, height) = decoder.dimensions()?;
+    crate::utils::pixel_buffer_size(
+        u32::try_from(width).ok()?,
+        u32::try_from(height).ok()?,
+        4,
+        max_pixels,
+    )?;
+    let rgba = decoder.decode().ok()?;
+    let info = decoder.info()?;
+    Some(Pixels {
+        width: u32::from(info.width),
+        height: u32::from(info.height),
+        rgba,
+    })
+}
+
+fn decode_png(data: &[u8], max_pixels: u64) -> Option<Pixels> {
+    let mut decoder = png::Decoder::new(Cursor::new(data));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let (width, height) = reader.info().size();
+    crate::utils::pixel_buffer_size(width, height, 4, max_pixels)?;
+    let mut buffer = vec![0; reader.output_buffer_size()?];
+    let info = reader.next_frame(&mut buffer).ok()?;
+    let buffer = &buffer[..info.buffer_size()];
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buffer.to_vec(),
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return None,
+    };
+    Some(Pixels {
+        width: info.width,
+        height: info.height,
+        rgba,
+    })
+}
+
+fn decode_gif(data: &[u8], max_pixels: u64) -> Option<Pixels> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(data).ok()?;
+    let (width, height) = (usize::from(decoder.width()), usize::from(decoder.height()));
+    let size = crate::utils::pixel_buffer_size(width as u32, height as u32, 4, max_pixels)?;
+    let mut rgba = vec![0; size];
+    let frame = decoder.read_next_frame().ok()??;
+    let (left, top) = (usize::from(frame.left), usize::from(frame.top));
+    let rows = frame.buffer.chunks_exact(usize::from(frame.width) * 4);
+    for (y, row) in (top..height).zip(rows) {
+        for (x, pixel) in (left..width).zip(row.chunks_exact(4)) {
+            let i = (y * width + x) * 4;
+            rgba[i..i + 4].copy_from_slice(pixel);
+        }
+    }
+    Some(Pixels {
+        width: width as u32,
+        height: height as u32,
+        rgba,
+    })
+}
+
+fn decode_webp(data: &[u8], max_pixels: u64) -> Option<Pixels> {
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(data)).ok()?;
+    let (width, height) = decoder.dimensions();
+    crate::utils::pixel_buffer_size(width, height, 4, max_pixels)?;
+    let mut buffer = vec![0; decoder.output_buffer_size()?];
+    decoder.read_image(&mut buffer).ok()?;
+    let rgba = if decoder.has_alpha() {
+        buffer
+    } else {
+        buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect()
+    };
+    Some(Pixels {
+        width,
+        height,
+        rgba,
+    })
+}
+
+/// Downscale `pixels` to fit in `max_size` with a box filter, averaging premultiplied colors so
+/// transparent pixels don't bleed into their neighbors.
+fn downscale(pixels: &Pixels, max_size: u32) -> Pixels {
+    let (width, height) = (pixels.width as usize, pixels.height as usize);
+    let scale = f64::from(max_size) / width.max(height) as f64;
+    let new_width = ((width as f64 * scale).round() as usize).max(1);
+    let new_height = ((height as f64 * scale).round() as usize).max(1);
+    let mut rgba = Vec::with_capacity(new_width * new_height * 4);
+    for dy in 0..new_height {
+        let y0 = dy * height / new_height;
+        let y1 = ((dy + 1) * height / new_height).max(y0 + 1);
+        for dx in 0..new_width {
+            let x0 = dx * width / new_width;
+            let x1 = ((dx + 1) * width / new_width).max(x0 + 1);
+            let mut sum = [0u64; 4];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let p = &pixels.rgba[(y * width + x) * 4..][..4];
+                    let alpha = u64::from(p[3]);
+                    for (sum, c) in sum.iter_mut().zip(&p[..3]) {
+                        *sum += u64::from(*c) * alpha;
+                    }
+                    sum[3] += alpha;
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            if sum[3] == 0 {
+                rgba.extend_from_slice(&[0; 4]);
+            } else {
+                rgba.extend(sum[..3].iter().map(|c| (c / sum[3]) as u8));
+                rgba.push((sum[3] / count) as u8);
+            }
+        }
+    }
+    Pixels {
+        width: new_width as u32,
+        height: new_height as u32,
+        rgba,
+    }
+}
+
+fn encode_png(pixels: &Pixels) -> Option<Vec<u8>> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, pixels.width, pixels.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().ok()?;
+    writer.write_image_data(&pixels.rgba).ok()?;
+    writer.finish().ok()?;
+    Some(png)
+}
+
+/// A resolver that downscales the raster images resolved by the inner resolver, for previews.
+///
+/// Images larger than the maximum size (256 pixels by default) are decoded and downscaled with
+/// [`thumbnail`] before they are handed to usvg, trading fidelity for speed and memory in
+/// thumbnail pipelines. The layout is not affected, since the downscaled image keeps the size of
+/// the original. Use the inner resolver directly for full renders, which get the untouched bytes.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+/// use usvg_remote_resolvers::thumbnail::ThumbnailResolver;
+///
+/// let resolver = ThumbnailResolver::new(DefaultResolver).with_max_size(128);
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ThumbnailResolver<T> {
+    inner: T,
+    max_size: u32,
+    max_pixels: u64,
+}
+
+impl<T> ThumbnailResolver<T> {
+    /// Create a new `ThumbnailResolver` downscaling images to at most 256 pixels.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            max_size: 256,
+            max_pixels: crate::utils::DEFAULT_MAX_PIXELS,
+        }
+    }
+
+    /// Set the maximum width and height of the downscaled images, in pixels.
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Set the maximum number of pixels of the images that are decoded, 64 megapixels by default.
+    ///
+    /// Larger images are passed through without decoding them, as their decoded pixels are
+    /// buffered whole before they are downscaled.
+    pub fn with_max_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for ThumbnailResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let kind = self.inner.get_image_kind(href, options)?;
+        Some(thumbnail_within(&kind, self.max_size, self.max_pixels).unwrap_or(kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn downscales_keeping_layout() {
+        let gray = ImageKind::PNG(Arc::new(include_bytes!("../test_data/gray.png").to_vec()));
+        assert!(thumbnail(&gray, 1024).is_none());
+
+        let Some(ImageKind::SVG(tree)) = thumbnail(&gray, 20) else {
+            panic!("not downscaled");
+        };
+        assert_eq!(tree.size(), usvg::Size::from_wh(200.0, 200.0).unwrap());
+
+        let mut pixmap = resvg::tiny_skia::Pixmap::new(200, 200).unwrap();
+        resvg::render(
+            &tree,
+            resvg::tiny_skia::Transform::identity(),
+            &mut pixmap.as_mut(),
+        );
+        let corner = pixmap.pixel(199, 199).unwrap();
+        assert!(corner.blue() > 200 && corner.red() < 50);
+    }
+
+    #[test]
+    fn skips_images_over_the_pixel_budget() {
+        let mut data = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut data, u16::MAX, u16::MAX, &[]).unwrap();
+        let frame = gif::Frame::from_rgba(1, 1, &mut [255, 0, 0, 255]);
+        encoder.write_frame(&frame).unwrap();
+        drop(encoder);
+        assert!(data.len() < 100);
+        assert!(thumbnail(&ImageKind::GIF(Arc::new(data)), 256).is_none());
+
+        let gray = ImageKind::PNG(Arc::new(include_bytes!("../test_data/gray.png").to_vec()));
+        assert!(thumbnail_within(&gray, 20, 200 * 200 - 1).is_none());
+        assert!(thumbnail_within(&gray, 20, 200 * 200).is_some());
+    }
+}
@@ -0,0 +1,219 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use usvg::{ImageKind, Options};
+
+use crate::HrefStringResolver;
+
+/// The estimated size of a node, apart from its path data and image data.
+const NODE_SIZE: usize = 256;
+
+/// Estimate the memory used by a parsed [`Tree`](`usvg::Tree`), in bytes.
+///
+/// This is a heuristic from the number of nodes, the path data and the encoded raster data, and
+/// includes the subtrees of clip paths, masks, patterns, filters and nested SVG images.
+pub fn estimate_tree_size(tree: &usvg::Tree) -> usize {
+    group_size(tree.root())
+}
+
+fn group_size(group: &usvg::Group) -> usize {
+    NODE_SIZE + group.children().iter().map(node_size).sum::<usize>()
+}
+
+fn node_size(node: &usvg::Node) -> usize {
+    let mut size = match node {
+        usvg::Node::Group(group) => group_size(group),
+        usvg::Node::Path(path) => {
+            let data = path.data();
+            NODE_SIZE + data.points().len() * 8 + data.verbs().len()
+        }
+        usvg::Node::Image(image) => match image.kind() {
+            ImageKind::JPEG(data)
+            | ImageKind::PNG(data)
+            | ImageKind::GIF(data)
+            | ImageKind::WEBP(data) => NODE_SIZE + data.len(),
+            ImageKind::SVG(_) => NODE_SIZE,
+        },
+        usvg::Node::Text(_) => NODE_SIZE,
+    };
+    node.subroots(|root| size += group_size(root));
+    size
+}
+
+/// A budget of estimated tree memory shared by the nested SVG images of a render.
+///
+/// Clones share the budget. Use a new budget for every render, or [`reset`](`Self::reset`) it
+/// between renders.
+#[derive(Debug, Clone)]
+pub struct TreeSizeBudget {
+    limit: usize,
+    used: Arc<AtomicUsize>,
+}
+
+impl TreeSizeBudget {
+    /// Create a new `TreeSizeBudget` allowing `limit` bytes of estimated tree memory.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: Arc::default(),
+        }
+    }
+
+    /// The estimated bytes used so far.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// Restore the full budget.
+    pub fn reset(&self) {
+        self.used.store(0, Ordering::SeqCst);
+    }
+
+    /// Spend `size` bytes, returning `false` without spending them if they don't fit.
+    fn try_spend(&self, size: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(size).filter(|total| *total <= self.limit)
+            })
+            .is_ok()
+    }
+}
+
+/// A resolver that rejects nested SVG images whose parsed tree is too large.
+///
+/// Even a small SVG file can expand into a huge tree. The size is estimated with
+/// [`estimate_tree_size`], and checked against the limit for a single image and against a
+/// [`TreeSizeBudget`] for the whole render. Raster images are passed through.
+///
+/// ```
+/// use usvg_remote_resolvers::{DefaultResolver, HrefStringResolver};
+/// use usvg_remote_resolvers::tree_size::{TreeSizeBudget, TreeSizeLimitResolver};
+///
+/// let resolver = TreeSizeLimitResolver::new(DefaultResolver)
+///     .with_max_size(16 << 20)
+///     .with_budget(TreeSizeBudget::new(64 << 20));
+/// let mut options = usvg::Options::default();
+/// resolver.set_into_options(&mut options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TreeSizeLimitResolver<T> {
+    inner: T,
+    max_size: Option<usize>,
+    budget: Option<TreeSizeBudget>,
+}
+
+impl<T> TreeSizeLimitResolver<T> {
+    /// Create a new `TreeSizeLimitResolver` without limits.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            max_size: None,
+            budget: None,
+        }
+    }
+
+    /// Reject nested SVG images whose tree is estimated to be larger than `bytes`.
+    pub fn with_max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Reject nested SVG images once their trees would exceed `budget` in total.
+    pub fn with_budget(mut self, budget: TreeSizeBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Get the inner resolver.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T: HrefStringResolver<'a>> HrefStringResolver<'a> for TreeSizeLimitResolver<T> {
+    fn is_target(&self, href: &str) -> bool {
+        self.inner.is_target(href)
+    }
+    fn explain_into(&self, trace: &mut crate::explain::ResolutionTrace) {
+        trace.wrap::<Self>(&self.inner);
+    }
+    fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+        let kind = self.inner.get_image_kind(href, options)?;
+        if let ImageKind::SVG(tree) = &kind {
+            let size = estimate_tree_size(tree);
+            if self.max_size.is_some_and(|max| size > max) {
+                crate::utils::log_warn!(
+                    "SVG tree of '{}' is too large ({} bytes)",
+                    crate::redact::redact(href),
+                    size
+                );
+                return None;
+            }
+            if let Some(budget) = &self.budget {
+                if !budget.try_spend(size) {
+                    crate::utils::log_warn!(
+                        "SVG tree of '{}' exceeds the render budget ({} bytes)",
+                        crate::redact::redact(href),
+                        size
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SvgResolver;
+
+    impl HrefStringResolver<'_> for SvgResolver {
+        fn is_target(&self, _: &str) -> bool {
+            true
+        }
+        fn get_image_kind(&self, href: &str, options: &Options) -> Option<ImageKind> {
+            let paths = "<path d='M0 0 L10 10 L20 0 Z'/>".repeat(href.len());
+            let svg = format!("<svg xmlns='http://www.w3.org/2000/svg'>{paths}</svg>");
+            Some(ImageKind::SVG(usvg::Tree::from_str(&svg, options).ok()?))
+        }
+    }
+
+    #[test]
+    fn estimates_size() {
+        let ImageKind::SVG(small) = SvgResolver
+            .get_image_kind("a", &Options::default())
+            .unwrap()
+        else {
+            unreachable!()
+        };
+        let ImageKind::SVG(large) = SvgResolver
+            .get_image_kind("aaaaaaaaaa", &Options::default())
+            .unwrap()
+        else {
+            unreachable!()
+        };
+        assert!(estimate_tree_size(&large) > estimate_tree_size(&small) * 5);
+    }
+
+    #[test]
+    fn limits_tree_size() {
+        let options = Options::default();
+        let budget = TreeSizeBudget::new(4096);
+        let resolver = TreeSizeLimitResolver::new(SvgResolver)
+            .with_max_size(2048)
+            .with_budget(budget.clone());
+
+        assert!(resolver.get_image_kind(&"a".repeat(20), &options).is_none());
+        assert_eq!(budget.used(), 0);
+        assert!(resolver.get_image_kind("a", &options).is_some());
+        assert!(budget.used() > 0);
+        while resolver.get_image_kind("a", &options).is_some() {}
+        assert!(budget.used() <= 4096);
+
+        budget.reset();
+        assert!(resolver.get_image_kind("a", &options).is_some());
+    }
+}
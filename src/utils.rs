@@ -10,12 +10,220 @@ macro_rules! log_warn {
 }
 pub(crate) use log_warn;
 
-/// Check if the `href` is a remote URL (http or https).
-pub fn is_remote_url(href: &str) -> bool {
-    href.starts_with("https://") || href.starts_with("http://")
+/// Encode `s` as a JSON string literal, including the quotes.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len() + 2);
+    encoded.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => encoded.push_str("\\\""),
+            '\\' => encoded.push_str("\\\\"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            c if u32::from(c) < 0x20 => encoded.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => encoded.push(c),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+/// Expand the `{href}`, `{stem}`, `{ext}` and `{suffix}` placeholders of `template` for `href`.
+///
+/// `{stem}` is the `href` without its query, fragment and file extension, `{ext}` the file
+/// extension without the leading dot, and `{suffix}` the query and fragment, or empty strings.
+pub(crate) fn expand_href_template(template: &str, href: &str) -> String {
+    let suffix_start = href.find(['?', '#']).unwrap_or(href.len());
+    let (path, suffix) = href.split_at(suffix_start);
+    let (stem, ext) = path
+        .rsplit_once('.')
+        .filter(|(_, ext)| !ext.contains('/'))
+        .unwrap_or((path, ""));
+
+    template
+        .replace("{href}", href)
+        .replace("{stem}", stem)
+        .replace("{ext}", ext)
+        .replace("{suffix}", suffix)
+}
+
+/// Get the file extension of the last path segment of `href`, lowercased and without the dot.
+///
+/// URLs are parsed so their query, fragment and host are ignored, as in
+/// `https://cdn.example.com/img.png?v=123#frag`; other `href`s are taken for file paths, up to a
+/// `?` or `#`.
+pub(crate) fn href_extension(href: &str) -> Option<String> {
+    let path = match url::Url::parse(href) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => href
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    };
+    let file = path.rsplit(['/', '\\']).next()?;
+    let (_, ext) = file.rsplit_once('.')?;
+    Some(ext.to_ascii_lowercase())
+}
+
+/// Returns `true` if the response body `body` of `href` is empty, logging it.
+///
+/// Servers answer with no body for `204 No Content`, and sometimes for a `200 OK`. It is never an
+/// image, and must not be cached in place of one.
+#[cfg(any(
+    feature = "reqwest",
+    feature = "reqwest_blocking",
+    feature = "reqwest_http_cache",
+    feature = "hyper",
+    feature = "s3"
+))]
+pub(crate) fn is_empty_body(href: &str, body: &[u8]) -> bool {
+    if body.is_empty() {
+        log_warn!("empty response body for '{}'", crate::redact::redact(href));
+    }
+    body.is_empty()
+}
+
+/// Encode `data` with the standard base64 alphabet, with padding.
+#[cfg(any(feature = "http_auth", feature = "thumbnail"))]
+pub(crate) fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The default pixel budget of the decoders of [`frame`](`crate::frame`) and
+/// [`thumbnail`](`crate::thumbnail`): 64 megapixels, e.g. 8192x8192.
+#[cfg(any(feature = "frame_selection", feature = "thumbnail"))]
+pub(crate) const DEFAULT_MAX_PIXELS: u64 = 64 * 1024 * 1024;
+
+/// Get the size in bytes of a `width`x`height` buffer of `channels` bytes per pixel, or `None`
+/// if it exceeds `max_pixels` pixels, logging it.
+///
+/// Image headers declare their size in a few bytes, e.g. a 78 byte GIF can claim a 65535x65535
+/// screen, so the size is checked before allocating the buffer of decoded pixels.
+#[cfg(any(feature = "frame_selection", feature = "thumbnail"))]
+pub(crate) fn pixel_buffer_size(
+    width: u32,
+    height: u32,
+    channels: usize,
+    max_pixels: u64,
+) -> Option<usize> {
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels > max_pixels {
+        log_warn!(
+            "image of {}x{} pixels exceeds {} pixels",
+            width,
+            height,
+            max_pixels
+        );
+        return None;
+    }
+    usize::try_from(pixels).ok()?.checked_mul(channels)
+}
+
+/// Create a copy of `options` whose resolvers forward to the ones in `options`.
+///
+/// [`Options`](`usvg::Options`) is not `Clone` because of its boxed resolvers, so the copy borrows
+/// them instead. This is used to tweak options for a single nested parse.
+pub(crate) fn borrow_options<'b>(options: &'b usvg::Options) -> usvg::Options<'b> {
+    usvg::Options {
+        resources_dir: options.resources_dir.clone(),
+        dpi: options.dpi,
+        font_family: options.font_family.clone(),
+        font_size: options.font_size,
+        languages: options.languages.clone(),
+        shape_rendering: options.shape_rendering,
+        text_rendering: options.text_rendering,
+        image_rendering: options.image_rendering,
+        default_size: options.default_size,
+        image_href_resolver: usvg::ImageHrefResolver {
+            resolve_data: Box::new(|mime, data, opts| {
+                (options.image_href_resolver.resolve_data)(mime, data, opts)
+            }),
+            resolve_string: Box::new(|href, opts| {
+                (options.image_href_resolver.resolve_string)(href, opts)
+            }),
+        },
+        font_resolver: usvg::FontResolver {
+            select_font: Box::new(|font, db| (options.font_resolver.select_font)(font, db)),
+            select_fallback: Box::new(|c, used, db| {
+                (options.font_resolver.select_fallback)(c, used, db)
+            }),
+        },
+        fontdb: options.fontdb.clone(),
+        style_sheet: options.style_sheet.clone(),
+    }
+}
+
+/// Read the body of `resp` chunk by chunk, failing as soon as it exceeds `max_size` bytes, so an
+/// oversized body is never buffered whole. Failures are logged.
+#[cfg(any(feature = "reqwest", feature = "reqwest_http_cache"))]
+pub(crate) async fn read_body(
+    mut resp: reqwest::Response,
+    href: &str,
+    max_size: Option<usize>,
+) -> Option<Vec<u8>> {
+    let read_error = |e: reqwest::Error| {
+        log_warn!(
+            "failed to read response body for '{}': {}",
+            crate::redact::redact(href),
+            crate::redact::redact_error(&e, href)
+        );
+    };
+    let too_large = |max_size: usize| {
+        log_warn!(
+            "response body for '{}' exceeds {} bytes",
+            crate::redact::redact(href),
+            max_size
+        );
+    };
+    let Some(max_size) = max_size else {
+        return resp
+            .bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(read_error)
+            .ok();
+    };
+    let len = resp.content_length();
+    if len.is_some_and(|len| len > max_size as u64) {
+        too_large(max_size);
+        return None;
+    }
+    let mut body = Vec::with_capacity(len.unwrap_or_default() as usize);
+    loop {
+        match resp.chunk().await {
+            Ok(Some(chunk)) if body.len() + chunk.len() > max_size => {
+                too_large(max_size);
+                return None;
+            }
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            Ok(None) => return Some(body),
+            Err(e) => {
+                read_error(e);
+                return None;
+            }
+        }
+    }
 }
 
 /// Represents the image format types supported by usvg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ImageKindTypes {
     /// JPEG image format.
     Jpeg,
@@ -32,27 +240,98 @@ pub enum ImageKindTypes {
 impl ImageKindTypes {
     /// Detect the image type from the HTTP `Content-Type` header or the file extension in the `href`.
     ///
-    /// The `content_type` is checked first. If it is `None` or not recognized,
-    /// the file extension of the `href` is used as a fallback.
+    /// The `content_type` is checked first, ignoring its parameters and case, as in
+    /// `image/svg+xml; charset=utf-8`. If it is `None` or not recognized,
+    /// the file extension of the `href` is used as a fallback, ignoring its case and, for URLs,
+    /// the query and fragment.
     pub fn get_image_type(content_type: Option<&str>, href: &str) -> Option<Self> {
-        let kind = match content_type.unwrap_or_default() {
-            "image/png" => Self::Png,
-            "image/jpeg" => Self::Jpeg,
-            "image/webp" => Self::Webp,
-            "image/gif" => Self::Gif,
-            "image/svg+xml" => Self::Svg,
-            _ => match href.rsplit_once('.')?.1 {
-                "png" => Self::Png,
-                "jpg" | "jpeg" => Self::Jpeg,
-                "webp" => Self::Webp,
-                "gif" => Self::Gif,
-                "svg" => Self::Svg,
-                _ => return None,
-            },
+        content_type
+            .and_then(Self::from_content_type)
+            .or_else(|| Self::from_extension(&href_extension(href)?))
+    }
+
+    /// Detect the image type from a lowercase file extension like `png`.
+    pub(crate) fn from_extension(ext: &str) -> Option<Self> {
+        let kind = match ext {
+            "png" => Self::Png,
+            "jpg" | "jpeg" => Self::Jpeg,
+            "webp" => Self::Webp,
+            "gif" => Self::Gif,
+            "svg" => Self::Svg,
+            _ => return None,
         };
         Some(kind)
     }
 
+    /// Detect the image type from a MIME type like `image/png`.
+    pub(crate) fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime: mime::Mime = content_type.trim().parse().ok()?;
+        if mime.type_() != mime::IMAGE {
+            return None;
+        }
+        let kind = match mime.subtype().as_str() {
+            "png" | "x-png" => Self::Png,
+            "jpeg" | "jpg" | "pjpeg" => Self::Jpeg,
+            "webp" => Self::Webp,
+            "gif" => Self::Gif,
+            "svg" => Self::Svg,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Detect the image type from the magic bytes at the start of `data`.
+    ///
+    /// Anything starting with `<`, after whitespace, is taken for an SVG image, which fails to
+    /// parse later if it isn't one.
+    ///
+    /// ```
+    /// use usvg_remote_resolvers::ImageKindTypes;
+    ///
+    /// assert!(matches!(ImageKindTypes::from_bytes(b"GIF89a..."), Some(ImageKindTypes::Gif)));
+    /// assert!(ImageKindTypes::from_bytes(b"%PDF-1.7").is_none());
+    /// ```
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let kind = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Self::Png
+        } else if data.starts_with(b"\xff\xd8\xff") {
+            Self::Jpeg
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Self::Gif
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            Self::Webp
+        } else if data.trim_ascii_start().starts_with(b"<") {
+            Self::Svg
+        } else {
+            return None;
+        };
+        Some(kind)
+    }
+
+    /// Returns `true` if `content_type` says nothing about the format, so the body should be
+    /// sniffed: no content type, or a generic binary one like `application/octet-stream`.
+    pub(crate) fn is_generic(content_type: Option<&str>) -> bool {
+        content_type.is_none_or(|content_type| {
+            content_type.trim().parse::<mime::Mime>().is_ok_and(|mime| {
+                matches!(
+                    mime.essence_str(),
+                    "application/octet-stream" | "binary/octet-stream"
+                )
+            })
+        })
+    }
+
+    /// Detect the image type of a response body `data` from `content_type` or `href` like
+    /// [`get_image_type`](Self::get_image_type), or from its magic bytes if neither is recognized
+    /// and `content_type` is generic.
+    ///
+    /// See [`detect::detect`](crate::detect::detect) for why the type could not be detected.
+    pub fn detect(content_type: Option<&str>, href: &str, data: &[u8]) -> Option<Self> {
+        crate::detect::detect(content_type, href, data)
+            .ok()
+            .map(|detected| detected.image_type)
+    }
+
     /// Convert image data into a [`usvg::ImageKind`] based on this image type.
     ///
     /// For SVG images, the data is parsed into a [`usvg::Tree`] using the given `options`.
@@ -74,4 +353,73 @@ impl ImageKindTypes {
         };
         Some(ik)
     }
+
+    /// Convert image data fetched from `href` into a [`usvg::ImageKind`] based on this image type.
+    ///
+    /// Unlike [`into_image_kind`](Self::into_image_kind), relative `href`s within SVG images are
+    /// resolved against `href` if it is an absolute URL, as a browser would.
+    pub fn into_image_kind_from(
+        self,
+        vec: Arc<Vec<u8>>,
+        href: &str,
+        options: &usvg::Options,
+    ) -> Option<usvg::ImageKind> {
+        match (self, url::Url::parse(href)) {
+            (Self::Svg, Ok(base)) => {
+                let nested = crate::base_url::nested_options(options, base);
+                Self::Svg.into_image_kind(vec, &nested)
+            }
+            (image_type, _) => image_type.into_image_kind(vec, options),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_content_type_variants() {
+        let detect = |content_type| ImageKindTypes::get_image_type(Some(content_type), "image");
+        assert!(matches!(
+            detect("image/svg+xml; charset=utf-8"),
+            Some(ImageKindTypes::Svg)
+        ));
+        assert!(matches!(detect("Image/PNG"), Some(ImageKindTypes::Png)));
+        assert!(matches!(detect(" image/jpeg "), Some(ImageKindTypes::Jpeg)));
+        assert!(matches!(
+            detect("image/webp;q=0.9"),
+            Some(ImageKindTypes::Webp)
+        ));
+        assert!(matches!(detect("IMAGE/GIF"), Some(ImageKindTypes::Gif)));
+        assert!(detect("text/html; charset=utf-8").is_none());
+        assert!(detect("image/avif").is_none());
+        assert!(detect("image/").is_none());
+
+        assert!(matches!(
+            ImageKindTypes::get_image_type(Some("text/plain"), "a.png"),
+            Some(ImageKindTypes::Png)
+        ));
+        assert!(ImageKindTypes::is_generic(Some(
+            "Application/Octet-Stream; x=y"
+        )));
+        assert!(!ImageKindTypes::is_generic(Some("text/plain")));
+    }
+
+    #[test]
+    fn extension_from_url_path() {
+        let ext = |href| href_extension(href);
+        assert_eq!(
+            ext("https://cdn.example.com/img.PNG?v=1.2#frag.svg").as_deref(),
+            Some("png")
+        );
+        assert_eq!(ext("https://cdn.example.com/a.b/img").as_deref(), None);
+        assert_eq!(ext("https://xn--bcher-kva.example").as_deref(), None);
+        assert_eq!(ext("./test_data/gray.png?x").as_deref(), Some("png"));
+        assert_eq!(ext("C:\\images\\a.jpg").as_deref(), Some("jpg"));
+        assert!(matches!(
+            ImageKindTypes::get_image_type(None, "https://example.com/a.svg?v=1.0"),
+            Some(ImageKindTypes::Svg)
+        ));
+    }
 }